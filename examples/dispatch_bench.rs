@@ -0,0 +1,104 @@
+// Instructions per second with and without the predecoded dispatch cache
+//
+// Runs the same loop of loads, stores, arithmetic, shifts, skips and a JSX
+// subroutine call twice: a step at a time through the instruction table, and
+// with Cpu::run through the dispatch cache.  Prints the rate of each and
+// fails if the runs end in a different machine state.
+//
+//      cargo run --release --example dispatch_bench [instructions]
+
+use std::env;
+use std::process;
+use std::time::Instant;
+
+use rustheon::cpu::{Cpu, Memory, Mode};
+use rustheon::dispatch::DispatchCache;
+
+const ORIGIN:usize = 0x0100;
+const INSTRUCTIONS:u64 = 20_000_000;
+
+const PROGRAM:[u16; WORDS] = [
+    0x8115,              // 0100  LOOP   LDW CNT
+    0xA116,              // 0101         ADD ONE
+    0x7115,              // 0102         STW CNT
+    0x210B,              // 0103         JSX WORK
+    0x8115,              // 0104         LDW CNT
+    0xE117,              // 0105         AND MASK
+    0x0800,              // 0106         SAZ
+    0x1100,              // 0107         JMP LOOP
+    0x0110,              // 0108         CLR
+    0x7118,              // 0109         STW ACC
+    0x1100,              // 010A         JMP LOOP
+    0x6119,              // 010B  WORK   STX SAVE
+    0x8118,              // 010C         LDW ACC
+    0xA115,              // 010D         ADD CNT
+    0x0911,              // 010E         SLA 1
+    0x0A01,              // 010F         SRL 1
+    0x0120,              // 0110         CMP
+    0x0130,              // 0111         INV
+    0x7118,              // 0112         STW ACC
+    0x9119,              // 0113         LDX SAVE
+    0x1800,              // 0114         JMP 0,X
+    0x0000,              // 0115  CNT    DATA 0
+    0x0001,              // 0116  ONE    DATA 1
+    0x00FF,              // 0117  MASK   DATA 0x00FF
+    0x0000,              // 0118  ACC    DATA 0
+    0x0000,              // 0119  SAVE   DATA 0
+];
+const WORDS:usize = 26;
+
+// run the loop for a number of instructions, returning the final state and
+// the instructions per second
+#[derive(Clone, Copy)]
+enum Path {
+    Table,                                      // OPCODES decode table
+    Cache,                                      // Cpu::run through the dispatch cache
+}
+
+fn run(instructions: u64, path: Path) -> (Cpu, Box<Memory>, f64) {
+    let mut cpu = Cpu::new();
+    let mut memory = Box::new(Memory::new());
+    for (i, word) in PROGRAM.iter().enumerate() {
+        memory.core[ORIGIN + i] = *word as i16;
+    }
+    cpu.pcr = ORIGIN as u16;
+    cpu.mode = Mode::RUN;
+    let start = Instant::now();
+    match path {
+        Path::Table => while cpu.mode == Mode::RUN && cpu.instructions < instructions {
+            cpu.step(&mut memory);
+        },
+        Path::Cache => {
+            cpu.dispatch = Some(DispatchCache::new());
+            cpu.run(&mut memory, instructions);
+        },
+    }
+    let rate = cpu.instructions as f64 / start.elapsed().as_secs_f64();
+    (cpu, memory, rate)
+}
+
+fn main() {
+    let instructions = match env::args().nth(1) {
+        Some(arg) => arg.parse().unwrap_or_else(|_| {
+            eprintln!("usage: dispatch_bench [instructions]");
+            process::exit(2);
+        }),
+        None => INSTRUCTIONS,
+    };
+    let (table, table_memory, table_rate) = run(instructions, Path::Table);
+    println!("step, decode table  {:>12.0} instructions/second", table_rate);
+    let (cpu, memory, rate) = run(instructions, Path::Cache);
+    println!("run, dispatch cache {:>12.0} instructions/second  ({:.2}x)", rate, rate / table_rate);
+    if let Some(cache) = &cpu.dispatch {
+        println!("cache hits {}  decodes {}", cache.hits, cache.decodes);
+    }
+    let same = table.acr == cpu.acr && table.ixr == cpu.ixr && table.status == cpu.status
+        && table.pcr == cpu.pcr && table.cycles == cpu.cycles
+        && table.instructions == cpu.instructions && table_memory.core == memory.core;
+    if !same {
+        eprintln!("final state differs: ACR {:04X}/{:04X} IXR {:04X}/{:04X} PCR {:04X}/{:04X} cycles {}/{}",
+                  table.acr, cpu.acr, table.ixr, cpu.ixr, table.pcr, cpu.pcr, table.cycles, cpu.cycles);
+        process::exit(1);
+    }
+    println!("final state identical after {} instructions", table.instructions);
+}
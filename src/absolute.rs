@@ -0,0 +1,217 @@
+// Absolute binary paper tape format
+//
+// This is the emulator's own format, modelled on the block structured
+// absolute loaders of the period.  No 703 loader document was at hand, so
+// the frame layout, block size and checksum below are not taken from one,
+// and scanned Raytheon tapes in another layout need converting to it.
+//
+// An absolute tape is a string of 8 bit frames.  Blank frames are leader and
+// trailer and may appear before, between and after blocks.  Every block starts
+// with a start-of-block frame followed by 16 bit words punched high byte first:
+//
+//      SOB frame           0x81
+//      word count          n, 1..=MAX_BLOCK_WORDS (0 marks the transfer record)
+//      load address        first core address of the block
+//      n data words
+//      checksum            two's complement of the sum of count, address and data
+//
+// The transfer record is a block with a word count of zero and no data words.
+// Its address is where execution starts after loading.  If bit 15 of the
+// transfer address is set the loader halts instead of starting the program.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::cpu::Memory;
+
+pub const SOB_FRAME:u8 = 0x81;              // start of block frame
+pub const MAX_BLOCK_WORDS:usize = 64;       // largest block punched or accepted
+pub const LEADER_FRAMES:usize = 32;         // blank frames punched as leader and trailer
+pub const NO_TRANSFER:u16 = 0x8000;         // transfer address flag for "halt after load"
+const CORE_WORDS:usize = 32_768;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub address: u16,                       // core address of the first word
+    pub words: Vec<i16>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct AbsoluteTape {
+    pub blocks: Vec<Block>,
+    pub transfer: Option<u16>,              // transfer record address, if one was punched
+}
+
+#[derive(Debug)]
+pub enum AbsError {
+    Io(io::Error),
+    UnexpectedEnd { block: usize, offset: usize },
+    BadFrame { block: usize, offset: usize, frame: u8 },
+    BadWordCount { block: usize, offset: usize, count: u16 },
+    AddressRange { block: usize, address: u16, count: u16 },
+    Checksum { block: usize, offset: usize, address: u16, expected: u16, found: u16 },
+    DataAfterTransfer { offset: usize },
+}
+
+impl fmt::Display for AbsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AbsError::Io(err) => write!(f, "absolute tape i/o error: {}", err),
+            AbsError::UnexpectedEnd { block, offset } =>
+                write!(f, "tape ends inside block {} at frame offset {}", block, offset),
+            AbsError::BadFrame { block, offset, frame } =>
+                write!(f, "expected start of block {} but found frame {:02X} at offset {}",
+                       block, frame, offset),
+            AbsError::BadWordCount { block, offset, count } =>
+                write!(f, "block {} at offset {} has word count {} (maximum {})",
+                       block, offset, count, MAX_BLOCK_WORDS),
+            AbsError::AddressRange { block, address, count } =>
+                write!(f, "block {} loads {} words at {:04X}, past the end of core",
+                       block, count, address),
+            AbsError::Checksum { block, offset, address, expected, found } =>
+                write!(f, "checksum error in block {} (load address {:04X}) at offset {}: expected {:04X} found {:04X}",
+                       block, address, offset, expected, found),
+            AbsError::DataAfterTransfer { offset } =>
+                write!(f, "data block at offset {} follows the transfer record", offset),
+        }
+    }
+}
+
+impl std::error::Error for AbsError {}
+
+impl From<io::Error> for AbsError {
+    fn from(err: io::Error) -> Self {
+        AbsError::Io(err)
+    }
+}
+
+// checksum that makes count + address + data + checksum sum to zero
+pub fn checksum(count: u16, address: u16, words: &[i16]) -> u16 {
+    let mut sum = count.wrapping_add(address);
+    for word in words {
+        sum = sum.wrapping_add(*word as u16);
+    }
+    sum.wrapping_neg()
+}
+
+impl AbsoluteTape {
+    // parse the frames of an absolute tape, checking every block
+    pub fn parse(frames: &[u8]) -> Result<AbsoluteTape, AbsError> {
+        let mut tape = AbsoluteTape::default();
+        let mut offset = 0;
+        let mut block = 0;
+        loop {
+            while offset < frames.len() && frames[offset] == 0 {
+                offset += 1;                        // skip leader
+            }
+            if offset >= frames.len() {
+                return Ok(tape);
+            }
+            let start = offset;
+            if frames[offset] != SOB_FRAME {
+                return Err(AbsError::BadFrame { block, offset, frame: frames[offset] });
+            }
+            offset += 1;
+            let count = read_word(frames, &mut offset, block)?;
+            let address = read_word(frames, &mut offset, block)?;
+            if count as usize > MAX_BLOCK_WORDS {
+                return Err(AbsError::BadWordCount { block, offset: start, count });
+            }
+            if count != 0 && address as usize + count as usize > CORE_WORDS {
+                return Err(AbsError::AddressRange { block, address, count });
+            }
+            let mut words = Vec::with_capacity(count as usize);
+            for _ in 0..count {
+                words.push(read_word(frames, &mut offset, block)? as i16);
+            }
+            let expected = checksum(count, address, &words);
+            let sum_offset = offset;
+            let found = read_word(frames, &mut offset, block)?;
+            if found != expected {
+                return Err(AbsError::Checksum { block, offset: sum_offset, address, expected, found });
+            }
+            if tape.transfer.is_some() {
+                return Err(AbsError::DataAfterTransfer { offset: start });
+            }
+            if count == 0 {
+                tape.transfer = Some(address);
+            } else {
+                tape.blocks.push(Block { address, words });
+            }
+            block += 1;
+        }
+    }
+
+    // punch the tape, with leader and trailer
+    pub fn punch(&self) -> Vec<u8> {
+        let mut frames = vec![0u8; LEADER_FRAMES];
+        for block in &self.blocks {
+            for (i, chunk) in block.words.chunks(MAX_BLOCK_WORDS).enumerate() {
+                let address = block.address.wrapping_add((i * MAX_BLOCK_WORDS) as u16);
+                punch_block(&mut frames, address, chunk);
+            }
+        }
+        if let Some(address) = self.transfer {
+            punch_block(&mut frames, address, &[]);
+        }
+        frames.extend_from_slice(&[0u8; LEADER_FRAMES]);
+        frames
+    }
+
+    // capture core from start to end inclusive as a tape, with no blocks
+    // when the range is empty
+    pub fn from_memory(memory: &Memory, start: u16, end: u16, transfer: Option<u16>) -> AbsoluteTape {
+        let end = end.min((CORE_WORDS - 1) as u16);
+        if start > end {
+            return AbsoluteTape { blocks: Vec::new(), transfer };
+        }
+        let words = memory.core[start as usize..=end as usize].to_vec();
+        AbsoluteTape { blocks: vec![Block { address: start, words }], transfer }
+    }
+
+    // deposit every block into core, returning the transfer address
+    pub fn load(&self, memory: &mut Memory) -> Option<u16> {
+        for block in &self.blocks {
+            let base = block.address as usize;
+            memory.write_block(base, &block.words);
+        }
+        self.transfer
+    }
+}
+
+fn read_word(frames: &[u8], offset: &mut usize, block: usize) -> Result<u16, AbsError> {
+    if *offset + 2 > frames.len() {
+        return Err(AbsError::UnexpectedEnd { block, offset: frames.len() });
+    }
+    let word = (frames[*offset] as u16) << 8 | frames[*offset + 1] as u16;
+    *offset += 2;
+    Ok(word)
+}
+
+fn punch_block(frames: &mut Vec<u8>, address: u16, words: &[i16]) {
+    let count = words.len() as u16;
+    frames.push(SOB_FRAME);
+    frames.extend_from_slice(&count.to_be_bytes());
+    frames.extend_from_slice(&address.to_be_bytes());
+    for word in words {
+        frames.extend_from_slice(&word.to_be_bytes());
+    }
+    frames.extend_from_slice(&checksum(count, address, words).to_be_bytes());
+}
+
+// load an absolute tape image file into core, returning the transfer address
+pub fn load_file<P: AsRef<Path>>(path: P, memory: &mut Memory) -> Result<Option<u16>, AbsError> {
+    let frames = fs::read(path)?;
+    let tape = AbsoluteTape::parse(&frames)?;
+    Ok(tape.load(memory))
+}
+
+// punch core from start to end inclusive to an absolute tape image file
+pub fn punch_file<P: AsRef<Path>>(path: P, memory: &Memory, start: u16, end: u16,
+                                  transfer: Option<u16>) -> Result<(), AbsError> {
+    let tape = AbsoluteTape::from_memory(memory, start, end, transfer);
+    fs::write(path, tape.punch())?;
+    Ok(())
+}
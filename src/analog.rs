@@ -0,0 +1,389 @@
+// Analog subsystem, a multiplexed A/D converter and a bank of D/A converters
+//
+// A/D registers
+//      DOT +0  channel       select multiplexer channel 0..255 and start a conversion
+//      DOT +1  control       INT_ENABLE interrupts at end of conversion
+//      DIN +0  data          the converted value, reading it clears END_OF_CONVERSION
+//      DIN +1  status        END_OF_CONVERSION, BUSY, OVERRANGE, channel in bits 7..0
+//
+// Inputs are volts.  The converter spans +/- full scale and returns a left
+// justified two's complement value, so full scale positive reads 7FFF less
+// the bits below the converter's resolution.  Inputs beyond full scale clamp
+// and set OVERRANGE.
+//
+// Each channel's voltage comes from a Source evaluated at the emulated time
+// the conversion completes: a fixed value (which a plant model can update),
+// a Rust closure of time, or a time series read from a CSV file.
+//
+// D/A registers
+//      DOT +n  channel n     output value, same scaling as the A/D
+//      DIN +n  channel n     the value last written
+//
+// Every D/A update is recorded with its emulated time so the commanded
+// outputs can be exported as CSV for plotting or compared between runs.
+
+use std::any::Any;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::cpu::{cycles_to_seconds, Memory};
+use crate::io::Device;
+
+pub const ADC_ADDRESS:u8 = 0x20;                // standard bus addresses
+pub const DAC_ADDRESS:u8 = 0x28;
+pub const DAC_CHANNELS:u8 = 8;
+
+pub const END_OF_CONVERSION:i16 = 0x8000u16 as i16;     // status bits
+pub const BUSY:i16 = 0x4000;
+pub const OVERRANGE:i16 = 0x2000;
+
+pub const INT_ENABLE:i16 = 0x0002;              // control bit
+
+pub const CONVERSION_CYCLES:u64 = 23;           // 40 us including multiplexer settling
+pub const FULL_SCALE:f64 = 10.0;                // volts
+pub const RESOLUTION:u32 = 12;                  // bits including sign
+
+// piecewise linear samples of one signal
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TimeSeries {
+    points: Vec<(f64, f64)>,                    // (seconds, value) in time order
+}
+
+impl TimeSeries {
+    pub fn new(mut points: Vec<(f64, f64)>) -> Self {
+        points.sort_by(|a, b| a.0.total_cmp(&b.0));
+        TimeSeries { points }
+    }
+
+    // value at time t, holding the first and last samples outside the series
+    pub fn at(&self, t: f64) -> f64 {
+        let Some(first) = self.points.first() else { return 0.0 };
+        if t <= first.0 {
+            return first.1;
+        }
+        let next = self.points.partition_point(|p| p.0 <= t);
+        if next >= self.points.len() {
+            return self.points[self.points.len() - 1].1;
+        }
+        let (t0, v0) = self.points[next - 1];
+        let (t1, v1) = self.points[next];
+        v0 + (v1 - v0) * (t - t0) / (t1 - t0)
+    }
+}
+
+#[derive(Debug)]
+pub enum CsvError {
+    Io(io::Error),
+    BadNumber { line: usize, column: usize, text: String },
+    ShortRow { line: usize, columns: usize, expected: usize },
+}
+
+impl fmt::Display for CsvError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CsvError::Io(err) => write!(f, "csv i/o error: {}", err),
+            CsvError::BadNumber { line, column, text } =>
+                write!(f, "line {} column {}: '{}' is not a number", line, column, text),
+            CsvError::ShortRow { line, columns, expected } =>
+                write!(f, "line {} has {} columns, expected {}", line, columns, expected),
+        }
+    }
+}
+
+impl std::error::Error for CsvError {}
+
+impl From<io::Error> for CsvError {
+    fn from(err: io::Error) -> Self {
+        CsvError::Io(err)
+    }
+}
+
+// parse "time,value,value..." rows into one series per value column.  Blank
+// lines and lines starting with # are skipped, as is a first row of headings.
+pub fn parse_csv(text: &str) -> Result<Vec<TimeSeries>, CsvError> {
+    let mut columns: Vec<Vec<(f64, f64)>> = Vec::new();
+    let mut first_row = true;
+    for (index, line) in text.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split(',').map(|field| field.trim()).collect();
+        let is_heading = first_row && fields[0].parse::<f64>().is_err();
+        if first_row {
+            columns = vec![Vec::new(); fields.len().saturating_sub(1)];
+            first_row = false;
+        }
+        if is_heading {
+            continue;
+        }
+        if fields.len() < columns.len() + 1 {
+            return Err(CsvError::ShortRow { line: line_number, columns: fields.len(), expected: columns.len() + 1 });
+        }
+        let mut numbers = Vec::with_capacity(fields.len());
+        for (column, field) in fields.iter().enumerate() {
+            match field.parse::<f64>() {
+                Ok(number) => numbers.push(number),
+                Err(_) => return Err(CsvError::BadNumber { line: line_number, column: column + 1, text: field.to_string() }),
+            }
+        }
+        for (column, series) in columns.iter_mut().enumerate() {
+            series.push((numbers[0], numbers[column + 1]));
+        }
+    }
+    Ok(columns.into_iter().map(TimeSeries::new).collect())
+}
+
+pub enum Source {
+    Value(f64),
+    Function(Box<dyn FnMut(f64) -> f64>),       // volts as a function of emulated seconds
+    Series(TimeSeries),
+}
+
+impl Source {
+    fn sample(&mut self, t: f64) -> f64 {
+        match self {
+            Source::Value(value) => *value,
+            Source::Function(function) => function(t),
+            Source::Series(series) => series.at(t),
+        }
+    }
+}
+
+pub struct AnalogInput {
+    sources: Vec<Source>,                       // indexed by channel
+    channel: u8,
+    data: i16,
+    converting: bool,
+    done_at: u64,
+    end_of_conversion: bool,
+    overrange: bool,
+    int_enable: bool,
+    pub conversion_cycles: u64,
+    pub full_scale: f64,
+    pub resolution: u32,
+}
+
+impl AnalogInput {
+    pub fn new() -> Self {
+        AnalogInput {
+            sources: Vec::new(),
+            channel: 0,
+            data: 0,
+            converting: false,
+            done_at: 0,
+            end_of_conversion: false,
+            overrange: false,
+            int_enable: false,
+            conversion_cycles: CONVERSION_CYCLES,
+            full_scale: FULL_SCALE,
+            resolution: RESOLUTION,
+        }
+    }
+
+    pub fn set_source(&mut self, channel: u8, source: Source) {
+        let channel = channel as usize;
+        while self.sources.len() <= channel {
+            self.sources.push(Source::Value(0.0));
+        }
+        self.sources[channel] = source;
+    }
+
+    // drive a channel with a fixed voltage, as a plant model does every step
+    pub fn set_value(&mut self, channel: u8, volts: f64) {
+        match self.sources.get_mut(channel as usize) {
+            Some(Source::Value(value)) => *value = volts,
+            _ => self.set_source(channel, Source::Value(volts)),
+        }
+    }
+
+    pub fn set_function<F: FnMut(f64) -> f64 + 'static>(&mut self, channel: u8, function: F) {
+        self.set_source(channel, Source::Function(Box::new(function)));
+    }
+
+    // load CSV value columns onto consecutive channels from first_channel
+    pub fn load_csv<P: AsRef<Path>>(&mut self, path: P, first_channel: u8) -> Result<usize, CsvError> {
+        let series = parse_csv(&fs::read_to_string(path)?)?;
+        let count = series.len();
+        for (i, series) in series.into_iter().enumerate() {
+            self.set_source(first_channel.wrapping_add(i as u8), Source::Series(series));
+        }
+        Ok(count)
+    }
+
+    // convert volts to the converter's output word, noting overrange
+    fn quantize(&mut self, volts: f64) -> i16 {
+        let scaled = volts / self.full_scale * 32768.0;
+        self.overrange = !(-32768.0..=32767.0).contains(&scaled) || scaled.is_nan();
+        let value = if scaled.is_nan() { 0 } else { scaled.clamp(-32768.0, 32767.0) as i32 };
+        let drop = 16 - self.resolution.clamp(1, 16);
+        ((value >> drop) << drop) as i16
+    }
+}
+
+impl Default for AnalogInput {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Device for AnalogInput {
+    fn name(&self) -> &str {
+        "analog input"
+    }
+
+    fn registers(&self) -> u8 {
+        2
+    }
+
+    fn din(&mut self, reg: u8, _now: u64) -> i16 {
+        match reg {
+            0 => {
+                self.end_of_conversion = false;
+                self.data
+            },
+            _ => {
+                let mut status = self.channel as i16;
+                if self.end_of_conversion { status |= END_OF_CONVERSION; }
+                if self.converting { status |= BUSY; }
+                if self.overrange { status |= OVERRANGE; }
+                status
+            },
+        }
+    }
+
+    fn dot(&mut self, reg: u8, value: i16, now: u64) {
+        match reg {
+            0 => {
+                self.channel = (value & 0x00FF) as u8;
+                self.converting = true;
+                self.end_of_conversion = false;
+                self.done_at = now + self.conversion_cycles;
+            },
+            _ => self.int_enable = value & INT_ENABLE != 0,
+        }
+    }
+
+    fn service(&mut self, now: u64, _memory: &mut Memory) -> bool {
+        if !self.converting || now < self.done_at {
+            return false;
+        }
+        let t = cycles_to_seconds(self.done_at);
+        let volts = match self.sources.get_mut(self.channel as usize) {
+            Some(source) => source.sample(t),
+            None => 0.0,
+        };
+        self.data = self.quantize(volts);
+        self.converting = false;
+        self.end_of_conversion = true;
+        self.int_enable
+    }
+
+    fn busy(&self) -> bool {
+        self.converting
+    }
+
+    fn reset(&mut self) {
+        self.converting = false;
+        self.end_of_conversion = false;
+        self.int_enable = false;
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OutputUpdate {
+    pub cycle: u64,                             // emulated time of the DOT
+    pub channel: u8,
+    pub value: i16,
+}
+
+pub struct AnalogOutput {
+    values: Vec<i16>,                           // indexed by channel
+    recording: Vec<OutputUpdate>,
+    pub record: bool,                           // keep a recording of updates
+    pub full_scale: f64,
+}
+
+impl AnalogOutput {
+    pub fn new(channels: u8) -> Self {
+        AnalogOutput {
+            values: vec![0; channels.max(1) as usize],
+            recording: Vec::new(),
+            record: true,
+            full_scale: FULL_SCALE,
+        }
+    }
+
+    pub fn value(&self, channel: u8) -> i16 {
+        self.values.get(channel as usize).copied().unwrap_or(0)
+    }
+
+    pub fn volts(&self, channel: u8) -> f64 {
+        self.value(channel) as f64 / 32768.0 * self.full_scale
+    }
+
+    pub fn recording(&self) -> &[OutputUpdate] {
+        &self.recording
+    }
+
+    pub fn clear_recording(&mut self) {
+        self.recording.clear();
+    }
+
+    // write the recording as seconds,cycle,channel,value,volts rows
+    pub fn write_csv<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        writeln!(out, "seconds,cycle,channel,value,volts")?;
+        for update in &self.recording {
+            writeln!(out, "{:.9},{},{},{},{:.6}",
+                     cycles_to_seconds(update.cycle), update.cycle, update.channel, update.value,
+                     update.value as f64 / 32768.0 * self.full_scale)?;
+        }
+        Ok(())
+    }
+
+    pub fn save_csv<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut file = io::BufWriter::new(fs::File::create(path)?);
+        self.write_csv(&mut file)?;
+        file.flush()
+    }
+}
+
+impl Default for AnalogOutput {
+    fn default() -> Self {
+        Self::new(DAC_CHANNELS)
+    }
+}
+
+impl Device for AnalogOutput {
+    fn name(&self) -> &str {
+        "analog output"
+    }
+
+    fn registers(&self) -> u8 {
+        self.values.len() as u8
+    }
+
+    fn din(&mut self, reg: u8, _now: u64) -> i16 {
+        self.value(reg)
+    }
+
+    fn dot(&mut self, reg: u8, value: i16, now: u64) {
+        if let Some(slot) = self.values.get_mut(reg as usize) {
+            *slot = value;
+            if self.record {
+                self.recording.push(OutputUpdate { cycle: now, channel: reg, value });
+            }
+        }
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
@@ -0,0 +1,346 @@
+// Unit tests for 703 subroutines
+//
+// A test file names the program and lists test cases.  Each case starts from
+// a freshly loaded machine, sets registers and core, calls a subroutine the
+// way a program would, with JSX, and runs until the subroutine returns to
+// the sentinel, a JSX 0,X and a HLT planted at the top of core.  Then the
+// registers and core are checked.  Results are reported in TAP, and can be
+// written as JUnit XML for CI servers.
+//
+//      image = "math.s"                # or config = "machine.toml"; paths from this file
+//      symbols = "math.sym"            # symbols of an absolute image
+//      max_instructions = 100000       # per case, the default
+//
+//      [[test]]
+//      name = "add with carry"
+//      call = "ADDC"                   # symbol or address
+//      acr = 0x7FFF                    # registers on entry; IXR holds the return
+//      status = 0
+//      memory = { CARRY = 1, "TABLE+2" = [1, 2, 3], "0x0400" = -1 }
+//      expect = { acr = 0x8000, status = 0x0100, memory = { CARRY = 0 } }
+//
+// Core locations are a symbol, a hex address or either plus or minus a
+// number.  A word is a number from -32768 to 65535.  With host calls on in the
+// configuration, a TRAP FAIL during the call fails the case too.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::config::{ConfigError, MachineConfig, MachineSection};
+use crate::cpu::{Cpu, HaltReason, Memory, Mode, ADFGBL};
+use crate::instruction::{Instruction, Opcode};
+use crate::symbols::SymbolTable;
+
+pub const MAX_INSTRUCTIONS:u64 = 100_000;
+
+#[derive(Debug)]
+pub enum TestError {
+    Io { path: PathBuf, err: io::Error },
+    Parse(toml::de::Error),
+    Config(ConfigError),
+    NoProgram,                                  // neither image nor config
+    BadLocation { test: String, text: String },
+    BadWord { test: String, value: i64 },
+}
+
+impl fmt::Display for TestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TestError::Io { path, err } => write!(f, "{}: {}", path.display(), err),
+            TestError::Parse(err) => write!(f, "test file: {}", err),
+            TestError::Config(err) => write!(f, "{}", err),
+            TestError::NoProgram => write!(f, "test file names no image or config"),
+            TestError::BadLocation { test, text } => write!(f, "{}: '{}' is not a core location", test, text),
+            TestError::BadWord { test, value } => write!(f, "{}: {} does not fit in a word", test, value),
+        }
+    }
+}
+
+impl std::error::Error for TestError {}
+
+impl From<toml::de::Error> for TestError {
+    fn from(err: toml::de::Error) -> Self {
+        TestError::Parse(err)
+    }
+}
+
+impl From<ConfigError> for TestError {
+    fn from(err: ConfigError) -> Self {
+        TestError::Config(err)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum Location {
+    Address(i64),
+    Name(String),                               // symbol, hex address, either with an offset
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum Words {
+    One(i64),
+    Many(Vec<i64>),                             // consecutive words
+}
+
+impl Words {
+    fn values(&self) -> &[i64] {
+        match self {
+            Words::One(value) => std::slice::from_ref(value),
+            Words::Many(values) => values,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Expectation {
+    pub acr: Option<i64>,
+    pub ixr: Option<i64>,
+    pub status: Option<i64>,
+    #[serde(default)]
+    pub memory: BTreeMap<String, Words>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TestCase {
+    pub name: String,
+    pub call: Location,
+    pub acr: Option<i64>,
+    pub status: Option<i64>,
+    #[serde(default)]
+    pub memory: BTreeMap<String, Words>,
+    #[serde(default)]
+    pub expect: Expectation,
+    pub max_instructions: Option<u64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TestFile {
+    pub image: Option<PathBuf>,
+    pub symbols: Option<PathBuf>,
+    pub config: Option<PathBuf>,
+    pub max_instructions: Option<u64>,
+    #[serde(default, rename = "test")]
+    pub tests: Vec<TestCase>,
+    #[serde(skip)]
+    pub directory: PathBuf,                     // relative paths start here
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestResult {
+    pub name: String,
+    pub failures: Vec<String>,                  // empty when the case passed
+    pub instructions: u64,
+    pub cycles: u64,
+}
+
+impl TestResult {
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+fn word(test: &str, value: i64) -> Result<u16, TestError> {
+    if (-0x8000..=0xFFFF).contains(&value) {
+        Ok(value as u16)
+    } else {
+        Err(TestError::BadWord { test: test.to_string(), value })
+    }
+}
+
+// "TABLE", "TABLE+2", "0x0400", "0x0400-1"
+fn location(test: &str, text: &str, symbols: Option<&SymbolTable>) -> Result<u16, TestError> {
+    let bad = || TestError::BadLocation { test: test.to_string(), text: text.to_string() };
+    let text = text.trim();
+    let split = text.rfind(['+', '-']).filter(|at| *at > 0);
+    let (base, offset) = match split {
+        Some(at) => {
+            let offset: i64 = text[at + 1..].trim().parse().map_err(|_| bad())?;
+            (text[..at].trim(), if &text[at..at + 1] == "-" { -offset } else { offset })
+        },
+        None => (text, 0),
+    };
+    let base = match base.strip_prefix("0x").or_else(|| base.strip_prefix("0X")) {
+        Some(hex) => i64::from_str_radix(hex, 16).map_err(|_| bad())?,
+        None => symbols.and_then(|symbols| symbols.lookup(base)).ok_or_else(bad)? as i64,
+    };
+    u16::try_from(base + offset).ok().filter(|address| *address < 0x8000).ok_or_else(bad)
+}
+
+impl TestFile {
+    pub fn parse(text: &str) -> Result<TestFile, TestError> {
+        Ok(toml::from_str(text)?)
+    }
+
+    pub fn load_file<P: AsRef<Path>>(path: P) -> Result<TestFile, TestError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|err| TestError::Io { path: path.to_path_buf(), err })?;
+        let mut file = TestFile::parse(&text)?;
+        file.directory = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        Ok(file)
+    }
+
+    // a path in the test file, made absolute so a configuration elsewhere can use it
+    fn resolve(&self, path: &Path) -> PathBuf {
+        std::env::current_dir().unwrap_or_default().join(&self.directory).join(path)
+    }
+
+    // the machine every case starts from
+    fn machine(&self) -> Result<MachineConfig, TestError> {
+        if let Some(config) = &self.config {
+            let mut machine = MachineConfig::load_file(self.resolve(config))?;
+            machine.machine.image = self.image.as_ref().map(|image| self.resolve(image)).or(machine.machine.image);
+            machine.machine.symbols = self.symbols.as_ref().map(|symbols| self.resolve(symbols)).or(machine.machine.symbols);
+            return Ok(machine);
+        }
+        if self.image.is_none() {
+            return Err(TestError::NoProgram);
+        }
+        Ok(MachineConfig {
+            machine: MachineSection { image: self.image.clone(), symbols: self.symbols.clone(), ..Default::default() },
+            directory: self.directory.clone(),
+            ..Default::default()
+        })
+    }
+
+    // run every case, in order
+    pub fn run(&self) -> Result<Vec<TestResult>, TestError> {
+        let machine = self.machine()?;
+        self.tests.iter().map(|test| self.run_case(&machine, test)).collect()
+    }
+
+    fn run_case(&self, machine: &MachineConfig, test: &TestCase) -> Result<TestResult, TestError> {
+        let mut cpu = Cpu::new();
+        let mut memory = Memory::new();
+        let symbols = machine.build(&mut cpu, &mut memory)?;
+        let symbols = symbols.as_ref();
+        let name = test.name.as_str();
+
+        for (at, words) in &test.memory {
+            let at = location(name, at, symbols)?;
+            for (offset, value) in words.values().iter().enumerate() {
+                memory.write((at as usize + offset) & 0x7FFF, word(name, *value)? as i16);
+            }
+        }
+        let entry = match &test.call {
+            Location::Address(address) => u16::try_from(*address).ok().filter(|address| *address < 0x8000)
+                .ok_or_else(|| TestError::BadLocation { test: name.to_string(), text: address.to_string() })?,
+            Location::Name(text) => location(name, text, symbols)?,
+        };
+        let sentinel = (cpu.core_words - 2) as u16;            // JSX 0,X then HLT
+        memory.write(sentinel as usize, Instruction::indexed(Opcode::JSX, 0).encode() as i16);
+        memory.write(sentinel as usize + 1, Instruction::new(Opcode::HLT, 0).encode() as i16);
+        if let Some(acr) = test.acr {
+            cpu.acr = word(name, acr)? as i16;
+        }
+        cpu.status = test.status.map_or(Ok(0), |status| word(name, status))? | ADFGBL;
+        cpu.ixr = entry as i16;
+        cpu.set_pcr(sentinel);
+        cpu.mode = Mode::RUN;
+        cpu.halt_reason = None;
+
+        let limit = test.max_instructions.or(self.max_instructions).unwrap_or(MAX_INSTRUCTIONS);
+        let start_cycles = cpu.cycles;
+        let start_instructions = cpu.instructions;
+        cpu.run(&mut memory, limit.saturating_add(1));         // the call and the subroutine
+        if cpu.mode == Mode::RUN && cpu.pcr == sentinel + 1 {
+            cpu.run(&mut memory, 1);                            // the HLT it returned to
+        }
+        let top = (cpu.core_words - 1) as u16;                 // the PCR wraps past the HLT
+        let returned = cpu.halt_reason == Some(HaltReason::Instruction) && cpu.pcr == (sentinel + 2) & top;
+        let last = cpu.pcr.wrapping_sub(1) & top;              // the instruction that stopped
+        let instructions = cpu.instructions - start_instructions - 1 - returned as u64;
+        let cycles = cpu.cycles - start_cycles;
+
+        let mut failures = Vec::new();
+        if !returned {
+            failures.push(match cpu.halt_reason {
+                _ if cpu.mode == Mode::RUN => format!("did not return within {} instructions", limit),
+                Some(HaltReason::Instruction) => format!("halted at {:04X}", last),
+                Some(HaltReason::Exit(status)) => format!("exited with status {} at {:04X}", status, cpu.pcr),
+                Some(HaltReason::Protected(address)) =>
+                    format!("stored to protected core {:04X} at {:04X}", address, last),
+                _ => format!("stopped on an illegal instruction at {:04X}", last),
+            });
+        } else {
+            let expect = &test.expect;
+            for (register, expected, actual) in [("ACR", expect.acr, cpu.acr as u16),
+                                                 ("IXR", expect.ixr, cpu.ixr as u16),
+                                                 ("status", expect.status, cpu.status)] {
+                if let Some(expected) = expected {
+                    let expected = word(name, expected)?;
+                    if actual != expected {
+                        failures.push(format!("{} = {:04X}, expected {:04X}", register, actual, expected));
+                    }
+                }
+            }
+            for (at, words) in &expect.memory {
+                let address = location(name, at, symbols)?;
+                for (offset, value) in words.values().iter().enumerate() {
+                    let expected = word(name, *value)?;
+                    let actual = memory.core[(address as usize + offset) & 0x7FFF] as u16;
+                    if actual != expected {
+                        let describe = symbols.map_or(format!("{:04X}", address as usize + offset),
+                                                      |symbols| symbols.describe(address + offset as u16));
+                        failures.push(format!("{} = {:04X}, expected {:04X}", describe, actual, expected));
+                    }
+                }
+            }
+        }
+        if let Some(host) = &cpu.host_calls {
+            for check in &host.failed {
+                failures.push(format!("TRAP FAIL {:04X} at {:04X}", check.code, check.pcr));
+            }
+        }
+        Ok(TestResult { name: test.name.clone(), failures, instructions, cycles })
+    }
+}
+
+// Test Anything Protocol, version 13
+pub fn tap(results: &[TestResult]) -> String {
+    let mut out = format!("TAP version 13\n1..{}\n", results.len());
+    for (index, result) in results.iter().enumerate() {
+        let status = if result.passed() { "ok" } else { "not ok" };
+        out += &format!("{} {} - {}\n", status, index + 1, result.name);
+        if !result.passed() {
+            out += "  ---\n  message: |\n";
+            for failure in &result.failures {
+                out += &format!("    {}\n", failure);
+            }
+            out += &format!("  instructions: {}\n  cycles: {}\n  ...\n", result.instructions, result.cycles);
+        }
+    }
+    out
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+// JUnit XML, one testsuite; time is emulated time
+pub fn junit(suite: &str, results: &[TestResult]) -> String {
+    let failed = results.iter().filter(|result| !result.passed()).count();
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out += &format!("<testsuite name=\"{}\" tests=\"{}\" failures=\"{}\">\n", xml_escape(suite), results.len(), failed);
+    for result in results {
+        out += &format!("  <testcase name=\"{}\" classname=\"{}\" time=\"{:.6}\"",
+                        xml_escape(&result.name), xml_escape(suite), crate::cpu::cycles_to_seconds(result.cycles));
+        if result.passed() {
+            out += "/>\n";
+        } else {
+            out += &format!(">\n    <failure message=\"{}\">{}</failure>\n  </testcase>\n",
+                            xml_escape(&result.failures[0]), xml_escape(&result.failures.join("\n")));
+        }
+    }
+    out += "</testsuite>\n";
+    out
+}
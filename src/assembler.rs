@@ -0,0 +1,387 @@
+// Two pass assembler for 703 programs
+//
+// One statement per line:
+//
+//      LABEL   OP      OPERAND         ; comment
+//
+// A label starts in the first column; a line starting with a blank has none.
+// Mnemonics and their operands come from the instruction set table:
+//
+//      LDW     TABLE,X         memory reference, ",X" for indexed
+//      DIN     0x20            device address or literal byte
+//      ENB     3               level, bank or shift count
+//
+// A memory reference must be in the 2K page of the instruction.  The byte
+// references STB, CMB and LDB take a byte address, twice the word address
+// plus one for the right byte, and must be in the instruction's 1K page.
+// Pseudo operations:
+//
+//      ORG     0x0100          set the location counter
+//      NAME    EQU     12      define a symbol
+//              DATA    1,-2,'A',NAME    words
+//              BSS     8       reserve words
+//              END     START   end of source, with the transfer address
+//
+// Operands are expressions of numbers (decimal, or hex with 0x), symbols,
+// character constants and * for the location counter, joined by + and -.
+// The operands of EQU, ORG and BSS are fixed on the first pass, so any
+// symbol in them must be defined on an earlier line.
+// The result is the words with their addresses, the symbol table, the
+// transfer address and a listing, and can be loaded or punched as a tape.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::absolute::{AbsoluteTape, Block};
+use crate::cpu::Memory;
+use crate::instruction::{Instruction, Opcode, Operand};
+use crate::symbols::SymbolTable;
+
+const CORE_WORDS:u32 = 32_768;
+
+#[derive(Debug)]
+pub enum AsmError {
+    Io(io::Error),
+    Syntax { line: usize, text: String },
+    UnknownOpcode { line: usize, text: String },
+    UndefinedSymbol { line: usize, name: String },
+    DuplicateSymbol { line: usize, name: String },
+    OutOfRange { line: usize, value: i32, operand: &'static str },
+    OutOfPage { line: usize, address: u16, location: u16 },
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AsmError::Io(err) => write!(f, "assembler i/o error: {}", err),
+            AsmError::Syntax { line, text } => write!(f, "line {}: cannot parse '{}'", line, text),
+            AsmError::UnknownOpcode { line, text } => write!(f, "line {}: unknown operation '{}'", line, text),
+            AsmError::UndefinedSymbol { line, name } => write!(f, "line {}: undefined symbol '{}'", line, name),
+            AsmError::DuplicateSymbol { line, name } => write!(f, "line {}: '{}' is already defined", line, name),
+            AsmError::OutOfRange { line, value, operand } =>
+                write!(f, "line {}: {} is out of range for {}", line, value, operand),
+            AsmError::OutOfPage { line, address, location } =>
+                write!(f, "line {}: {:04X} is not addressable from {:04X} without indexing", line, address, location),
+        }
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+impl From<io::Error> for AsmError {
+    fn from(err: io::Error) -> Self {
+        AsmError::Io(err)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListingLine {
+    pub line: usize,
+    pub location: Option<u16>,                  // where the first word went
+    pub words: Vec<u16>,
+    pub source: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Assembly {
+    pub words: BTreeMap<u16, u16>,              // core address to word
+    pub symbols: SymbolTable,
+    pub transfer: Option<u16>,                  // END operand
+    pub listing: Vec<ListingLine>,
+}
+
+impl Assembly {
+    // deposit the words into core, returning the transfer address
+    pub fn load(&self, memory: &mut Memory) -> Option<u16> {
+        for (address, word) in &self.words {
+            memory.write(*address as usize, *word as i16);
+        }
+        self.transfer
+    }
+
+    // the program as an absolute tape, one block per run of addresses
+    pub fn tape(&self) -> AbsoluteTape {
+        let mut blocks: Vec<Block> = Vec::new();
+        for (address, word) in &self.words {
+            match blocks.last_mut() {
+                Some(block) if block.address as usize + block.words.len() == *address as usize =>
+                    block.words.push(*word as i16),
+                _ => blocks.push(Block { address: *address, words: vec![*word as i16] }),
+            }
+        }
+        AbsoluteTape { blocks, transfer: self.transfer }
+    }
+
+    pub fn listing_text(&self) -> String {
+        let mut out = String::new();
+        for line in &self.listing {
+            let location = line.location.map_or("    ".to_string(), |location| format!("{:04X}", location));
+            let first = line.words.first().map_or("    ".to_string(), |word| format!("{:04X}", word));
+            out += &format!("{:>5}  {}  {}  {}\n", line.line, location, first, line.source);
+            for (i, word) in line.words.iter().enumerate().skip(1) {
+                out += &format!("       {:04X}  {:04X}\n", line.location.unwrap_or(0) as usize + i, word);
+            }
+        }
+        out
+    }
+}
+
+// a source line split into its fields
+struct Statement<'a> {
+    label: Option<&'a str>,
+    operation: Option<&'a str>,
+    operand: &'a str,
+}
+
+fn split(text: &str) -> Statement<'_> {
+    let code = strip_comment(text);
+    let label_present = code.chars().next().is_some_and(|ch| !ch.is_whitespace());
+    let mut rest = code.trim_start();
+    let mut label = None;
+    if label_present {
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        label = Some(&rest[..end]);
+        rest = rest[end..].trim_start();
+    }
+    let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+    let operation = if end == 0 { None } else { Some(&rest[..end]) };
+    Statement { label, operation, operand: rest[end..].trim() }
+}
+
+// the line up to a semicolon that is not inside a character constant
+fn strip_comment(text: &str) -> &str {
+    let mut quoted = false;
+    for (i, ch) in text.char_indices() {
+        match ch {
+            '\'' => quoted = !quoted,
+            ';' if !quoted => return &text[..i],
+            _ => {},
+        }
+    }
+    text
+}
+
+fn valid_name(name: &str) -> bool {
+    name.chars().next().is_some_and(|ch| ch.is_ascii_alphabetic() || ch == '_')
+        && name.chars().all(|ch| ch.is_ascii_alphanumeric() || ch == '_')
+}
+
+struct Assembler {
+    symbols: SymbolTable,
+    location: u32,
+    pass: u8,
+    line: usize,
+}
+
+impl Assembler {
+    // value of an expression; on pass 1 undefined symbols count as zero
+    fn expression(&self, text: &str) -> Result<i32, AsmError> {
+        self.evaluate(text, self.pass == 1)
+    }
+
+    // value of an expression that must not refer forward, as for EQU, ORG and BSS
+    fn fixed(&self, text: &str) -> Result<i32, AsmError> {
+        self.evaluate(text, false)
+    }
+
+    fn evaluate(&self, text: &str, forward: bool) -> Result<i32, AsmError> {
+        let syntax = || AsmError::Syntax { line: self.line, text: text.to_string() };
+        let text = text.trim();
+        if text.is_empty() {
+            return Err(syntax());
+        }
+        let mut value = 0i32;
+        let mut sign = 1;
+        let mut rest = text;
+        if let Some(negated) = rest.strip_prefix('-') {
+            sign = -1;
+            rest = negated;
+        }
+        loop {
+            rest = rest.trim_start();
+            let end = if let Some(quoted) = rest.strip_prefix('\'') {
+                quoted.find('\'').map(|end| end + 2).ok_or_else(syntax)?
+            } else {
+                rest.find(['+', '-']).filter(|end| *end > 0).unwrap_or(rest.len())
+            };
+            let term = rest[..end].trim();
+            value += sign * self.term(term, forward).ok_or_else(syntax)??;
+            rest = rest[end..].trim_start();
+            match rest.chars().next() {
+                None => return Ok(value),
+                Some('+') => sign = 1,
+                Some('-') => sign = -1,
+                Some(_) => return Err(syntax()),
+            }
+            rest = &rest[1..];
+        }
+    }
+
+    // None if the term is not well formed
+    fn term(&self, term: &str, forward: bool) -> Option<Result<i32, AsmError>> {
+        if term == "*" {
+            return Some(Ok(self.location as i32));
+        }
+        if let Some(digits) = term.strip_prefix("0x").or_else(|| term.strip_prefix("0X")) {
+            return i32::from_str_radix(digits, 16).ok().map(Ok);
+        }
+        if term.starts_with(|ch: char| ch.is_ascii_digit()) {
+            return term.parse::<i32>().ok().map(Ok);
+        }
+        if let Some(chars) = term.strip_prefix('\'').and_then(|term| term.strip_suffix('\'')) {
+            let bytes = chars.as_bytes();
+            return match bytes.len() {
+                1 => Some(Ok(bytes[0] as i32)),
+                2 => Some(Ok((bytes[0] as i32) << 8 | bytes[1] as i32)),
+                _ => None,
+            };
+        }
+        if !valid_name(term) {
+            return None;
+        }
+        Some(match self.symbols.lookup(term) {
+            Some(address) => Ok(address as i32),
+            None if forward => Ok(0),
+            None => Err(AsmError::UndefinedSymbol { line: self.line, name: term.to_string() }),
+        })
+    }
+
+    fn range(&self, value: i32, low: i32, high: i32, operand: &'static str) -> Result<u16, AsmError> {
+        if value < low || value > high {
+            return Err(AsmError::OutOfRange { line: self.line, value, operand });
+        }
+        Ok(value as u16)
+    }
+
+    fn instruction(&self, opcode: Opcode, operand: &str) -> Result<Instruction, AsmError> {
+        let kind = opcode.operand();
+        if kind == Operand::Address {
+            let (address, indexed) = match operand.strip_suffix(",X").or_else(|| operand.strip_suffix(",x")) {
+                Some(address) => (address, true),
+                None => (operand, false),
+            };
+            let location = self.location as u16;
+            let (address, page) = if opcode.byte_reference() {
+                let address = self.range(self.expression(address)?, 0, 2 * CORE_WORDS as i32 - 1, "a byte address")?;
+                (address, (address >> 1) & 0x7C00)
+            } else {
+                let address = self.range(self.expression(address)?, 0, CORE_WORDS as i32 - 1, "an address")?;
+                (address, address & 0x7800)
+            };
+            let location_page = if opcode.byte_reference() { location & 0x7C00 } else { location & 0x7800 };
+            if self.pass == 2 && page != location_page {
+                return Err(AsmError::OutOfPage { line: self.line, address, location });
+            }
+            let address = address & 0x07FF;
+            return Ok(if indexed { Instruction::indexed(opcode, address) } else { Instruction::new(opcode, address) });
+        }
+        if kind == Operand::Unused && operand.is_empty() {
+            return Ok(Instruction::new(opcode, 0));
+        }
+        let value = self.expression(operand)?;
+        let operand = match kind {
+            Operand::Byte => self.range(value, -128, 255, "a byte")? & 0x00FF,
+            Operand::Level => self.range(value, 0, 15, "a level")?,
+            Operand::Bank => self.range(value, 0, 15, "a bank")?,
+            Operand::Count => self.range(value, 0, 15, "a shift count")?,
+            _ => self.range(value, 0, 15, "an unused field")?,
+        };
+        Ok(Instruction::new(opcode, operand))
+    }
+
+    fn data(&self, operand: &str) -> Result<Vec<u16>, AsmError> {
+        operand.split(',')
+            .map(|item| self.range(self.expression(item)?, -32768, 65535, "a word"))
+            .collect()
+    }
+
+    fn define(&mut self, name: &str, value: u16) -> Result<(), AsmError> {
+        if !valid_name(name) {
+            return Err(AsmError::Syntax { line: self.line, text: name.to_string() });
+        }
+        if self.pass == 1 {
+            if self.symbols.lookup(name).is_some() {
+                return Err(AsmError::DuplicateSymbol { line: self.line, name: name.to_string() });
+            }
+            self.symbols.insert(name, value);
+        }
+        Ok(())
+    }
+
+    fn pass(&mut self, source: &str, pass: u8, assembly: &mut Assembly) -> Result<(), AsmError> {
+        self.pass = pass;
+        self.location = 0;
+        for (index, text) in source.lines().enumerate() {
+            self.line = index + 1;
+            let statement = split(text);
+            let start = self.location;
+            let mut words: Vec<u16> = Vec::new();
+            let mut ended = false;
+            let operation = statement.operation.map(|operation| operation.to_ascii_uppercase());
+            if operation.as_deref() != Some("EQU") {
+                if let Some(label) = statement.label {
+                    self.define(label, self.location as u16)?;
+                }
+            }
+            match operation.as_deref() {
+                None => {},
+                Some("EQU") => {
+                    let label = statement.label.ok_or_else(|| AsmError::Syntax { line: self.line, text: text.to_string() })?;
+                    let value = self.range(self.fixed(statement.operand)?, -32768, 65535, "a symbol")?;
+                    self.define(label, value)?;
+                },
+                Some("ORG") => self.location = self.range(self.fixed(statement.operand)?, 0, CORE_WORDS as i32 - 1, "an origin")? as u32,
+                Some("BSS") => self.location += self.range(self.fixed(statement.operand)?, 0, CORE_WORDS as i32, "a count")? as u32,
+                Some("DATA") => words = self.data(statement.operand)?,
+                Some("END") => {
+                    if !statement.operand.is_empty() {
+                        assembly.transfer = Some(self.range(self.expression(statement.operand)?, 0, CORE_WORDS as i32 - 1, "a transfer address")?);
+                    }
+                    ended = true;
+                },
+                Some(mnemonic) => {
+                    let opcode = Opcode::from_mnemonic(mnemonic)
+                        .ok_or_else(|| AsmError::UnknownOpcode { line: self.line, text: mnemonic.to_string() })?;
+                    words.push(self.instruction(opcode, statement.operand)?.encode());
+                },
+            }
+            if self.location + words.len() as u32 > CORE_WORDS {
+                return Err(AsmError::OutOfRange { line: self.line, value: self.location as i32, operand: "core" });
+            }
+            if pass == 2 {
+                for (i, word) in words.iter().enumerate() {
+                    assembly.words.insert((self.location + i as u32) as u16, *word);
+                }
+                let placed = !words.is_empty() || statement.label.is_some() || operation.as_deref() == Some("BSS");
+                assembly.listing.push(ListingLine {
+                    line: self.line,
+                    location: (placed && operation.as_deref() != Some("EQU")).then_some(start as u16),
+                    words: words.clone(),
+                    source: text.to_string(),
+                });
+            }
+            self.location += words.len() as u32;
+            if ended {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn assemble(source: &str) -> Result<Assembly, AsmError> {
+    let mut assembler = Assembler { symbols: SymbolTable::new(), location: 0, pass: 1, line: 0 };
+    let mut assembly = Assembly::default();
+    assembler.pass(source, 1, &mut assembly)?;
+    assembly.transfer = None;
+    assembler.pass(source, 2, &mut assembly)?;
+    assembly.symbols = assembler.symbols;
+    Ok(assembly)
+}
+
+pub fn assemble_file<P: AsRef<Path>>(path: P) -> Result<Assembly, AsmError> {
+    assemble(&fs::read_to_string(path)?)
+}
@@ -0,0 +1,391 @@
+// Headless batch runs
+//
+// A batch run loads a program, types scripted input on the teletype, runs
+// until the machine halts or a limit is reached, saves the output of the
+// devices to files and checks the final registers and core against expected
+// values.  The process exit code tells a CI job what happened:
+//
+//      0   halted by HLT or a host call EXIT of 0, every expectation met and
+//          no host call FAIL
+//      1   the configuration or program could not be loaded
+//      2   bad command line
+//      3   halted by HLT or EXIT but an expectation or host call check failed,
+//          EXIT gave another status, or a dialog or console script failed
+//      4   the instruction or cycle limit was reached
+//      5   halted on a word that is not an instruction
+//      6   not used; every instruction is implemented
+//      7   halted on a store to protected core
+//
+// Expectations name a register or a core address in hex:
+//      acr=0x0005  ixr=-1  pcr=0x0105  status=0x0000  0x0018=0x1234
+// Captures name an output device and a file:
+//      teletype=console.txt  punch=out.tape  printer=listing.txt  dac=dac.csv
+
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use crate::analog::{AnalogOutput, DAC_ADDRESS, DAC_CHANNELS};
+use crate::cpu::{Cpu, HaltReason, Memory, Mode};
+use crate::io::BusError;
+use crate::papertape::{PaperTapePunch, PUNCH_ADDRESS};
+use crate::printer::{LinePrinter, PRINTER_ADDRESS};
+use crate::teletype::{Teletype, TELETYPE_ADDRESS};
+
+pub const EXIT_PASS:i32 = 0;
+pub const EXIT_LOAD_ERROR:i32 = 1;
+pub const EXIT_USAGE:i32 = 2;
+pub const EXIT_EXPECT_FAILED:i32 = 3;
+pub const EXIT_LIMIT:i32 = 4;
+pub const EXIT_ILLEGAL:i32 = 5;
+pub const EXIT_PROTECTED:i32 = 7;
+
+const MAX_INSTRUCTION_CYCLES:u64 = 5;           // operand fetch and interrupt entry included
+const CHUNK:u64 = 100_000;                      // instructions between limit checks
+
+#[derive(Debug)]
+pub enum BatchError {
+    Io(io::Error),
+    Bus(BusError),
+    BadExpect(String),
+    BadCapture(String),
+    Captured(Output),                           // device already writes its own file
+}
+
+impl fmt::Display for BatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BatchError::Io(err) => write!(f, "{}", err),
+            BatchError::Bus(err) => write!(f, "{}", err),
+            BatchError::BadExpect(text) => write!(f, "bad expectation '{}', expected register=value", text),
+            BatchError::BadCapture(text) => write!(f, "bad capture '{}', expected device=file", text),
+            BatchError::Captured(output) => write!(f, "the {} output is already set by the configuration", output),
+        }
+    }
+}
+
+impl std::error::Error for BatchError {}
+
+impl From<io::Error> for BatchError {
+    fn from(err: io::Error) -> Self {
+        BatchError::Io(err)
+    }
+}
+
+impl From<BusError> for BatchError {
+    fn from(err: BusError) -> Self {
+        BatchError::Bus(err)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    Acr,
+    Ixr,
+    Pcr,
+    Status,
+    Core(u16),
+}
+
+impl Target {
+    pub fn read(self, cpu: &Cpu, memory: &Memory) -> u16 {
+        match self {
+            Target::Acr => cpu.acr as u16,
+            Target::Ixr => cpu.ixr as u16,
+            Target::Pcr => cpu.pcr,
+            Target::Status => cpu.status,
+            Target::Core(address) => memory.core[address as usize & 0x7FFF] as u16,
+        }
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Target::Acr => write!(f, "ACR"),
+            Target::Ixr => write!(f, "IXR"),
+            Target::Pcr => write!(f, "PCR"),
+            Target::Status => write!(f, "status"),
+            Target::Core(address) => write!(f, "core {:04X}", address),
+        }
+    }
+}
+
+// a number as the assembler writes it: decimal, 0x hex or negative decimal
+fn parse_word(text: &str) -> Option<u16> {
+    let text = text.trim();
+    if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        return u16::from_str_radix(hex, 16).ok();
+    }
+    match text.parse::<i32>() {
+        Ok(value) if (-0x8000..=0xFFFF).contains(&value) => Some(value as u16),
+        _ => None,
+    }
+}
+
+// command line input text with \n, \r, \t and \\ escapes
+pub fn unescape(text: &str) -> String {
+    let mut chars = text.chars();
+    let mut out = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n') => out.push('\n'),
+                Some('r') => out.push('\r'),
+                Some('t') => out.push('\t'),
+                Some(other) => out.push(other),
+                None => out.push('\\'),
+            },
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Expect {
+    pub target: Target,
+    pub value: u16,
+}
+
+impl Expect {
+    pub fn parse(text: &str) -> Result<Expect, BatchError> {
+        let bad = || BatchError::BadExpect(text.to_string());
+        let (name, value) = text.split_once('=').ok_or_else(bad)?;
+        let target = match name.trim().to_ascii_lowercase().as_str() {
+            "acr" => Target::Acr,
+            "ixr" => Target::Ixr,
+            "pcr" => Target::Pcr,
+            "status" => Target::Status,
+            name if name.starts_with("0x") => match parse_word(name) {
+                Some(address) if address < 0x8000 => Target::Core(address),
+                _ => return Err(bad()),
+            },
+            _ => return Err(bad()),
+        };
+        Ok(Expect { target, value: parse_word(value).ok_or_else(bad)? })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Output {
+    Teletype,
+    Punch,
+    Printer,
+    Dac,
+}
+
+impl fmt::Display for Output {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Output::Teletype => write!(f, "teletype"),
+            Output::Punch => write!(f, "punch"),
+            Output::Printer => write!(f, "printer"),
+            Output::Dac => write!(f, "dac"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Capture {
+    pub output: Output,
+    pub path: PathBuf,
+}
+
+impl Capture {
+    pub fn parse(text: &str) -> Result<Capture, BatchError> {
+        let bad = || BatchError::BadCapture(text.to_string());
+        let (name, path) = text.split_once('=').ok_or_else(bad)?;
+        let output = match name.trim().to_ascii_lowercase().as_str() {
+            "teletype" => Output::Teletype,
+            "punch" => Output::Punch,
+            "printer" => Output::Printer,
+            "dac" => Output::Dac,
+            _ => return Err(bad()),
+        };
+        if path.is_empty() {
+            return Err(bad());
+        }
+        Ok(Capture { output, path: PathBuf::from(path) })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stop {
+    Halted(HaltReason),
+    InstructionLimit,
+    CycleLimit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Failure {
+    pub expect: Expect,
+    pub actual: u16,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outcome {
+    pub stop: Stop,
+    pub pcr: u16,
+    pub instructions: u64,                      // run in this batch
+    pub cycles: u64,
+    pub failures: Vec<Failure>,
+    pub checks_passed: usize,                   // host call PASS and FAIL
+    pub checks_failed: usize,
+}
+
+impl Outcome {
+    pub fn exit_code(&self) -> i32 {
+        let clean = self.failures.is_empty() && self.checks_failed == 0;
+        match self.stop {
+            Stop::Halted(HaltReason::Instruction) | Stop::Halted(HaltReason::Exit(0)) if clean => EXIT_PASS,
+            Stop::Halted(HaltReason::Instruction) | Stop::Halted(HaltReason::Exit(_)) => EXIT_EXPECT_FAILED,
+            Stop::Halted(HaltReason::Illegal) => EXIT_ILLEGAL,
+            Stop::Halted(HaltReason::Protected(_)) => EXIT_PROTECTED,
+            Stop::InstructionLimit | Stop::CycleLimit => EXIT_LIMIT,
+        }
+    }
+}
+
+// "halted by HLT at PCR 0105 after 5 instructions, 8 cycles" and a line per failure
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let stop = match self.stop {
+            Stop::Halted(HaltReason::Instruction) => "halted by HLT".to_string(),
+            Stop::Halted(HaltReason::Exit(status)) => format!("exited with status {}", status as i16),
+            Stop::Halted(HaltReason::Illegal) => "halted on an illegal instruction".to_string(),
+            Stop::Halted(HaltReason::Protected(address)) => format!("halted on a store to protected core {:04X}", address),
+            Stop::InstructionLimit => "instruction limit reached".to_string(),
+            Stop::CycleLimit => "cycle limit reached".to_string(),
+        };
+        writeln!(f, "{} at PCR {:04X} after {} instructions, {} cycles", stop, self.pcr, self.instructions, self.cycles)?;
+        for failure in &self.failures {
+            writeln!(f, "{} = {:04X}, expected {:04X}", failure.expect.target, failure.actual, failure.expect.value)?;
+        }
+        if self.checks_passed + self.checks_failed > 0 {
+            writeln!(f, "checks: {} passed, {} failed", self.checks_passed, self.checks_failed)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Batch {
+    pub max_instructions: Option<u64>,
+    pub max_cycles: Option<u64>,
+    pub input: Vec<u8>,                         // typed on the teletype
+    pub expects: Vec<Expect>,
+    pub captures: Vec<Capture>,
+}
+
+impl Batch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Attach the devices the run needs at their standard addresses when the
+    // configuration has not, and type the input.
+    pub fn prepare(&self, cpu: &mut Cpu) -> Result<(), BatchError> {
+        let wants_teletype = !self.input.is_empty() || self.captures.iter().any(|capture| capture.output == Output::Teletype);
+        if wants_teletype && cpu.io.device_mut::<Teletype>(TELETYPE_ADDRESS).is_none() {
+            cpu.io.attach(TELETYPE_ADDRESS, None, Box::new(Teletype::new()))?;
+        }
+        if let Some(teletype) = cpu.io.device_mut::<Teletype>(TELETYPE_ADDRESS) {
+            teletype.type_bytes(&self.input);
+        }
+        for capture in &self.captures {
+            match capture.output {
+                Output::Teletype => {},
+                Output::Punch => if cpu.io.device_mut::<PaperTapePunch>(PUNCH_ADDRESS).is_none() {
+                    cpu.io.attach(PUNCH_ADDRESS, None, Box::new(PaperTapePunch::new()))?;
+                },
+                Output::Printer => {
+                    if cpu.io.device_mut::<LinePrinter>(PRINTER_ADDRESS).is_some() {
+                        return Err(BatchError::Captured(Output::Printer));
+                    }
+                    cpu.io.attach(PRINTER_ADDRESS, None, Box::new(LinePrinter::to_file(&capture.path)?))?;
+                },
+                Output::Dac => if cpu.io.device_mut::<AnalogOutput>(DAC_ADDRESS).is_none() {
+                    cpu.io.attach(DAC_ADDRESS, None, Box::new(AnalogOutput::new(DAC_CHANNELS)))?;
+                },
+            }
+        }
+        Ok(())
+    }
+
+    // run from the current PCR until the machine halts or a limit is reached
+    pub fn run(&self, cpu: &mut Cpu, memory: &mut Memory) -> Stop {
+        let first_instruction = cpu.instructions;
+        let first_cycle = cpu.cycles;
+        cpu.halt_reason = None;
+        loop {
+            if cpu.mode != Mode::RUN {
+                return Stop::Halted(cpu.halt_reason.unwrap_or(HaltReason::Instruction));
+            }
+            let mut count = CHUNK;
+            if let Some(limit) = self.max_instructions {
+                let run = cpu.instructions - first_instruction;
+                if run >= limit {
+                    return Stop::InstructionLimit;
+                }
+                count = count.min(limit - run);
+            }
+            if let Some(limit) = self.max_cycles {      // close in on the limit without passing it by much
+                let run = cpu.cycles - first_cycle;
+                if run >= limit {
+                    return Stop::CycleLimit;
+                }
+                count = count.min(((limit - run) / MAX_INSTRUCTION_CYCLES).max(1));
+            }
+            cpu.run(memory, count);
+        }
+    }
+
+    pub fn check(&self, cpu: &Cpu, memory: &Memory) -> Vec<Failure> {
+        self.expects.iter()
+            .map(|expect| Failure { expect: *expect, actual: expect.target.read(cpu, memory) })
+            .filter(|failure| failure.actual != failure.expect.value)
+            .collect()
+    }
+
+    // write the captured output of the devices
+    pub fn save(&self, cpu: &mut Cpu) -> Result<(), BatchError> {
+        for capture in &self.captures {
+            match capture.output {
+                Output::Teletype => if let Some(teletype) = cpu.io.device_mut::<Teletype>(TELETYPE_ADDRESS) {
+                    teletype.save(&capture.path)?;
+                },
+                Output::Punch => if let Some(punch) = cpu.io.device_mut::<PaperTapePunch>(PUNCH_ADDRESS) {
+                    punch.save(&capture.path)?;
+                },
+                Output::Printer => if let Some(printer) = cpu.io.device_mut::<LinePrinter>(PRINTER_ADDRESS) {
+                    printer.flush();
+                    if let Some(err) = printer.last_error.take() {
+                        return Err(BatchError::Io(io::Error::other(err)));
+                    }
+                },
+                Output::Dac => if let Some(dac) = cpu.io.device_mut::<AnalogOutput>(DAC_ADDRESS) {
+                    dac.save_csv(&capture.path)?;
+                },
+            }
+        }
+        Ok(())
+    }
+
+    // run, save and check, once prepared
+    pub fn execute(&self, cpu: &mut Cpu, memory: &mut Memory) -> Result<Outcome, BatchError> {
+        let first_instruction = cpu.instructions;
+        let first_cycle = cpu.cycles;
+        let stop = self.run(cpu, memory);
+        self.save(cpu)?;
+        Ok(Outcome {
+            stop,
+            pcr: cpu.pcr,
+            instructions: cpu.instructions - first_instruction,
+            cycles: cpu.cycles - first_cycle,
+            failures: self.check(cpu, memory),
+            checks_passed: cpu.host_calls.as_ref().map_or(0, |host| host.passed.len()),
+            checks_failed: cpu.host_calls.as_ref().map_or(0, |host| host.failed.len()),
+        })
+    }
+}
@@ -0,0 +1,117 @@
+// Front panel LOAD
+//
+// The operator mounted the tape, set the boot device and pressed LOAD.  That
+// deposits the bootstrap below into low core just above the interrupt
+// vectors, points its DIN/DOT instructions at the boot device and starts it.
+// The bootstrap reads an absolute tape (see absolute.rs) from the device,
+// checking every block, and jumps to the transfer address.  It halts with the
+// PCR just past one of its HLT instructions when something goes wrong:
+//
+//      PCR 004A    frame that is not a start of block between blocks
+//      PCR 0057    checksum error in the transfer record
+//      PCR 005B    transfer address has bit 15 set, load only
+//      PCR 006C    checksum error in a data block
+//      PCR 0083    end of tape before the transfer record
+//
+// The boot device must follow the paper tape reader register layout: DOT +0
+// with RUN set starts it, DIN +1 returns READY in bit 15 and END_OF_TAPE in
+// bit 13, and DIN +0 returns the next frame.
+
+use crate::cpu::{Cpu, Memory, Mode};
+
+pub const BOOT_ADDRESS:u16 = 0x0040;            // first word of the bootstrap
+pub const BOOT_WORDS:usize = 77;                // length of the bootstrap
+
+pub fn bootstrap(dev: u8) -> Vec<i16> {
+    let dev = dev as u16;
+    let words: [u16; BOOT_WORDS] = [
+        0x0110,              // 0040  BOOT   CLR
+        0x0601,              // 0041         LLB 1
+        0x0300 | dev,        // 0042         DOT DEV+0
+        0x2079,              // 0043  BLOCK  JSX GETB
+        0x0800,              // 0044         SAZ
+        0x1047,              // 0045         JMP GOTF
+        0x1043,              // 0046         JMP BLOCK
+        0xB084,              // 0047  GOTF   SUB SOB
+        0x0800,              // 0048         SAZ
+        0x0000,              // 0049         HLT
+        0x0110,              // 004A         CLR
+        0x7087,              // 004B         STW SUM
+        0x206D,              // 004C         JSX GETW
+        0x7088,              // 004D         STW CNT
+        0x206D,              // 004E         JSX GETW
+        0x7089,              // 004F         STW ADR
+        0x8088,              // 0050         LDW CNT
+        0x0800,              // 0051         SAZ
+        0x105D,              // 0052         JMP DATA
+        0x206D,              // 0053         JSX GETW
+        0x8087,              // 0054         LDW SUM
+        0x0800,              // 0055         SAZ
+        0x0000,              // 0056         HLT
+        0x8089,              // 0057         LDW ADR
+        0x0820,              // 0058         SAM
+        0x105B,              // 0059         JMP GO
+        0x0000,              // 005A         HLT
+        0x0140,              // 005B  GO     CAX
+        0x1800,              // 005C         JMP 0,X
+        0x206D,              // 005D  DATA   JSX GETW
+        0x9089,              // 005E         LDX ADR
+        0x7800,              // 005F         STW 0,X
+        0x8089,              // 0060         LDW ADR
+        0xA086,              // 0061         ADD ONE
+        0x7089,              // 0062         STW ADR
+        0x8088,              // 0063         LDW CNT
+        0xB086,              // 0064         SUB ONE
+        0x7088,              // 0065         STW CNT
+        0x0800,              // 0066         SAZ
+        0x105D,              // 0067         JMP DATA
+        0x206D,              // 0068         JSX GETW
+        0x8087,              // 0069         LDW SUM
+        0x0800,              // 006A         SAZ
+        0x0000,              // 006B         HLT
+        0x1043,              // 006C         JMP BLOCK
+        0x608B,              // 006D  GETW   STX RETW
+        0x2079,              // 006E         JSX GETB
+        0x0A18,              // 006F         SLL 8
+        0x708A,              // 0070         STW WORD
+        0x2079,              // 0071         JSX GETB
+        0xC08A,              // 0072         ORI WORD
+        0x708A,              // 0073         STW WORD
+        0xA087,              // 0074         ADD SUM
+        0x7087,              // 0075         STW SUM
+        0x808A,              // 0076         LDW WORD
+        0x908B,              // 0077         LDX RETW
+        0x1800,              // 0078         JMP 0,X
+        0x608C,              // 0079  GETB   STX RETB
+        0x0200 | (dev + 1),  // 007A  WAIT   DIN DEV+1
+        0x0820,              // 007B         SAM
+        0x1080,              // 007C         JMP NRDY
+        0x0200 | dev,        // 007D         DIN DEV+0
+        0x908C,              // 007E         LDX RETB
+        0x1800,              // 007F         JMP 0,X
+        0xE085,              // 0080  NRDY   AND EOT
+        0x0800,              // 0081         SAZ
+        0x0000,              // 0082         HLT
+        0x107A,              // 0083         JMP WAIT
+        0x0081,              // 0084  SOB    DATA 0x0081
+        0x2000,              // 0085  EOT    DATA 0x2000
+        0x0001,              // 0086  ONE    DATA 0x0001
+        0x0000,              // 0087  SUM    DATA 0
+        0x0000,              // 0088  CNT    DATA 0
+        0x0000,              // 0089  ADR    DATA 0
+        0x0000,              // 008A  WORD   DATA 0
+        0x0000,              // 008B  RETW   DATA 0
+        0x0000,              // 008C  RETB   DATA 0
+    ];
+    words.iter().map(|word| *word as i16).collect()
+}
+
+// press LOAD: master clear, deposit the bootstrap for device and start it
+pub fn load(cpu: &mut Cpu, memory: &mut Memory, device: u8) {
+    cpu.reset();
+    let base = BOOT_ADDRESS as usize;
+    let words = bootstrap(device);
+    memory.write_block(base, &words);
+    cpu.set_pcr(BOOT_ADDRESS);
+    cpu.mode = Mode::RUN;
+}
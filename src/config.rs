@@ -0,0 +1,371 @@
+// Machine configuration
+//
+// A TOML file describes one installation: the core fitted, the console sense
+// switches, the devices on the bus with their device codes, interrupt levels
+// and the files behind them, and the program to load and where to start it.
+// Relative paths are taken from the directory of the configuration file.
+//
+//      [machine]
+//      name = "boiler house"
+//      core = 16384                    # words, a power of two from 4096 to 32768
+//      sense_switches = [0, 3]         # switches that are on
+//      image = "control.abs"           # absolute tape, or assembler source if .s/.asm
+//      symbols = "control.sym"
+//      pcr = 0x0100                    # start here instead of the transfer address
+//      boot = false                    # true presses LOAD on the paper tape reader
+//      host_calls = false              # true enables the TRAP host calls for test programs
+//      uninitialized = false           # true reports reads of core nothing has written
+//      self_modifying = false          # true reports stores into instructions already run
+//      interrupt_log = false           # true records interrupt requests, entries and exits
+//
+//      [[device]]
+//      type = "paper-tape-reader"
+//      code = 0x04                     # defaults to the standard address
+//      level = 5                       # no interrupts when absent
+//      file = "data.tape"
+//
+//      [[protect]]
+//      start = 0x0200                  # program stores here halt the machine
+//      end = 0x02FF
+//      action = "halt"                 # or "log" or "drop", see protect.rs
+//
+// Device types and their settings
+//      paper-tape-reader   file
+//      paper-tape-punch    file, written when the run ends
+//      magnetic-tape       file, write_enable
+//      disk                file, write_enable, cylinders, heads, sectors, words_per_sector
+//      line-printer        file, standard output when absent
+//      teletype            input, typed on the keyboard; output, written when the run ends; echo
+//      analog-input        file, a CSV of channel voltages over time; first_channel
+//      analog-output       channels; file, a CSV of updates written when the run ends
+//      digital-io          groups
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::absolute::{self, NO_TRANSFER};
+use crate::analog::{AnalogInput, AnalogOutput, ADC_ADDRESS, DAC_ADDRESS, DAC_CHANNELS};
+use crate::assembler;
+use crate::boot;
+use crate::cpu::{Cpu, Memory, Mode};
+use crate::digital::{DigitalIo, DIGITAL_ADDRESS, DIGITAL_GROUPS};
+use crate::disk::{Disk, Geometry, DISK_ADDRESS};
+use crate::hostcall::HostCalls;
+use crate::io::{BusError, Device};
+use crate::magtape::{MagTape, MAGTAPE_ADDRESS};
+use crate::papertape::{PaperTapePunch, PaperTapeReader, PUNCH_ADDRESS, READER_ADDRESS};
+use crate::printer::{LinePrinter, Sink, PRINTER_ADDRESS};
+use crate::protect::ProtectedRange;
+use crate::symbols::SymbolTable;
+use crate::teletype::{Teletype, TELETYPE_ADDRESS};
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io { path: PathBuf, err: io::Error },
+    Parse(toml::de::Error),
+    CoreSize(usize),
+    SenseSwitch(u8),
+    Bus { device: String, err: BusError },
+    Load { path: PathBuf, message: String },
+    NoReader,                                   // boot asked for without a reader
+    DiskGeometry(Geometry),                     // a dimension of zero
+    Protect(ProtectedRange),                    // range outside core or backwards
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Io { path, err } => write!(f, "{}: {}", path.display(), err),
+            ConfigError::Parse(err) => write!(f, "machine configuration: {}", err),
+            ConfigError::CoreSize(words) =>
+                write!(f, "core of {} words is not a power of two from 4096 to 32768", words),
+            ConfigError::SenseSwitch(switch) => write!(f, "there is no sense switch {}", switch),
+            ConfigError::Bus { device, err } => write!(f, "{}: {}", device, err),
+            ConfigError::Load { path, message } => write!(f, "{}: {}", path.display(), message),
+            ConfigError::NoReader => write!(f, "boot needs a paper-tape-reader device"),
+            ConfigError::DiskGeometry(geometry) =>
+                write!(f, "disk of {} cylinders, {} heads, {} sectors of {} words has a dimension of zero",
+                       geometry.cylinders, geometry.heads, geometry.sectors, geometry.words_per_sector),
+            ConfigError::Protect(range) =>
+                write!(f, "protected range {:04X}-{:04X} is not within core", range.start, range.last()),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl From<toml::de::Error> for ConfigError {
+    fn from(err: toml::de::Error) -> Self {
+        ConfigError::Parse(err)
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MachineSection {
+    pub name: Option<String>,
+    pub core: Option<usize>,                    // words
+    #[serde(default)]
+    pub sense_switches: Vec<u8>,                // switches that are on
+    pub image: Option<PathBuf>,
+    pub symbols: Option<PathBuf>,
+    pub pcr: Option<u16>,
+    #[serde(default)]
+    pub boot: bool,
+    #[serde(default)]
+    pub host_calls: bool,
+    #[serde(default)]
+    pub uninitialized: bool,
+    #[serde(default)]
+    pub self_modifying: bool,
+    #[serde(default)]
+    pub interrupt_log: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case", deny_unknown_fields)]
+pub enum DeviceKind {
+    PaperTapeReader { file: Option<PathBuf> },
+    PaperTapePunch { file: Option<PathBuf> },
+    MagneticTape { file: Option<PathBuf>, #[serde(default)] write_enable: bool },
+    Disk {
+        file: Option<PathBuf>,
+        #[serde(default)] write_enable: bool,
+        cylinders: Option<u16>,
+        heads: Option<u8>,
+        sectors: Option<u8>,
+        words_per_sector: Option<u16>,
+    },
+    LinePrinter { file: Option<PathBuf> },
+    Teletype { input: Option<PathBuf>, output: Option<PathBuf>, #[serde(default)] echo: bool },
+    AnalogInput { file: Option<PathBuf>, #[serde(default)] first_channel: u8 },
+    AnalogOutput { channels: Option<u8>, file: Option<PathBuf> },
+    DigitalIo { groups: Option<u8> },
+}
+
+impl DeviceKind {
+    pub fn standard_address(&self) -> u8 {
+        match self {
+            DeviceKind::PaperTapeReader { .. } => READER_ADDRESS,
+            DeviceKind::PaperTapePunch { .. } => PUNCH_ADDRESS,
+            DeviceKind::MagneticTape { .. } => MAGTAPE_ADDRESS,
+            DeviceKind::Disk { .. } => DISK_ADDRESS,
+            DeviceKind::LinePrinter { .. } => PRINTER_ADDRESS,
+            DeviceKind::Teletype { .. } => TELETYPE_ADDRESS,
+            DeviceKind::AnalogInput { .. } => ADC_ADDRESS,
+            DeviceKind::AnalogOutput { .. } => DAC_ADDRESS,
+            DeviceKind::DigitalIo { .. } => DIGITAL_ADDRESS,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DeviceConfig {
+    pub code: Option<u8>,                       // base bus address
+    pub level: Option<u8>,                      // interrupt level
+    #[serde(flatten)]
+    pub kind: DeviceKind,
+}
+
+impl DeviceConfig {
+    pub fn code(&self) -> u8 {
+        self.code.unwrap_or_else(|| self.kind.standard_address())
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MachineConfig {
+    #[serde(default)]
+    pub machine: MachineSection,
+    #[serde(default, rename = "device")]
+    pub devices: Vec<DeviceConfig>,
+    #[serde(default, rename = "protect")]
+    pub protected: Vec<ProtectedRange>,
+    #[serde(skip)]
+    pub directory: PathBuf,                     // relative paths start here
+}
+
+impl MachineConfig {
+    pub fn parse(text: &str) -> Result<MachineConfig, ConfigError> {
+        Ok(toml::from_str(text)?)
+    }
+
+    pub fn load_file<P: AsRef<Path>>(path: P) -> Result<MachineConfig, ConfigError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|err| ConfigError::Io { path: path.to_path_buf(), err })?;
+        let mut config = MachineConfig::parse(&text)?;
+        config.directory = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        Ok(config)
+    }
+
+    pub fn resolve(&self, path: &Path) -> PathBuf {
+        self.directory.join(path)
+    }
+
+    fn open_error(&self, path: &Path, err: io::Error) -> ConfigError {
+        ConfigError::Io { path: self.resolve(path), err }
+    }
+
+    fn device(&self, device: &DeviceConfig) -> Result<Box<dyn Device>, ConfigError> {
+        Ok(match &device.kind {
+            DeviceKind::PaperTapeReader { file } => {
+                let mut reader = PaperTapeReader::new();
+                if let Some(file) = file {
+                    reader.mount_file(self.resolve(file)).map_err(|err| self.open_error(file, err))?;
+                }
+                Box::new(reader)
+            },
+            DeviceKind::PaperTapePunch { .. } => Box::new(PaperTapePunch::new()),
+            DeviceKind::MagneticTape { file, write_enable } => {
+                let mut tape = MagTape::new();
+                if let Some(file) = file {
+                    tape.mount_file(self.resolve(file), *write_enable).map_err(|err| self.open_error(file, err))?;
+                }
+                Box::new(tape)
+            },
+            DeviceKind::Disk { file, write_enable, cylinders, heads, sectors, words_per_sector } => {
+                let standard = Geometry::default();
+                let geometry = Geometry {
+                    cylinders: cylinders.unwrap_or(standard.cylinders),
+                    heads: heads.unwrap_or(standard.heads),
+                    sectors: sectors.unwrap_or(standard.sectors),
+                    words_per_sector: words_per_sector.unwrap_or(standard.words_per_sector),
+                };
+                if !geometry.is_valid() {
+                    return Err(ConfigError::DiskGeometry(geometry));
+                }
+                let mut disk = Disk::new(geometry);
+                if let Some(file) = file {
+                    disk.attach_file(self.resolve(file), *write_enable).map_err(|err| self.open_error(file, err))?;
+                }
+                Box::new(disk)
+            },
+            DeviceKind::LinePrinter { file } => match file {
+                Some(file) => Box::new(LinePrinter::to_file(self.resolve(file)).map_err(|err| self.open_error(file, err))?),
+                None => Box::new(LinePrinter::new(Sink::Stdout)),
+            },
+            DeviceKind::Teletype { input, echo, .. } => {
+                let mut teletype = Teletype::new();
+                teletype.echo = *echo;
+                if let Some(file) = input {
+                    teletype.type_file(self.resolve(file)).map_err(|err| self.open_error(file, err))?;
+                }
+                Box::new(teletype)
+            },
+            DeviceKind::AnalogInput { file, first_channel } => {
+                let mut adc = AnalogInput::new();
+                if let Some(file) = file {
+                    adc.load_csv(self.resolve(file), *first_channel)
+                        .map_err(|err| ConfigError::Load { path: self.resolve(file), message: err.to_string() })?;
+                }
+                Box::new(adc)
+            },
+            DeviceKind::AnalogOutput { channels, .. } => Box::new(AnalogOutput::new(channels.unwrap_or(DAC_CHANNELS))),
+            DeviceKind::DigitalIo { groups } => Box::new(DigitalIo::new(groups.unwrap_or(DIGITAL_GROUPS))),
+        })
+    }
+
+    // Bring up the installation on a CPU and core: fit the core, set the
+    // switches, attach the devices and load the program.  The CPU is left
+    // running at the start address, or halted if there is none.  Returns the
+    // symbols of the program, if any.
+    pub fn build(&self, cpu: &mut Cpu, memory: &mut Memory) -> Result<Option<SymbolTable>, ConfigError> {
+        let machine = &self.machine;
+        let core = machine.core.unwrap_or(32_768);
+        if !core.is_power_of_two() || !(4_096..=32_768).contains(&core) {
+            return Err(ConfigError::CoreSize(core));
+        }
+        cpu.core_words = core;
+        if machine.host_calls && cpu.host_calls.is_none() {
+            cpu.host_calls = Some(HostCalls::new());
+        }
+        if machine.uninitialized && memory.shadow.is_none() {
+            memory.track_writes();                      // before anything is loaded
+        }
+        if machine.self_modifying && memory.code_watch.is_none() {
+            memory.watch_code();
+        }
+        for range in &self.protected {
+            if range.last() < range.start || range.last() as usize >= core {
+                return Err(ConfigError::Protect(*range));
+            }
+            memory.protect(*range);
+        }
+        cpu.int_log.enabled |= machine.interrupt_log;
+        cpu.sense_switches = [false; 4];
+        for switch in &machine.sense_switches {
+            *cpu.sense_switches.get_mut(*switch as usize).ok_or(ConfigError::SenseSwitch(*switch))? = true;
+        }
+        for device in &self.devices {
+            let name = format!("{:?} at {:02X}", device.kind, device.code());
+            cpu.io.attach(device.code(), device.level, self.device(device)?)
+                .map_err(|err| ConfigError::Bus { device: name, err })?;
+        }
+        let mut symbols = match &machine.symbols {
+            Some(file) => Some(SymbolTable::load_file(self.resolve(file))
+                .map_err(|err| ConfigError::Load { path: self.resolve(file), message: err.to_string() })?),
+            None => None,
+        };
+        if machine.boot {
+            let reader = self.devices.iter()
+                .find(|device| matches!(device.kind, DeviceKind::PaperTapeReader { .. }))
+                .ok_or(ConfigError::NoReader)?;
+            boot::load(cpu, memory, reader.code());
+            return Ok(symbols);
+        }
+        cpu.mode = Mode::HALT;
+        let mut transfer = None;
+        if let Some(file) = &machine.image {
+            let path = self.resolve(file);
+            let load_error = |message: String| ConfigError::Load { path: path.clone(), message };
+            let extension = path.extension().and_then(|extension| extension.to_str()).unwrap_or("");
+            transfer = if extension == "s" || extension == "asm" {
+                let assembly = assembler::assemble_file(&path).map_err(|err| load_error(err.to_string()))?;
+                symbols.get_or_insert_with(|| assembly.symbols.clone());
+                assembly.load(memory)
+            } else {
+                absolute::load_file(&path, memory).map_err(|err| load_error(err.to_string()))?
+            };
+        }
+        if let Some(start) = machine.pcr.or(transfer.filter(|transfer| transfer & NO_TRANSFER == 0)) {
+            cpu.set_pcr(start);
+            cpu.mode = Mode::RUN;
+        }
+        Ok(symbols)
+    }
+
+    // write the output files of the devices at the end of a run
+    pub fn finish(&self, cpu: &mut Cpu) -> Result<(), ConfigError> {
+        for device in &self.devices {
+            match &device.kind {
+                DeviceKind::PaperTapePunch { file: Some(file) } => {
+                    if let Some(punch) = cpu.io.device_mut::<PaperTapePunch>(device.code()) {
+                        punch.save(self.resolve(file)).map_err(|err| self.open_error(file, err))?;
+                    }
+                },
+                DeviceKind::AnalogOutput { file: Some(file), .. } => {
+                    if let Some(dac) = cpu.io.device_mut::<AnalogOutput>(device.code()) {
+                        dac.save_csv(self.resolve(file)).map_err(|err| self.open_error(file, err))?;
+                    }
+                },
+                DeviceKind::Teletype { output: Some(file), .. } => {
+                    if let Some(teletype) = cpu.io.device_mut::<Teletype>(device.code()) {
+                        teletype.save(self.resolve(file)).map_err(|err| self.open_error(file, err))?;
+                    }
+                },
+                DeviceKind::LinePrinter { .. } => {
+                    if let Some(printer) = cpu.io.device_mut::<LinePrinter>(device.code()) {
+                        printer.flush();
+                    }
+                },
+                _ => {},
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,441 @@
+// Scripted console, Rhai embedded
+//
+// A console script drives the machine the way an operator at the front
+// panel would, with a program: breakpoint actions, memory dumpers and test
+// drivers, without rebuilding the emulator.  Numbers are Rhai integers;
+// registers and core words read back as their 16 bit unsigned value.
+//
+//      reg("acr")  set_reg("pcr", 0x100)     acr ixr pcr status mbr mar int_enb int_act int_req
+//      peek(a)  poke(a, w)  dump(a, n)  disasm(a)  listing(a, b)
+//      symbol("LOOP")  describe(a)
+//      break_at(a)  break_at(a, |pcr| { ...; true })  clear_break(a)  clear_breaks()  breakpoints()
+//      run()  run(n)  run_from(a)  step()  step(n)  stop()  reset()  halt_reason()
+//      cycles()  instructions()  seconds()
+//      devices()  din(code)  dot(code, w)  interrupt(level)  switch(n, on)
+//      log_interrupts(on)  interrupt_log()
+//      interrupt_at_cycle(level, c)  interrupt_at_instruction(level, n)
+//      interrupt_every_cycles(level, first, period)  interrupt_every_instructions(level, first, period)
+//      cancel_interrupt(id)  fired()
+//      type_text("RUN\n")  printed()
+//
+// run() presses RUN and returns "halt" when the machine stops, "break" at a
+// breakpoint or "limit" after n instructions.  A breakpoint with an action
+// calls it with the PCR before the instruction there runs; the run carries on
+// if the action returns true and stops at the breakpoint otherwise.
+//
+//      break_at(symbol("ALARM"), |pcr| { print(`alarm with ACR ${reg("acr")}`); true });
+//      while run() == "break" { print(dump(symbol("TABLE"), 16)); }
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::rc::Rc;
+
+use rhai::{Array, Dynamic, Engine, EvalAltResult, FnPtr, Map, NativeCallContext, Scope, AST, INT};
+
+use crate::cpu::{cycles_to_seconds, Cpu, HaltReason, Memory, Mode};
+use crate::disasm;
+use crate::symbols::SymbolTable;
+use crate::teletype::{Teletype, TELETYPE_ADDRESS};
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+const CHUNK:u64 = 10_000;                       // instructions run between limit checks
+
+#[derive(Debug)]
+pub enum ConsoleError {
+    Io(io::Error),
+    Script(Box<EvalAltResult>),
+}
+
+impl fmt::Display for ConsoleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConsoleError::Io(err) => write!(f, "console script i/o error: {}", err),
+            ConsoleError::Script(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for ConsoleError {}
+
+impl From<io::Error> for ConsoleError {
+    fn from(err: io::Error) -> Self {
+        ConsoleError::Io(err)
+    }
+}
+
+impl From<Box<EvalAltResult>> for ConsoleError {
+    fn from(err: Box<EvalAltResult>) -> Self {
+        ConsoleError::Script(err)
+    }
+}
+
+// what the console works on, shared with the functions the scripts call
+pub struct Machine {
+    pub cpu: Cpu,
+    pub memory: Memory,
+    pub symbols: Option<SymbolTable>,
+    pub breakpoints: BTreeMap<u16, Option<FnPtr>>,  // address and action
+}
+
+type Shared = Rc<RefCell<Machine>>;
+
+fn address(value: INT) -> ScriptResult<u16> {
+    if (0..0x8000).contains(&value) {
+        Ok(value as u16)
+    } else {
+        Err(format!("{} is not a core address", value).into())
+    }
+}
+
+fn device_code(value: INT) -> ScriptResult<u8> {
+    u8::try_from(value).map_err(|_| format!("{} is not a device code", value).into())
+}
+
+fn level(value: INT) -> ScriptResult<u8> {
+    if (0..16).contains(&value) {
+        Ok(value as u8)
+    } else {
+        Err(format!("there is no interrupt level {}", value).into())
+    }
+}
+
+fn count(value: INT) -> ScriptResult<u64> {
+    u64::try_from(value).map_err(|_| format!("{} is not a cycle or instruction count", value).into())
+}
+
+fn reg(machine: &Machine, name: &str) -> ScriptResult<INT> {
+    let cpu = &machine.cpu;
+    Ok(match name {
+        "acr" => cpu.acr as u16 as INT,
+        "ixr" => cpu.ixr as u16 as INT,
+        "pcr" => cpu.pcr as INT,
+        "status" => cpu.status as INT,
+        "mbr" => cpu.mbr as INT,
+        "mar" => cpu.mar as INT,
+        "int_enb" => cpu.int_enb as INT,
+        "int_act" => cpu.int_act as INT,
+        "int_req" => cpu.int_req as INT,
+        _ => return Err(format!("there is no register {}", name).into()),
+    })
+}
+
+fn set_reg(machine: &mut Machine, name: &str, value: INT) -> ScriptResult<()> {
+    let cpu = &mut machine.cpu;
+    let word = value as u16;
+    match name {
+        "acr" => cpu.acr = word as i16,
+        "ixr" => cpu.ixr = word as i16,
+        "pcr" => cpu.set_pcr(word),
+        "status" => cpu.status = word,
+        "int_enb" => cpu.int_enb = word,
+        "int_act" => cpu.int_act = word,
+        "int_req" => cpu.int_req = word,
+        _ => return Err(format!("register {} cannot be set", name).into()),
+    }
+    Ok(())
+}
+
+// eight words to a line, address first
+fn dump(memory: &Memory, start: u16, count: u16) -> String {
+    let mut out = String::new();
+    let end = (start as u32 + count as u32).min(0x8000) as u16;
+    for line in (start..end).step_by(8) {
+        out += &format!("{:04X} ", line);
+        for address in line..(line + 8).min(end) {
+            out += &format!(" {:04X}", memory.core[address as usize] as u16);
+        }
+        out.push('\n');
+    }
+    out
+}
+
+// Run until the machine stops, a breakpoint stops it or limit instructions
+// have run.  The instruction at the PCR runs first even if it has a
+// breakpoint, so a run can continue from one.
+fn run(context: &NativeCallContext, machine: &Shared, limit: Option<u64>) -> ScriptResult<String> {
+    let start = {
+        let mut machine = machine.borrow_mut();
+        machine.cpu.mode = Mode::RUN;
+        machine.cpu.halt_reason = None;
+        machine.cpu.instructions
+    };
+    let mut first = true;
+    loop {
+        let action = {
+            let machine = &mut *machine.borrow_mut();
+            let run = machine.cpu.instructions - start;
+            if machine.cpu.mode != Mode::RUN {
+                return Ok("halt".into());
+            }
+            if limit.is_some_and(|limit| run >= limit) {
+                return Ok("limit".into());
+            }
+            let remaining = limit.map_or(CHUNK, |limit| (limit - run).min(CHUNK));
+            if machine.breakpoints.is_empty() {
+                machine.cpu.run(&mut machine.memory, remaining);
+                continue;
+            }
+            match machine.breakpoints.get(&machine.cpu.pcr) {
+                Some(action) if !first => action.clone().map(|action| (action, machine.cpu.pcr)),
+                _ => {
+                    first = false;
+                    machine.cpu.step(&mut machine.memory);
+                    continue;
+                },
+            }
+        };
+        match action {                          // the machine is free while the action runs
+            None => return Ok("break".into()),
+            Some((action, pcr)) => {
+                if !action.call_within_context::<Dynamic>(context, (pcr as INT,))?.as_bool().unwrap_or(false) {
+                    return Ok("break".into());
+                }
+                let machine = &mut *machine.borrow_mut();
+                machine.cpu.step(&mut machine.memory);
+            },
+        }
+    }
+}
+
+pub struct Console {
+    engine: Engine,
+    scope: Scope<'static>,
+    functions: AST,                             // functions and closures of earlier scripts
+    machine: Shared,
+}
+
+impl Console {
+    pub fn new(cpu: Cpu, memory: Memory, symbols: Option<SymbolTable>) -> Self {
+        let machine = Rc::new(RefCell::new(Machine { cpu, memory, symbols, breakpoints: BTreeMap::new() }));
+        let mut engine = Engine::new();
+        register(&mut engine, &machine);
+        Console { engine, scope: Scope::new(), functions: AST::empty(), machine }
+    }
+
+    pub fn machine(&self) -> std::cell::RefMut<'_, Machine> {
+        self.machine.borrow_mut()
+    }
+
+    // evaluate script text, keeping variables and functions from one call to
+    // the next, so a breakpoint action still runs after its script has ended
+    pub fn eval(&mut self, script: &str) -> Result<Dynamic, ConsoleError> {
+        let compiled = self.engine.compile_with_scope(&self.scope, script).map_err(Box::<EvalAltResult>::from)?;
+        let ast = self.functions.merge(&compiled);
+        self.functions = ast.clone_functions_only();
+        Ok(self.engine.eval_ast_with_scope::<Dynamic>(&mut self.scope, &ast)?)
+    }
+
+    pub fn eval_file<P: AsRef<Path>>(&mut self, path: P) -> Result<Dynamic, ConsoleError> {
+        let script = fs::read_to_string(path)?;
+        self.eval(&script)
+    }
+
+    // hand the machine back
+    pub fn into_parts(self) -> (Cpu, Memory) {
+        let machine = &mut *self.machine.borrow_mut();
+        (std::mem::take(&mut machine.cpu), std::mem::take(&mut machine.memory))
+    }
+}
+
+fn register(engine: &mut Engine, machine: &Shared) {
+    let m = machine.clone();
+    engine.register_fn("reg", move |name: &str| reg(&m.borrow(), name));
+    let m = machine.clone();
+    engine.register_fn("set_reg", move |name: &str, value: INT| set_reg(&mut m.borrow_mut(), name, value));
+    let m = machine.clone();
+    engine.register_fn("cycles", move || m.borrow().cpu.cycles as INT);
+    let m = machine.clone();
+    engine.register_fn("instructions", move || m.borrow().cpu.instructions as INT);
+    let m = machine.clone();
+    engine.register_fn("seconds", move || cycles_to_seconds(m.borrow().cpu.cycles));
+    let m = machine.clone();
+    engine.register_fn("halt_reason", move || match m.borrow().cpu.halt_reason {
+        Some(HaltReason::Instruction) => "hlt",
+        Some(HaltReason::Illegal) => "illegal",
+        Some(HaltReason::Exit(_)) => "exit",
+        Some(HaltReason::Protected(_)) => "protected",
+        None => "",
+    });
+
+    // core and symbols
+    let m = machine.clone();
+    engine.register_fn("peek", move |at: INT| -> ScriptResult<INT> {
+        Ok(m.borrow().memory.core[address(at)? as usize] as u16 as INT)
+    });
+    let m = machine.clone();
+    engine.register_fn("poke", move |at: INT, value: INT| -> ScriptResult<()> {
+        m.borrow_mut().memory.write(address(at)? as usize, value as u16 as i16);
+        Ok(())
+    });
+    let m = machine.clone();
+    engine.register_fn("dump", move |at: INT, count: INT| -> ScriptResult<String> {
+        Ok(dump(&m.borrow().memory, address(at)?, count.clamp(0, 0x8000) as u16))
+    });
+    let m = machine.clone();
+    engine.register_fn("disasm", move |at: INT| -> ScriptResult<String> {
+        let machine = m.borrow();
+        let at = address(at)?;
+        Ok(disasm::disassemble_at(at, machine.memory.core[at as usize] as u16, machine.symbols.as_ref()))
+    });
+    let m = machine.clone();
+    engine.register_fn("listing", move |start: INT, end: INT| -> ScriptResult<String> {
+        let machine = m.borrow();
+        Ok(disasm::listing(&machine.memory, address(start)?, address(end)?, machine.symbols.as_ref()))
+    });
+    let m = machine.clone();
+    engine.register_fn("symbol", move |name: &str| -> ScriptResult<INT> {
+        m.borrow().symbols.as_ref().and_then(|symbols| symbols.lookup(name))
+            .map(|address| address as INT)
+            .ok_or_else(|| format!("there is no symbol {}", name).into())
+    });
+    let m = machine.clone();
+    engine.register_fn("describe", move |at: INT| -> ScriptResult<String> {
+        let at = address(at)?;
+        Ok(m.borrow().symbols.as_ref().map_or(format!("{:04X}", at), |symbols| symbols.describe(at)))
+    });
+
+    // breakpoints
+    let m = machine.clone();
+    engine.register_fn("break_at", move |at: INT| -> ScriptResult<()> {
+        m.borrow_mut().breakpoints.insert(address(at)?, None);
+        Ok(())
+    });
+    let m = machine.clone();
+    engine.register_fn("break_at", move |at: INT, action: FnPtr| -> ScriptResult<()> {
+        m.borrow_mut().breakpoints.insert(address(at)?, Some(action));
+        Ok(())
+    });
+    let m = machine.clone();
+    engine.register_fn("clear_break", move |at: INT| -> ScriptResult<bool> {
+        Ok(m.borrow_mut().breakpoints.remove(&address(at)?).is_some())
+    });
+    let m = machine.clone();
+    engine.register_fn("clear_breaks", move || m.borrow_mut().breakpoints.clear());
+    let m = machine.clone();
+    engine.register_fn("breakpoints", move || -> Array {
+        m.borrow().breakpoints.keys().map(|address| Dynamic::from(*address as INT)).collect()
+    });
+
+    // run control
+    let m = machine.clone();
+    engine.register_fn("run", move |context: NativeCallContext| run(&context, &m, None));
+    let m = machine.clone();
+    engine.register_fn("run", move |context: NativeCallContext, limit: INT| run(&context, &m, Some(limit.max(0) as u64)));
+    let m = machine.clone();
+    engine.register_fn("run_from", move |context: NativeCallContext, at: INT| -> ScriptResult<String> {
+        m.borrow_mut().cpu.set_pcr(address(at)?);
+        run(&context, &m, None)
+    });
+    let m = machine.clone();
+    engine.register_fn("step", move || -> INT {
+        let machine = &mut *m.borrow_mut();
+        machine.cpu.step(&mut machine.memory);
+        machine.cpu.pcr as INT
+    });
+    let m = machine.clone();
+    engine.register_fn("step", move |count: INT| -> INT {
+        let machine = &mut *m.borrow_mut();
+        for _ in 0..count.max(0) {
+            machine.cpu.step(&mut machine.memory);
+        }
+        machine.cpu.pcr as INT
+    });
+    let m = machine.clone();
+    engine.register_fn("stop", move || m.borrow_mut().cpu.mode = Mode::HALT);
+    let m = machine.clone();
+    engine.register_fn("reset", move || m.borrow_mut().cpu.reset());
+
+    // devices and console switches
+    let m = machine.clone();
+    engine.register_fn("devices", move || -> Array {
+        m.borrow().cpu.io.devices().into_iter().map(|device| {
+            let mut info = Map::new();
+            info.insert("base".into(), (device.base as INT).into());
+            info.insert("count".into(), (device.count as INT).into());
+            info.insert("level".into(), device.level.map_or(Dynamic::UNIT, |level| (level as INT).into()));
+            info.insert("name".into(), device.name.into());
+            info.insert("busy".into(), device.busy.into());
+            Dynamic::from_map(info)
+        }).collect()
+    });
+    let m = machine.clone();
+    engine.register_fn("din", move |code: INT| -> ScriptResult<INT> {
+        let cpu = &mut m.borrow_mut().cpu;
+        Ok(cpu.io.din(device_code(code)?, cpu.cycles) as u16 as INT)
+    });
+    let m = machine.clone();
+    engine.register_fn("dot", move |code: INT, value: INT| -> ScriptResult<()> {
+        let cpu = &mut m.borrow_mut().cpu;
+        cpu.io.dot(device_code(code)?, value as u16 as i16, cpu.cycles);
+        Ok(())
+    });
+    let m = machine.clone();
+    engine.register_fn("interrupt", move |at: INT| -> ScriptResult<()> {
+        m.borrow_mut().cpu.int_req |= 0x0001 << level(at)?;
+        Ok(())
+    });
+
+    // scheduled interrupt requests, returning an id to cancel them by
+    let m = machine.clone();
+    engine.register_fn("interrupt_at_cycle", move |at: INT, cycle: INT| -> ScriptResult<INT> {
+        Ok(m.borrow_mut().cpu.scheduler.at_cycle(level(at)?, count(cycle)?) as INT)
+    });
+    let m = machine.clone();
+    engine.register_fn("interrupt_at_instruction", move |at: INT, instruction: INT| -> ScriptResult<INT> {
+        Ok(m.borrow_mut().cpu.scheduler.at_instruction(level(at)?, count(instruction)?) as INT)
+    });
+    let m = machine.clone();
+    engine.register_fn("interrupt_every_cycles", move |at: INT, first: INT, period: INT| -> ScriptResult<INT> {
+        Ok(m.borrow_mut().cpu.scheduler.every_cycles(level(at)?, count(first)?, count(period)?) as INT)
+    });
+    let m = machine.clone();
+    engine.register_fn("interrupt_every_instructions", move |at: INT, first: INT, period: INT| -> ScriptResult<INT> {
+        Ok(m.borrow_mut().cpu.scheduler.every_instructions(level(at)?, count(first)?, count(period)?) as INT)
+    });
+    let m = machine.clone();
+    engine.register_fn("cancel_interrupt", move |id: INT| m.borrow_mut().cpu.scheduler.cancel(id as u32));
+    let m = machine.clone();
+    engine.register_fn("fired", move || -> Array {
+        m.borrow().cpu.scheduler.history.iter().map(|firing| {
+            let mut info = Map::new();
+            info.insert("id".into(), (firing.id as INT).into());
+            info.insert("level".into(), (firing.level as INT).into());
+            info.insert("cycle".into(), (firing.cycle as INT).into());
+            info.insert("instruction".into(), (firing.instruction as INT).into());
+            Dynamic::from_map(info)
+        }).collect()
+    });
+    let m = machine.clone();
+    engine.register_fn("log_interrupts", move |on: bool| m.borrow_mut().cpu.int_log.enabled = on);
+    let m = machine.clone();
+    engine.register_fn("interrupt_log", move || -> Array {
+        m.borrow().cpu.int_log.events.iter().map(|event| Dynamic::from(event.to_string())).collect()
+    });
+    let m = machine.clone();
+    engine.register_fn("switch", move |switch: INT, on: bool| -> ScriptResult<()> {
+        let mut machine = m.borrow_mut();
+        let switch = usize::try_from(switch).ok()
+            .and_then(|switch| machine.cpu.sense_switches.get_mut(switch))
+            .ok_or_else(|| format!("there is no sense switch {}", switch))?;
+        *switch = on;
+        Ok(())
+    });
+    let m = machine.clone();
+    engine.register_fn("type_text", move |text: &str| -> ScriptResult<()> {
+        teletype(&mut m.borrow_mut().cpu)?.type_text(text);
+        Ok(())
+    });
+    let m = machine.clone();
+    engine.register_fn("printed", move || -> ScriptResult<String> {
+        Ok(teletype(&mut m.borrow_mut().cpu)?.printed_text())
+    });
+}
+
+fn teletype(cpu: &mut Cpu) -> ScriptResult<&mut Teletype> {
+    cpu.io.device_mut::<Teletype>(TELETYPE_ADDRESS).ok_or_else(|| "there is no teletype".into())
+}
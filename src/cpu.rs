@@ -0,0 +1,1046 @@
+/*Rustheon Raytheon 703 emulator written in Rust
+
+MIT License
+Copyright (c) 2023 Darwin Geiselbrecht
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+use crate::dispatch::{Decoded, DispatchCache, Handler, Reference};
+use crate::hostcall::HostCalls;
+use crate::instruction::{Instruction, Opcode, Operand};
+use crate::interrupt::{InterruptEvent, InterruptEventKind, InterruptLog};
+use crate::io::IoBus;
+use crate::profile::Profiler;
+use crate::protect::{ProtectAction, ProtectedRange, Protection};
+use crate::schedule::InterruptScheduler;
+use crate::selfmod::CodeWatch;
+use crate::shadow::ShadowMap;
+use crate::vcd::{VcdSample, VcdWriter};
+
+pub const MAX_INST:i32 = 1000;         // max instructions before checking controls 
+
+// masks defining the contexts of the status register
+pub const EXR_WORD_MASK:u16 = 0xF000;   // word portion of exr register in status word
+pub const EXR_BYTE_MASK:u16 = 0xF800;   // byte portion of exr in status word
+pub const ADFNEG:u16 =   0x0400;        // compare negative  flag
+pub const ADFEQL:u16 =   0x0200;        // compare equal flag
+pub const ADFOVF:u16 =   0x0100;        // overflow flag
+pub const ADFGBL:u16 =   0x0080;        // global mode flag
+
+pub const CYCLE_NS:u64 = 1_750;         // core memory cycle time in nanoseconds
+
+pub fn cycles_to_seconds(cycles:u64) -> f64 {   // emulated time of a cycle count
+    cycles as f64 * CYCLE_NS as f64 * 1e-9
+}
+pub fn seconds_to_cycles(seconds:f64) -> u64 {
+    (seconds * 1e9 / CYCLE_NS as f64).round().max(0.0) as u64
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mode{
+    HALT,
+    RUN,
+    STEP
+}
+// why the machine last left RUN mode
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HaltReason{
+    Instruction,                        // HLT
+    Illegal,                            // word that is not an instruction
+    Exit(u16),                          // host call EXIT, with the program's status
+    Protected(u16),                     // store to a protected word, with its address
+}
+enum ByteSelect{
+    LEFT,
+    RIGHT,
+}
+pub struct Memory {
+    pub core:[i16;32_768],
+    pub shadow: Option<ShadowMap>,      // written words, when uninitialized reads are reported
+    pub protection: Option<Protection>, // ranges the program may not store into
+    pub code_watch: Option<CodeWatch>,  // fetched words, when stores into code are reported
+}
+impl Memory{
+    pub fn new() -> Self {
+        Memory { core: [0i16;32_768], shadow: None, protection: None, code_watch: None }
+    }
+    // report reads of words not written from now on; start before loading
+    pub fn track_writes(&mut self) {
+        self.shadow = Some(ShadowMap::new());
+    }
+    // report program stores into words fetched as instructions from now on
+    pub fn watch_code(&mut self) {
+        self.code_watch = Some(CodeWatch::new());
+    }
+    // deposit a word, from the loader, the console, a device or a program store
+    pub fn write(&mut self, address:usize, word:i16) {
+        self.core[address] = word;
+        if let Some(shadow) = self.shadow.as_mut() {
+            shadow.written(address);
+        }
+    }
+    // protect a range against program stores
+    pub fn protect(&mut self, range:ProtectedRange) {
+        self.protection.get_or_insert_with(Protection::new).ranges.push(range);
+    }
+    // a store by the program at pcr, made unless the word is protected by a
+    // halt or drop range; false when the protection halts the machine
+    pub fn store(&mut self, address:usize, word:i16, pcr:u16) -> bool {
+        if let Some(protection) = self.protection.as_mut() {
+            match protection.check(address, word, pcr) {
+                None => {},
+                Some(ProtectAction::Halt) => return false,
+                Some(ProtectAction::Drop) => return true,
+                Some(ProtectAction::Log) => {},
+            }
+        }
+        if let Some(watch) = self.code_watch.as_mut() {
+            watch.store(address, self.core[address], word, pcr);
+        }
+        self.write(address, word);
+        true
+    }
+    pub fn write_block(&mut self, address:usize, words:&[i16]) {
+        self.core[address..address + words.len()].copy_from_slice(words);
+        if let Some(shadow) = self.shadow.as_mut() {
+            (address..address + words.len()).for_each(|address| shadow.written(address));
+        }
+    }
+    // a read by the program, as an operand or an instruction fetched from pcr
+    #[inline]
+    pub fn read(&mut self, address:usize, pcr:u16, fetch:bool) -> i16 {
+        if let Some(shadow) = self.shadow.as_mut() {
+            shadow.read(address, pcr, fetch);
+        }
+        if fetch {
+            if let Some(watch) = self.code_watch.as_mut() {
+                watch.fetched(address);
+            }
+        }
+        self.core[address]
+    }
+}
+impl Default for Memory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+pub struct Cpu {
+    pub mode:Mode,
+    pub acr:i16,
+    pub ixr:i16,                        // the index register and extension of the acr
+    pub status: u16,                    // status register
+    pub pcr: u16,                       // program counter
+    pub mbr: u16,                       // memory buffer register
+    pub mar: usize,                     // memory address register
+    pub inr: u8,                        // instruction register
+    pub int_req: u16,                   // interrupt request register   // int 15 (highest) is MSB
+    pub int_act: u16,                   // interrupt active register
+    pub int_enb: u16,                   // interrupt enabled register
+    pub int_masked: bool,               // interrupt mask flip/flop
+    pub int_inhibit: bool,              // hold off recognition for one instruction
+    pub int_seen: u16,                  // requests already logged
+    pub int_log: InterruptLog,          // interrupt request/entry/exit history
+    pub cycles: u64,                    // memory cycles since power on, the emulated clock
+    pub instructions: u64,              // instructions executed since power on
+    pub io: IoBus,                      // devices on the DIN/DOT bus
+    pub scheduler: InterruptScheduler,  // interrupt requests injected by the host
+    pub vcd: Option<VcdWriter>,         // waveform dump, sampled at every instruction boundary
+    pub profiler: Option<Profiler>,     // execution counts and cycles by address and level
+    pub dispatch: Option<DispatchCache>, // predecoded handlers, for long runs
+    pub core_words: usize,              // core fitted, a power of two; addresses wrap at it
+    pub sense_switches: [bool; 4],      // console switches tested by SS0..SS3
+    pub external_sense: bool,           // external sense line tested by SSE
+    pub halt_reason: Option<HaltReason>, // why the last run stopped, None until one has
+    pub host_calls: Option<HostCalls>,  // TRAP services for test programs, off unless set
+
+}
+impl Cpu{                           // create new implementation of Cpu
+    pub fn new() -> Self {
+        Cpu {
+            mode:Mode::HALT,
+            acr:0,// acr:0, // this->acr = 0;
+            ixr:0,                                                
+            status:0,                      
+            pcr: 0,                        
+            mbr: 0,                       
+            mar: 0,
+            inr: 0,
+            int_req:0,                        
+            int_act: 0,                       
+            int_enb:0,
+            int_masked: false,   
+            int_inhibit: false,
+            int_seen: 0,
+            int_log: InterruptLog::new(),
+            cycles: 0,
+            instructions: 0,
+            io: IoBus::new(),
+            scheduler: InterruptScheduler::new(),
+            vcd: None,
+            profiler: None,
+            dispatch: None,
+            core_words: 32_768,
+            sense_switches: [false; 4],
+            external_sense: false,
+            halt_reason: None,
+            host_calls: None,
+        }
+    }
+    // master clear, devices stay attached but are reset
+    pub fn reset(&mut self) {
+        self.mode = Mode::HALT;
+        self.acr = 0;
+        self.ixr = 0;
+        self.status = 0;
+        self.pcr = 0;
+        self.mbr = 0;
+        self.mar = 0;
+        self.inr = 0;
+        self.int_req = 0;
+        self.int_act = 0;
+        self.int_enb = 0;
+        self.int_masked = false;
+        self.int_inhibit = false;
+        self.int_seen = 0;
+        self.halt_reason = None;
+        self.io.reset();
+    }
+    // load the PCR from the panel or a loader, the EXR follows its page
+    pub fn set_pcr(&mut self, pcr:u16) {
+        self.pcr = pcr & 0x7FFF;
+        self.copy_pcr_to_exr();
+    }
+    // instruction execution loop, broken periodically to update console
+    pub fn execute(&mut self,memory:&mut Memory) {
+        let mut inst_counter = 0;               // counter for number instructions before checking console
+        'executing: loop {
+            //println!("Top of executing loop");
+            match self.mode {
+                Mode::HALT => {
+                    println!{ "Halt instruction encountered"};
+                    self.print_registers();
+                    break 'executing;
+                },
+                Mode::STEP => {
+                    self.step(memory);
+                    break 'executing;
+                },
+                Mode::RUN => {
+                    self.step(memory);
+                },
+            }
+            inst_counter += 1;
+            if inst_counter >= MAX_INST {
+                break 'executing;                   // give the caller a chance to check controls
+            }
+        }
+    }
+    // execute one instruction and service the devices, whatever the mode
+    pub fn step(&mut self,memory:&mut Memory) {
+        if self.profiler.is_some() {
+            self.profiled_step(memory);
+        } else {
+            self.decode(memory);
+            self.service_io(memory);
+            self.check_interrupts(memory);            // every instruction ends at a boundary
+        }
+        self.sample_vcd();
+    }
+    // Run through the dispatch cache until the machine leaves RUN mode or
+    // count instructions have run, returning the number run.  This is the
+    // path for long runs: instructions dispatch straight to their predecoded
+    // handlers, and the device service and interrupt check at the boundary
+    // are skipped while there is nothing for them to do (no devices, no
+    // scheduled or pending requests, no inhibit), so results match step.
+    // With a VCD or profiler attached it steps as usual.
+    pub fn run(&mut self,memory:&mut Memory,count:u64) -> u64 {
+        let start = self.instructions;
+        if self.vcd.is_some() || self.profiler.is_some() {
+            while self.mode == Mode::RUN && self.instructions - start < count {
+                self.step(memory);
+            }
+            return self.instructions - start;
+        }
+        let mut cache = self.dispatch.take().unwrap_or_default();
+        while self.mode == Mode::RUN && self.instructions - start < count {
+            let address = self.fetch_address();
+            let decoded = cache.lookup(address, memory.core[address] as u16, Cpu::predecode);
+            self.fetch(memory);
+            self.advance_pcr();
+            (decoded.handler)(self, memory, decoded.reference);
+            if (self.int_req | self.int_seen) != 0 || self.int_inhibit ||
+               !self.io.is_empty() || !self.scheduler.is_idle() {
+                self.service_io(memory);
+                self.check_interrupts(memory);
+            }
+        }
+        self.dispatch = Some(cache);
+        self.instructions - start
+    }
+    // step, reporting the instruction and any interrupt entry to the profiler
+    fn profiled_step(&mut self,memory:&mut Memory) {
+        let address = self.pcr;
+        let int_act = self.int_act;
+        let is_jsx = (memory.core[self.fetch_address()] as u16 >> 12) == 0x2;
+        let start = self.cycles;
+        self.decode(memory);
+        let executed = self.cycles - start;
+        let target = self.pcr;
+        self.service_io(memory);
+        let before_entry = self.cycles;
+        self.check_interrupts(memory);
+        if let Some(profiler) = self.profiler.as_mut() {
+            profiler.record(address, int_act, executed, is_jsx.then_some(target));
+            if self.cycles != before_entry {
+                profiler.enter(self.int_act, self.cycles - before_entry);
+            }
+        }
+    }
+    // start a waveform dump to path, with busy lines for the devices attached now
+    pub fn start_vcd<P: AsRef<std::path::Path>>(&mut self, path: P) -> std::io::Result<()> {
+        self.vcd = Some(VcdWriter::create(path, &self.io.devices())?);
+        self.sample_vcd();                            // initial values at the current cycle
+        Ok(())
+    }
+    fn sample_vcd(&mut self) {
+        let Some(vcd) = self.vcd.as_mut() else { return };
+        let busy: Vec<(u8, bool)> = self.io.busy_lines().collect();
+        vcd.sample(&VcdSample {
+            cycle: self.cycles,
+            int_req: self.int_req,
+            int_act: self.int_act,
+            int_enb: self.int_enb,
+            int_masked: self.int_masked,
+            pcr: self.pcr,
+            acr: self.acr,
+            busy: &busy,
+        });
+    }
+    // let devices catch up to the current cycle and post their interrupt requests
+    fn service_io(&mut self,memory:&mut Memory) {
+        self.int_req = self.int_req | self.io.service(self.cycles, memory);
+    }
+    // first level decoder
+    fn decode(&mut self,memory:&mut Memory) {
+        let address = self.fetch_address();
+        let decoded = match self.dispatch.as_mut() {
+            Some(cache) => cache.lookup(address, memory.core[address] as u16, Cpu::predecode),
+            None => Cpu::predecode(memory.core[address] as u16),
+        };
+        self.fetch(memory);                         // fetch instruction into MBR and INR
+        self.advance_pcr();                         // increment the program counter
+        (decoded.handler)(self, memory, decoded.reference);
+    }
+    fn operand(&mut self,reference:Reference) {
+        self.cycles += 1;                           // operand cycle
+        self.compute_word_address(reference);      // form the effective word address in mar
+    }
+    fn byte_operand(&mut self,reference:Reference) -> ByteSelect {
+        self.cycles += 1;                           // operand cycle
+        self.compute_byte_address(reference)       // form the word address in mar, and which byte
+    }
+    // the handler for an instruction word, doing everything the instruction
+    // does after the fetch, and the address field it refers through; the
+    // instruction set itself is in instruction.rs
+    fn predecode(word:u16) -> Decoded {
+        match Instruction::decode(word) {
+            Some(instruction) => Decoded {
+                handler: Cpu::handler(instruction.opcode),
+                reference: match instruction.opcode.operand() {
+                    Operand::Address => Reference { address: instruction.operand, indexed: instruction.indexed },
+                    _ => Reference::default(),
+                },
+            },
+            None => Decoded { handler: |cpu, _, _| cpu.illegal_instruction(), reference: Reference::default() },
+        }
+    }
+    fn handler(opcode:Opcode) -> Handler {
+        match opcode {
+            Opcode::JMP => |cpu, _, reference| { cpu.operand(reference); cpu.jmp(); cpu.copy_pcr_to_exr() },
+            Opcode::JSX => |cpu, _, reference| { cpu.operand(reference); cpu.jsx(); cpu.copy_pcr_to_exr() },
+            Opcode::STB => |cpu, memory, reference| {
+                let byte = cpu.byte_operand(reference); cpu.stb(memory, byte); cpu.copy_pcr_to_exr()
+            },
+            Opcode::CMB => |cpu, memory, reference| {
+                let byte = cpu.byte_operand(reference); cpu.cmb(memory, byte); cpu.copy_pcr_to_exr()
+            },
+            Opcode::LDB => |cpu, memory, reference| {
+                let byte = cpu.byte_operand(reference); cpu.ldb(memory, byte); cpu.copy_pcr_to_exr()
+            },
+            Opcode::STX => |cpu, memory, reference| { cpu.operand(reference); cpu.stx(memory); cpu.copy_pcr_to_exr() },
+            Opcode::STW => |cpu, memory, reference| { cpu.operand(reference); cpu.stw(memory); cpu.copy_pcr_to_exr() },
+            Opcode::LDW => |cpu, memory, reference| { cpu.operand(reference); cpu.ldw(memory); cpu.copy_pcr_to_exr() },
+            Opcode::LDX => |cpu, memory, reference| { cpu.operand(reference); cpu.ldx(memory); cpu.copy_pcr_to_exr() },
+            Opcode::ADD => |cpu, memory, reference| { cpu.operand(reference); cpu.add(memory); cpu.copy_pcr_to_exr() },
+            Opcode::SUB => |cpu, memory, reference| { cpu.operand(reference); cpu.sub(memory); cpu.copy_pcr_to_exr() },
+            Opcode::ORI => |cpu, memory, reference| { cpu.operand(reference); cpu.ori(memory); cpu.copy_pcr_to_exr() },
+            Opcode::ORE => |cpu, memory, reference| { cpu.operand(reference); cpu.ore(memory); cpu.copy_pcr_to_exr() },
+            Opcode::AND => |cpu, memory, reference| { cpu.operand(reference); cpu.and(memory); cpu.copy_pcr_to_exr() },
+            Opcode::CMW => |cpu, memory, reference| { cpu.operand(reference); cpu.cmw(memory); cpu.copy_pcr_to_exr() },
+            Opcode::HLT => |cpu, _, _| cpu.halt(HaltReason::Instruction),
+            Opcode::TRAP => |cpu, memory, _| cpu.trap(memory),
+            Opcode::INRET => |cpu, memory, _| cpu.inret(memory),
+            Opcode::ENB => |cpu, _, _| cpu.enb(),
+            Opcode::DSB => |cpu, _, _| cpu.dsb(),
+            Opcode::SLM => |cpu, _, _| cpu.slm(),
+            Opcode::SGM => |cpu, _, _| cpu.sgm(),
+            Opcode::CEX => |cpu, _, _| cpu.cex(),
+            Opcode::CXE => |cpu, _, _| cpu.cxe(),
+            Opcode::SML => |cpu, _, _| cpu.sml(),
+            Opcode::SMU => |cpu, _, _| cpu.smu(),
+            Opcode::MSK => |cpu, _, _| cpu.msk(),
+            Opcode::UNM => |cpu, _, _| cpu.unm(),
+            Opcode::CLR => |cpu, _, _| cpu.clr(),
+            Opcode::CMP => |cpu, _, _| cpu.cmp(),
+            Opcode::INV => |cpu, _, _| cpu.inv(),
+            Opcode::CAX => |cpu, _, _| cpu.cax(),
+            Opcode::CXA => |cpu, _, _| cpu.cxa(),
+            Opcode::DIN => |cpu, _, _| cpu.din(),
+            Opcode::DOT => |cpu, _, _| cpu.dot(),
+            Opcode::IXS => |cpu, _, _| cpu.ixs(),
+            Opcode::DXS => |cpu, _, _| cpu.dxs(),
+            Opcode::LLB => |cpu, _, _| cpu.llb(),
+            Opcode::CLB => |cpu, _, _| cpu.clb(),
+            Opcode::SAZ => |cpu, _, _| cpu.saz(),
+            Opcode::SAP => |cpu, _, _| cpu.sap(),
+            Opcode::SAM => |cpu, _, _| cpu.sam(),
+            Opcode::SAO => |cpu, _, _| cpu.sao(),
+            Opcode::SLS => |cpu, _, _| cpu.sls(),
+            Opcode::SXE => |cpu, _, _| cpu.sxe(),
+            Opcode::SEQ => |cpu, _, _| cpu.seq(),
+            Opcode::SNE => |cpu, _, _| cpu.sne(),
+            Opcode::SGR => |cpu, _, _| cpu.sgr(),
+            Opcode::SLE => |cpu, _, _| cpu.sle(),
+            Opcode::SNO => |cpu, _, _| cpu.sno(),
+            Opcode::SSE => |cpu, _, _| cpu.sse(),
+            Opcode::SS0 => |cpu, _, _| cpu.ss0(),
+            Opcode::SS1 => |cpu, _, _| cpu.ss1(),
+            Opcode::SS2 => |cpu, _, _| cpu.ss2(),
+            Opcode::SS3 => |cpu, _, _| cpu.ss3(),
+            Opcode::SRA => |cpu, _, _| cpu.sra(),
+            Opcode::SLA => |cpu, _, _| cpu.sla(),
+            Opcode::SRAD => |cpu, _, _| cpu.srad(),
+            Opcode::SLAD => |cpu, _, _| cpu.slad(),
+            Opcode::SRL => |cpu, _, _| cpu.srl(),
+            Opcode::SLL => |cpu, _, _| cpu.sll(),
+            Opcode::SRLD => |cpu, _, _| cpu.srld(),
+            Opcode::SLLD => |cpu, _, _| cpu.slld(),
+            Opcode::SRC => |cpu, _, _| cpu.src(),
+            Opcode::SLC => |cpu, _, _| cpu.slc(),
+            Opcode::SRCD => |cpu, _, _| cpu.srcd(),
+            Opcode::SLCD => |cpu, _, _| cpu.slcd(),
+            Opcode::SRLL => |cpu, _, _| cpu.srll(),
+            Opcode::SLLL => |cpu, _, _| cpu.slll(),
+            Opcode::SRLR => |cpu, _, _| cpu.srlr(),
+            Opcode::SLLR => |cpu, _, _| cpu.sllr(),
+            Opcode::SRCL => |cpu, _, _| cpu.srcl(),
+            Opcode::SLCL => |cpu, _, _| cpu.slcl(),
+            Opcode::SRCR => |cpu, _, _| cpu.srcr(),
+            Opcode::SLCR => |cpu, _, _| cpu.slcr(),
+        }
+    }
+    fn illegal_instruction(&mut self){
+        println!(" Illegal instruction decoded");
+        self.halt(HaltReason::Illegal);
+    }
+    fn trap(&mut self,memory:&mut Memory){             // emulator host call, if enabled
+        match self.host_calls.take() {
+            Some(mut host) => {
+                let done = host.call(self, memory);
+                self.host_calls = Some(host);
+                if !done {
+                    self.illegal_instruction();
+                }
+            },
+            None => self.illegal_instruction(),
+        }
+    }
+    pub fn halt(&mut self,reason:HaltReason){
+        self.mode = Mode::HALT;
+        self.halt_reason = Some(reason);
+    }    
+
+// These are the memory reference handlers    
+    fn read(&self,memory:&mut Memory) -> i16{         // operand at MAR, for the instruction at PCR - 1
+        memory.read(self.mar, self.pcr.wrapping_sub(1), false)
+    }
+    fn store(&mut self,memory:&mut Memory,address:usize,word:i16) -> bool {   // program store, checked for protection
+        if !memory.store(address, word, self.pcr.wrapping_sub(1)) {
+            self.halt(HaltReason::Protected(address as u16));
+            return false;
+        }
+        true
+    }
+    fn jmp(&mut self){               // jump 
+        self.pcr = self.mar as u16;
+    }
+
+    fn jsx(&mut self){               // jump and store index
+        self.ixr = self.pcr as i16;
+        self.pcr = self.mar as u16;
+        self.status = self.status | ADFGBL;  // forces global mode
+    }
+
+    fn stb(&mut self,memory:&mut Memory,left_right:ByteSelect){   // store byte
+        let mut memory_word = memory.core[self.mar];
+        match left_right {
+            ByteSelect::RIGHT => {
+                memory_word = memory_word & ( 0xFF00 as u16)  as i16 | (self.acr & 0x00FF);
+            },
+            ByteSelect::LEFT  => {
+                memory_word = (memory_word & 0x00FF) | self.acr << 8 ;
+            }
+        }
+        self.store(memory, self.mar, memory_word);
+    }
+
+    fn cmb(&mut self,memory:&mut Memory,left_right:ByteSelect){   // compare memory byte
+        let mut memory_word = self.read(memory);
+        self.status = self.status & !(ADFEQL | ADFNEG);
+        match left_right {
+            ByteSelect::RIGHT => {
+                if ((self.acr & 0x00FF) as i8) < ((memory_word & 0x00FF) as i8) {
+                    self.status = self.status | ADFNEG;
+                } else if ((self.acr & 0x00FF) as i8) == ((memory_word & 0x00FF) as i8){
+                    self.status = self.status | ADFEQL;
+                } 
+            },
+            ByteSelect::LEFT  => {  
+                memory_word = memory_word >> 8;         
+                if ((self.acr & 0x00FF) as i8) < ((memory_word & 0x00FF) as i8) {
+                    self.status = self.status | ADFNEG;
+                } else if ((self.acr & 0x00FF) as i8) == ((memory_word & 0x00FF) as i8){
+                    self.status = self.status | ADFEQL;
+                } 
+            }
+        }
+    }
+
+    fn ldb(&mut self,memory:&mut Memory,left_right:ByteSelect){   // load byte
+        let memory_word = self.read(memory);
+        self.acr = self.acr & (0xFF00 as u16) as i16;
+        match left_right {
+            ByteSelect::RIGHT => {
+                self.acr = self.acr | memory_word & 0x00FF;
+            },
+            ByteSelect::LEFT  => {
+                self.acr = self.acr | memory_word >> 8 & 0x00FF;
+            }
+        }
+    }
+
+    fn stx(&mut self,memory:&mut Memory){               // store index
+        self.store(memory, self.mar, self.ixr);
+    }
+
+    fn stw(&mut self,memory:&mut Memory){               // store word
+        self.store(memory, self.mar, self.acr);
+    }
+
+    fn ldw(&mut self,memory:&mut Memory){               // load word
+        self.acr = self.read(memory);
+    }
+
+    fn ldx(&mut self,memory:&mut Memory){               // load index
+        self.ixr = self.read(memory);
+    }
+
+    fn add(&mut self,memory:&mut Memory){               // add 
+        let operand = self.read(memory);
+        match self.acr.checked_add(operand) {
+            Some(value) => {
+                self.acr = value;
+                self.status = self.status & !ADFOVF;
+            },
+            None           => {
+                self.status = self.status | ADFOVF;     // overflow, note and fake results
+                self.acr = self.acr.wrapping_add(operand);
+            },
+        }; 
+    }
+
+    fn sub(&mut self,memory:&mut Memory){               // subtract
+        let operand = self.read(memory);
+        match self.acr.checked_sub(operand) {
+            Some(value) => {
+                self.acr = value;
+                self.status = self.status & !ADFOVF;
+            },
+            None           => {
+                self.status = self.status | ADFOVF;     // overflow, note and fake results
+                self.acr = self.acr.wrapping_sub(operand);
+            },
+        }; 
+    }
+
+    fn ori(&mut self,memory:&mut Memory){               // inclusive or
+        self.acr = self.read(memory) | self.acr;
+    }
+
+    fn ore(&mut self,memory:&mut Memory){               // exclusive or
+        self.acr = self.read(memory) ^ self.acr;
+    }
+
+    fn and(&mut self,memory:&mut Memory){               // logical and
+        self.acr = self.read(memory) & self.acr;
+    }
+
+    fn cmw(&mut self,memory:&mut Memory){               // compare word
+        self.status = self.status & !(ADFEQL | ADFNEG); // clear compare flags for default
+        let operand = self.read(memory);
+        if self.acr < operand     {
+            self.status = self.status | ADFNEG;
+        } else if self.acr == operand  {
+            self.status = self.status | ADFEQL;
+        }
+    }
+
+ // These are the generic instruction handlers
+    fn inret(&mut self,memory:&mut Memory){             // interrupt return
+        let level = self.mbr & 0x000F;
+        let base:usize = (level * 4) as usize;         // base address of int vector
+        self.int_act = self.int_act & !(0x0001 << level);
+        self.status = memory.core[base + 2] as u16; // restore machine status
+        self.pcr = memory.core[base] as u16;        // return via saved pcr
+        self.int_inhibit = true;
+        self.log_interrupt(level as u8, InterruptEventKind::Exited);
+    }
+    fn enb(&mut self){                                    //interrupt enable
+        let level = self.mbr & 0x000F;  
+        self.int_enb = self.int_enb | (0x0001 << level);
+        self.int_inhibit = true;
+    }
+    fn dsb(&mut self){                                  // interrupt disable
+        let level = self.mbr & 0x000F;  
+        let bit = 0x0001 << level;
+        let dropped = (self.int_act | self.int_req) & bit != 0;
+        self.int_enb = self.int_enb & !bit; 
+        self.int_act = self.int_act & !bit;
+        self.int_req = self.int_req & !bit;       
+        self.int_seen = self.int_seen & !bit;
+        if dropped {
+            self.log_interrupt(level as u8, InterruptEventKind::Disabled);
+        }
+    }
+    fn slm(&mut self){                                  // set local mode
+        self.status = self.status & !ADFGBL;
+    }
+    fn sgm(&mut self){                                  // set global mode
+        self.status = self.status | ADFGBL;      
+    }
+    fn cex(&mut self){                                  // copy extension to index
+        self.ixr =  (self.ixr & 0x07FF) | (self.status as i16 & EXR_BYTE_MASK as i16);
+    }
+    fn cxe(&mut self){                                  // copy index to extension
+        self.status =  (self.status & !EXR_BYTE_MASK) | (self.ixr as u16 & EXR_BYTE_MASK);
+    }
+    fn sml(&mut self){                                  // set memory lower
+        self.status = (self.status & !EXR_BYTE_MASK) | (self.mbr & 0x000F) << 11;
+    }
+    fn smu(&mut self){                                  // set memory upper
+        self.status = ( (self.status & !EXR_BYTE_MASK) | 0x8000 )| (self.mbr & 0x000F) << 11;        
+    }
+    fn msk(&mut self){                                  // mask interrupts
+        self.int_masked = true;
+    }
+    fn unm(&mut self){                                  // unmask interrupts
+        self.int_masked = false;
+        self.int_inhibit = true;
+    }
+
+// These are register instruction handlers
+    fn clr(&mut self){                                  // clear accumulator
+        self.acr = 0;
+    }
+    fn cmp(&mut self){                                  // complement accumulator
+        match self.acr.checked_neg() {
+            Some(value) => {
+                self.acr = value;
+                self.status = self.status & !ADFOVF;
+            },
+            None => self.status = self.status | ADFOVF,   // -32768 has no complement, stays
+        }
+    }
+    fn inv(&mut self){                                  // invert accumulator
+        self.acr = ((self.acr as u16) ^ 0xFFFF) as i16;
+    }
+    fn cax(&mut self){                                  // copy accumulator to index
+        self.ixr = self.acr;
+    }
+    fn cxa(&mut self){                                  // copy index to accumulator
+        self.acr = self.ixr;
+    }
+// Direct input handler
+    fn din(&mut self){                                  // read bus address into acr
+        let address = (self.mbr & 0x00FF) as u8;
+        self.acr = self.io.din(address, self.cycles);
+    }
+// Direct output handler
+    fn dot(&mut self){                                  // write acr to bus address
+        let address = (self.mbr & 0x00FF) as u8;
+        self.io.dot(address, self.acr, self.cycles);
+    }
+
+    fn ixs(&mut self){                                  // increment index and skip >= 0
+        self.ixr = self.ixr.wrapping_add(( self.mbr & 0x00FF) as i16);
+        if self.ixr >= 0 {self.advance_pcr()}
+    }
+
+    fn dxs(&mut self){                                  // decrement index and skip < 0
+        self.ixr = self.ixr.wrapping_sub(( self.mbr & 0x00FF) as i16);
+        if self.ixr < 0 {self.advance_pcr()}
+    }
+
+    fn llb(&mut self){                                  // load literal byte into the right byte, as LDB
+        self.acr = (self.acr & ( (0xFF00 as u16) as i16) ) | (self.mbr & 0x00FF) as i16;
+    }
+// Compare literal byte handler
+    fn clb(&mut self){                                  // compare literal byte
+        self.status = self.status & !(ADFEQL | ADFNEG); 
+        let byte = (self.mbr & 0x00FF) as i8;
+        if ( (self.acr & 0x00FF) as i8 ) < byte {
+            self.status = self.status | ADFNEG;
+        } else if ( (self.acr & 0x00FF) as i8 ) == byte  {
+            self.status = self.status | ADFEQL;
+        }
+    }
+
+// These are the skip handlers
+    fn saz(&mut self){                                  // skip accumulator zero
+        if self.acr == 0 {self.advance_pcr()}
+    }
+    fn sap(&mut self){                                  // skip accumulator positive
+        if self.acr >= 0 {self.advance_pcr()}
+    }
+    fn sam(&mut self){                                  // skip accumulator negative
+        if self.acr < 0 { self.advance_pcr()}
+    }
+    fn sao(&mut self){                                  // skip accumulator odd
+        if self.acr & 1 > 0 {self.advance_pcr()}
+    }
+    fn sls(&mut self){                                  // skip on compare less
+        if self.status & ADFNEG != 0 {self.advance_pcr()}
+    }
+    fn sxe(&mut self){                                  // skip if index even
+        if self.ixr & 1 == 0 {self.advance_pcr()}
+    }
+    fn seq(&mut self){                                  // skip equal
+        if self.status & ADFEQL != 0 {self.advance_pcr()}
+    }
+    fn sne(&mut self){                                  // skip not equal
+        if self.status & ADFEQL == 0 {self.advance_pcr()}
+    }
+    fn sgr(&mut self){                                  // skip greater
+        if (self.status & ADFEQL == 0) & (self.status & ADFNEG == 0 ) {
+            self.advance_pcr();
+        } 
+    }
+    fn sle(&mut self){                                  // skip less than or equal
+        if (self.status & ADFEQL != 0) | (self.status & ADFNEG != 0 ) {
+            self.advance_pcr();
+        }
+    }
+    fn sno(&mut self){                                  // skip no overflow
+        if self.status & ADFOVF == 0 { self.advance_pcr()}
+    }
+    fn sse(&mut self){                                  // skip on external sense line
+        if self.external_sense {self.advance_pcr()}
+    }
+    fn ss0(&mut self){                                  // skip on sense switch 0
+        if self.sense_switches[0] {self.advance_pcr()}
+    }
+    fn ss1(&mut self){                                  // skip on sense switch 1
+        if self.sense_switches[1] {self.advance_pcr()}
+    }
+    fn ss2(&mut self){                                  // skip on sense switch 2
+        if self.sense_switches[2] {self.advance_pcr()}
+    }
+    fn ss3(&mut self){                                  // skip on sense switch 3
+        if self.sense_switches[3] {self.advance_pcr()}
+    }
+// These are the shift arithmetic handlers
+    fn sra(&mut self){                                  // shift right arithmetic
+       let count = self.mbr & 0x000F; 
+       self.acr = self.acr >> count;
+    }
+    fn sla(&mut self){                                  // shift left arithmetic
+        self.status = self.status & !ADFOVF;   // reset overflow for test
+        let count = self.mbr & 0x000F; 
+        for _i in 0 .. count {
+            if   (self.acr & (0x8000 as u16) as i16)  ^ (self.acr & 0x4000) << 1 != 0 { // check for overflow
+                self.status = self.status | ADFOVF ;
+            }
+           self.acr = self.acr << 1;
+        }
+    }
+    fn srad(&mut self){                                 // shift right arithmetic double
+        let count = self.mbr & 0x000F; 
+        let mut word32:i32 = ((self.acr as i32) << 16) | ( (self.ixr as i32) & 0x0000FFFF );
+        word32 = word32 >> count;
+        self.ixr = (word32 & 0x0000FFFF) as i16;
+        self.acr = (word32 >> 16) as i16;    
+    }
+    fn slad(&mut self){                                     // shift left arithmetic double
+        self.status = self.status & !ADFOVF;   // reset overflow for test
+        let count = self.mbr & 0x000F; 
+        let mut word32:i32 = ((self.acr as i32) << 16) | ( (self.ixr as i32) & 0x0000FFFF );
+        for _i in 0 .. count {
+            if   (word32 & (0x80000000 as u32) as i32)  ^ (word32 & 0x40000000) << 1 != 0 { // check for overflow
+                self.status = self.status | ADFOVF ;
+            }
+           word32 = word32 << 1;
+        }
+        self.ixr = (word32 & 0x0000FFFF) as i16;
+        self.acr = (word32 >> 16) as i16;
+    }
+// These are the shift logical handlers
+    fn srl(&mut self){                                      // shift right logical
+        let count = self.mbr & 0x000F; 
+        self.acr = ((self.acr  as u16) >> count) as i16;        
+    }
+    fn  sll(&mut self){                                     // shift left logical
+        let count = self.mbr & 0x000F; 
+        self.acr = ((self.acr  as u16) << count) as i16;  
+    }
+    fn srld(&mut self){                                     // shift right logical double
+        let count = self.mbr & 0x000F; 
+        let mut word32:u32 = ((self.acr as u32) << 16) | ( (self.ixr as u32) & 0x0000FFFF );
+        word32 = word32 >> count;
+        self.ixr = (word32 & 0x0000FFFF) as i16;
+        self.acr = (word32 >> 16) as i16;   
+    }
+    fn slld(&mut self){                                     // shift left logical double
+        let count = self.mbr & 0x000F; 
+        let mut word32:u32 = ((self.acr as u32) << 16) | ( (self.ixr as u32) & 0x0000FFFF );
+        word32 = word32 << count;
+        self.ixr = (word32 & 0x0000FFFF) as i16;
+        self.acr = (word32 >> 16) as i16;   
+    }       
+    fn  src(&mut self){                                     // shift right circular
+        let count:u32 = (self.mbr & 0x000F) as u32; 
+        let mut u_acr: u16 = self.acr  as u16;
+        u_acr = u_acr.rotate_right(count);
+        self.acr = u_acr as i16;         
+    }
+    fn  slc(&mut self){                                     // shift left circular
+        let count:u32 = (self.mbr & 0x000F) as u32; 
+        let mut u_acr: u16 = self.acr  as u16;
+        u_acr = u_acr.rotate_left(count);
+        self.acr = u_acr as i16;  
+    }
+    fn srcd(&mut self){                                     // shift right circular double
+        let count = self.mbr & 0x000F; 
+        let mut word32:u32 = ((self.acr as u32) << 16) | ( (self.ixr as u32) & 0x0000FFFF );
+        word32 = word32.rotate_right(count as u32);
+        self.ixr = (word32 & 0x0000FFFF) as i16;
+        self.acr = (word32 >> 16) as i16;   
+    }
+    fn slcd(&mut self){                                     // shift left circular double
+        let count = self.mbr & 0x000F; 
+        let mut word32:u32 = ((self.acr as u32) << 16) | ( (self.ixr as u32) & 0x0000FFFF );
+        word32 = word32.rotate_left(count as u32);
+        self.ixr = (word32 & 0x0000FFFF) as i16;
+        self.acr = (word32 >> 16) as i16;   
+    }
+    fn srll(&mut self){                                     // shift right logical left byte
+        let count = self.mbr & 0x000F; 
+        let mut byte =  (self.acr >> 8) as u8;
+        byte = byte.checked_shr(count as u32).unwrap_or(0);     // eight or more empties it
+        self.acr = (self.acr & 0x00FF) | (byte as i16) << 8;
+    }
+    fn slll(&mut self){                                     // shift left logical left byte
+        let count = self.mbr & 0x000F; 
+        let mut byte =  (self.acr >> 8) as u8;
+        byte = byte.checked_shl(count as u32).unwrap_or(0);
+        self.acr = (self.acr & 0x00FF) | (byte as i16) << 8;
+    }
+    fn srlr(&mut self){                                     // shift right logical right byte 
+        let count = self.mbr & 0x000F; 
+        let mut byte = (self.acr & 0x00FF) as u8;
+        byte = byte.checked_shr(count as u32).unwrap_or(0);     // eight or more empties it
+        self.acr = (self.acr & ( (0xFF00 as u16) as i16) )| (byte as i16);
+    }
+    fn sllr(&mut self){                                     // shift left logical right byte
+        let count = self.mbr & 0x000F; 
+        let mut byte = (self.acr & 0x00FF) as u8;
+        byte = byte.checked_shl(count as u32).unwrap_or(0);
+        self.acr = (self.acr & ( (0xFF00 as u16) as i16) )| (byte as i16);
+    }
+    fn srcl(&mut self){                                     // shift right circular left byte
+        let count = self.mbr & 0x000F; 
+        let mut byte =  (self.acr >> 8) as u8;
+        byte = byte.rotate_right(count as u32);
+        self.acr = (self.acr & 0x00FF) | (byte as i16) << 8;
+    }
+    fn slcl(&mut self){                                     // shift left circular left byte
+        let count = self.mbr & 0x000F; 
+        let mut byte =  (self.acr >> 8) as u8;
+        byte = byte.rotate_left(count as u32);
+        self.acr = (self.acr & 0x00FF) | (byte as i16) << 8;
+    }
+    fn srcr(&mut self){                                     // shift right circular right byte
+        let count = self.mbr & 0x000F; 
+        let mut byte = (self.acr & 0x00FF) as u8;
+        byte = byte.rotate_right(count as u32);
+        self.acr = (self.acr & ( (0xFF00 as u16) as i16) )| (byte as i16);
+    }
+    fn slcr(&mut self){                                     // shift left circular right byte
+        let count = self.mbr & 0x000F; 
+        let mut byte = (self.acr & 0x00FF) as u8;
+        byte = byte.rotate_left(count as u32);
+        self.acr = (self.acr & ( (0xFF00 as u16) as i16) )| (byte as i16);
+    }
+
+
+    fn fetch_address(&self) -> usize {                  // core word the PCR addresses
+        self.pcr as usize & (self.core_words - 1)
+    }
+
+    fn advance_pcr(&mut self) {                         // next word, wrapping at the end of core
+        self.pcr = self.pcr.wrapping_add(1) & (self.core_words - 1) as u16;
+    }
+
+    fn fetch(&mut self,memory:&mut Memory){            // fetch next instruction into mbr and inr
+        self.mar = self.fetch_address();
+        self.mbr = memory.read(self.mar, self.mar as u16, true) as u16;
+        self.inr = ( (self.mbr & 0xFF00) >> 8) as u8;
+        self.cycles += 1;
+        self.instructions += 1;
+    }
+
+    fn compute_word_address(&mut self,reference:Reference) {    // form effective word address in MAR
+        self.mar = 0;
+        self.mar = self.mar | (reference.address & 0x07FF) as usize;    // get partial address from instruction
+        self.mar = self.mar | ( (self.status & EXR_WORD_MASK) >> 1 )as usize ;    //if not indexed, we are finishedd
+        if reference.indexed {                          // indexed instruction
+            if (self.status & ADFGBL) != 0 {            // global mode
+                self.mar = self.mar & 0x07FF;           // in global, clear out exr portion
+            } 
+            self.mar = self.mar.wrapping_add(self.ixr as isize as usize);  // a negative index counts down
+        }
+        self.mar = self.mar & (self.core_words - 1);    // wrap at the end of core
+    }
+
+    fn compute_byte_address(&mut self,reference:Reference) -> ByteSelect{    // form effective word address in MAR
+        self.mar = 0;
+        let mut byte_flag =  ByteSelect::LEFT; 
+        if !reference.indexed {                         // handlle non-indexed case
+            match reference.address & 0x0001 {
+                0x0000 => {byte_flag = ByteSelect::LEFT},
+                0x0001 => {byte_flag = ByteSelect::RIGHT},
+                     _ => {}
+            }
+            self.mar =  ( (reference.address & 0x7ff) as usize) >> 1;
+            self.mar = self.mar | ( (self.status & EXR_BYTE_MASK) >> 1) as usize ;    
+        } else {                                          // handle indexed case
+            self.mar = (reference.address & 0x07FF) as usize;
+            if (self.status & ADFGBL) == 0 {   // local mode - add in exr
+                self.mar = self.mar | (self.status & EXR_BYTE_MASK) as usize ; 
+            }
+            self.mar = self.mar.wrapping_add(self.ixr as isize as usize);
+            match self.mar & 0x0001 {
+                0x0000 => {byte_flag = ByteSelect::LEFT},
+                0x0001 => {byte_flag = ByteSelect::RIGHT},
+                     _ => {},
+            }
+            self.mar = self.mar >> 1;
+        }
+        self.mar = self.mar & (self.core_words - 1);        // wrap at the end of core
+        byte_flag                                           // return left or right flag
+    }
+
+    fn copy_pcr_to_exr (&mut self){                     // copy high 5 bits of pcr to exr
+        self.status= ( (self.pcr << 1) & EXR_BYTE_MASK) | (self.status & !EXR_BYTE_MASK);    
+    }
+
+    // Interrupt recognition happens at every instruction boundary, after the
+    // devices have been serviced and any scheduled requests posted.  A level
+    // is entered when it is requested and enabled, the mask flip/flop is
+    // clear, and it is higher than every active level; the highest such level
+    // wins.  Entry stores the PCR in word 0 of the level's vector (4 * level)
+    // and the status in word 2, sets global mode and jumps through word 1.
+    //
+    // ENB, UNM and INRET hold off recognition until the end of the following
+    // instruction, so a handler can always return, and a program can always
+    // execute one instruction after enabling, before the next interrupt.
+    fn check_interrupts(&mut self,memory:&mut Memory) { // see if interrupt pending
+        self.int_req = self.int_req | self.scheduler.due(self.cycles, self.instructions);
+        let new_requests = self.int_req & !self.int_seen;
+        if new_requests != 0 {
+            for level in (0..16u8).rev().filter(|level| new_requests & (0x0001 << level) != 0) {
+                self.log_interrupt(level, InterruptEventKind::Requested);
+            }
+        }
+        self.int_seen = self.int_req;
+        if self.int_inhibit {
+            self.int_inhibit = false;
+            return;                                     // one instruction after ENB/UNM/INRET
+        }
+        if self.int_masked | (self.mode == Mode::HALT) {
+            return;
+        }
+        let pending = self.int_req & self.int_enb & !self.int_act;
+        if pending == 0 {
+            return;                                     // nothing to do, return
+        }
+        let level = 15 - pending.leading_zeros() as i32;    // highest pending level
+        if self.int_act != 0 && level < 15 - self.int_act.leading_zeros() as i32 {
+            return;                                     // higher one is active, return
+        }
+        self.process_interrupt(memory,level);
+    }
+
+    fn process_interrupt(&mut self,memory:&mut Memory,level:i32) { // do interrupt sequencee at level
+        let base:usize = (level * 4) as usize;              // base address of interrupt vector
+        if !self.store(memory, base, self.pcr as i16)       // save pcr
+            || !self.store(memory, base+2, self.status as i16) {    // save status
+            return;                                         // halted by protection, the entry is not made
+        }
+        self.int_act = self.int_act | (0x0001 << level);    // set level active
+        self.int_req = self.int_req & !(0x0001 << level);   // reset request
+        self.int_seen = self.int_seen & !(0x0001 << level);
+        self.status = self.status | ADFGBL;                 // set global mode
+        self.log_interrupt(level as u8, InterruptEventKind::Entered);
+        self.pcr = memory.core[base+1] as u16;              // transfer to linkage address
+        self.copy_pcr_to_exr();                             // the handler's page
+        self.cycles += 3;                                   // two stores and the linkage fetch
+    }
+
+    fn log_interrupt(&mut self,level:u8,kind:InterruptEventKind) {
+        self.int_log.record(InterruptEvent {
+            cycle: self.cycles,
+            instruction: self.instructions,
+            level,
+            kind,
+            pcr: self.pcr,
+        });
+    }
+
+    pub fn print_registers(&mut self){
+        println!("PCR = {:04X}  ACR = {:04X}  IXR =    {:04X}",self.pcr,self.acr,self.ixr);
+        println!("MBR = {:04X}  MAR = {:04X}  Status = {:04X}",self.mbr,self.mar,self.status);
+        println!("int_enb = {:04X} int_act = {:04X} int_req = {:04X}",self.int_enb,self.int_act,self.int_req);       
+        println!("Inr = {:02x}  Mode = {:?}",self.inr,self.mode); 
+    } 
+}
+impl Default for Cpu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
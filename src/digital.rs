@@ -0,0 +1,162 @@
+// Digital input/output module, contact inputs and relay outputs in 16 bit groups
+//
+// Registers, for a module with n groups
+//      DIN +g      input group g
+//      DOT +g      output group g
+//      DIN +n+g    change latch g, input bits of group g that changed since
+//                  last read and are selected in its mask; reading clears it
+//      DOT +n+g    change mask g, input bits of group g that interrupt on change
+//
+// The module requests an interrupt whenever a selected input bit changes.
+// Inputs can be set at once (as a plant model does) or scheduled to change
+// at a given cycle so a test can toggle contacts at known emulated times.
+
+use std::any::Any;
+
+use crate::cpu::Memory;
+use crate::io::Device;
+
+pub const DIGITAL_ADDRESS:u8 = 0x30;            // standard bus address
+pub const DIGITAL_GROUPS:u8 = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct InputChange {
+    cycle: u64,
+    group: u8,
+    mask: u16,                                  // bits affected
+    bits: u16,                                  // their new values
+}
+
+pub struct DigitalIo {
+    inputs: Vec<u16>,
+    seen: Vec<u16>,                             // inputs as of the last change check
+    outputs: Vec<u16>,
+    masks: Vec<u16>,
+    latches: Vec<u16>,
+    scheduled: Vec<InputChange>,                // in cycle order, ties in scheduling order
+}
+
+impl DigitalIo {
+    pub fn new(groups: u8) -> Self {
+        let groups = groups.clamp(1, 127) as usize;
+        DigitalIo {
+            inputs: vec![0; groups],
+            seen: vec![0; groups],
+            outputs: vec![0; groups],
+            masks: vec![0; groups],
+            latches: vec![0; groups],
+            scheduled: Vec::new(),
+        }
+    }
+
+    pub fn groups(&self) -> u8 {
+        self.inputs.len() as u8
+    }
+
+    pub fn input(&self, group: u8) -> u16 {
+        self.inputs.get(group as usize).copied().unwrap_or(0)
+    }
+
+    pub fn output(&self, group: u8) -> u16 {
+        self.outputs.get(group as usize).copied().unwrap_or(0)
+    }
+
+    pub fn output_bit(&self, group: u8, bit: u8) -> bool {
+        self.output(group) & (1 << (bit & 0x0F)) != 0
+    }
+
+    pub fn set_input(&mut self, group: u8, value: u16) {
+        if let Some(input) = self.inputs.get_mut(group as usize) {
+            *input = value;
+        }
+    }
+
+    pub fn set_input_bit(&mut self, group: u8, bit: u8, on: bool) {
+        let mask = 1 << (bit & 0x0F);
+        let value = if on { self.input(group) | mask } else { self.input(group) & !mask };
+        self.set_input(group, value);
+    }
+
+    // change the masked input bits of group to bits at cycle
+    pub fn schedule(&mut self, cycle: u64, group: u8, mask: u16, bits: u16) {
+        let change = InputChange { cycle, group, mask, bits };
+        let at = self.scheduled.partition_point(|pending| pending.cycle <= cycle);
+        self.scheduled.insert(at, change);
+    }
+
+    // set or clear one input bit at cycle
+    pub fn schedule_bit(&mut self, cycle: u64, group: u8, bit: u8, on: bool) {
+        let mask = 1 << (bit & 0x0F);
+        self.schedule(cycle, group, mask, if on { mask } else { 0 });
+    }
+
+    pub fn pending_changes(&self) -> usize {
+        self.scheduled.len()
+    }
+}
+
+impl Default for DigitalIo {
+    fn default() -> Self {
+        Self::new(DIGITAL_GROUPS)
+    }
+}
+
+impl Device for DigitalIo {
+    fn name(&self) -> &str {
+        "digital i/o"
+    }
+
+    fn registers(&self) -> u8 {
+        self.groups() * 2
+    }
+
+    fn din(&mut self, reg: u8, _now: u64) -> i16 {
+        let groups = self.inputs.len();
+        let reg = reg as usize;
+        if reg < groups {
+            self.inputs[reg] as i16
+        } else {
+            std::mem::take(&mut self.latches[reg - groups]) as i16
+        }
+    }
+
+    fn dot(&mut self, reg: u8, value: i16, _now: u64) {
+        let groups = self.inputs.len();
+        let reg = reg as usize;
+        if reg < groups {
+            self.outputs[reg] = value as u16;
+        } else {
+            self.masks[reg - groups] = value as u16;
+        }
+    }
+
+    fn service(&mut self, now: u64, _memory: &mut Memory) -> bool {
+        let due = self.scheduled.partition_point(|pending| pending.cycle <= now);
+        for change in self.scheduled.drain(..due) {
+            if let Some(input) = self.inputs.get_mut(change.group as usize) {
+                *input = (*input & !change.mask) | (change.bits & change.mask);
+            }
+        }
+        let mut request = false;
+        for group in 0..self.inputs.len() {
+            let changed = (self.inputs[group] ^ self.seen[group]) & self.masks[group];
+            self.seen[group] = self.inputs[group];
+            if changed != 0 {
+                self.latches[group] |= changed;
+                request = true;
+            }
+        }
+        request
+    }
+
+    fn reset(&mut self) {
+        self.outputs.iter_mut().for_each(|output| *output = 0);
+        self.masks.iter_mut().for_each(|mask| *mask = 0);
+        self.latches.iter_mut().for_each(|latch| *latch = 0);
+        self.seen.copy_from_slice(&self.inputs);
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
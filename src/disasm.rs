@@ -0,0 +1,51 @@
+// Disassembler
+//
+// Words are shown in the assembler's syntax, so a listing can be edited and
+// assembled again.  With a symbol table, the target of a memory reference
+// that is not indexed is shown by name, and a location with a symbol gets it
+// as a label.  Byte references keep their byte address, since a symbol names
+// a word.  Words that are not instructions are shown as DATA.
+//
+//      0100  8115  LOOP    LDW   CNT
+//      0101  A116          ADD   ONE
+
+use crate::cpu::Memory;
+use crate::instruction::{Instruction, Operand};
+use crate::symbols::SymbolTable;
+
+// a word on its own, addresses in hex
+pub fn disassemble(word: u16) -> String {
+    match Instruction::decode(word) {
+        Some(instruction) => instruction.to_string(),
+        None => format!("DATA  0x{:04X}", word),
+    }
+}
+
+// a word at a core location, naming memory reference targets from symbols
+pub fn disassemble_at(location: u16, word: u16, symbols: Option<&SymbolTable>) -> String {
+    let Some(instruction) = Instruction::decode(word) else {
+        return disassemble(word);
+    };
+    if instruction.opcode.operand() != Operand::Address || instruction.indexed
+        || instruction.opcode.byte_reference() {
+        return instruction.to_string();
+    }
+    let target = instruction.target(location).unwrap_or(instruction.operand);
+    match symbols.and_then(|symbols| symbols.containing(target)) {
+        Some((name, 0)) => format!("{:<5} {}", instruction.opcode.mnemonic(), name),
+        Some((name, offset)) if offset < 0x10 => format!("{:<5} {}+{}", instruction.opcode.mnemonic(), name, offset),
+        _ => instruction.to_string(),
+    }
+}
+
+// listing of core from start to end inclusive
+pub fn listing(memory: &Memory, start: u16, end: u16, symbols: Option<&SymbolTable>) -> String {
+    let mut out = String::new();
+    for location in start..=end.min(0x7FFF) {
+        let word = memory.core[location as usize] as u16;
+        let label = symbols.and_then(|symbols| symbols.name_at(location)).unwrap_or("");
+        out += &format!("{:04X}  {:04X}  {:<7} {}\n", location, word, label,
+                        disassemble_at(location, word, symbols));
+    }
+    out
+}
@@ -0,0 +1,363 @@
+// Moving head disk controller backed by a host image file
+//
+// The image holds every sector in cylinder, head, sector order, each word
+// stored high byte first.  Sectors past the end of a short image read as zero
+// and the image grows when they are written.
+//
+// Registers
+//      DOT +0  command       function in bits 3..0, INT_ENABLE interrupts on completion
+//      DOT +1  cylinder
+//      DOT +2  head/sector   head in bits 15..8, sector in bits 7..0
+//      DOT +3  core address  first word of the transfer
+//      DOT +4  word count    words to read or write
+//      DIN +0  status        READY, BUSY, ERROR, SEEK_ERROR, ADDRESS_ERROR, WRITE_LOCK
+//      DIN +1  cylinder      where the heads are
+//      DIN +2  head/sector   the sector after the last one transferred
+//      DIN +3  core address  the word after the last one transferred
+//      DIN +4  word count
+//
+// A transfer starts at the given sector and continues through following
+// sectors and heads of the cylinder.  Running off the end of the cylinder
+// stops it with ADDRESS_ERROR.  A write that ends part way into a sector
+// fills the rest of it with zeros.
+//
+// Commands take emulated time: the seek from the current cylinder, then the
+// rotational latency until the first sector comes under the heads, then one
+// sector time for every sector transferred.  The disk turns continuously so
+// its angular position follows the CPU cycle count.
+
+use std::any::Any;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::cpu::Memory;
+use crate::io::Device;
+
+pub const DISK_ADDRESS:u8 = 0x18;               // standard bus address
+
+pub const READY:i16 = 0x8000u16 as i16;         // status bits
+pub const BUSY:i16 = 0x4000;
+pub const ERROR:i16 = 0x2000;
+pub const SEEK_ERROR:i16 = 0x1000;
+pub const ADDRESS_ERROR:i16 = 0x0800;
+pub const WRITE_LOCK:i16 = 0x0400;
+
+pub const FN_SEEK:i16 = 1;                      // command functions
+pub const FN_READ:i16 = 2;
+pub const FN_WRITE:i16 = 3;
+pub const FN_RECALIBRATE:i16 = 4;
+pub const INT_ENABLE:i16 = 0x0100;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Geometry {
+    pub cylinders: u16,
+    pub heads: u8,
+    pub sectors: u8,                            // sectors per track
+    pub words_per_sector: u16,
+}
+
+impl Geometry {
+    // every dimension at least one
+    pub fn is_valid(&self) -> bool {
+        self.cylinders > 0 && self.heads > 0 && self.sectors > 0 && self.words_per_sector > 0
+    }
+
+    pub fn total_words(&self) -> u64 {
+        self.cylinders as u64 * self.heads as u64 * self.sectors as u64 * self.words_per_sector as u64
+    }
+
+    // word offset of a sector in the image
+    fn offset(&self, cylinder: u16, head: u8, sector: u8) -> u64 {
+        let track = cylinder as u64 * self.heads as u64 + head as u64;
+        (track * self.sectors as u64 + sector as u64) * self.words_per_sector as u64
+    }
+}
+
+impl Default for Geometry {
+    fn default() -> Self {
+        Geometry { cylinders: 200, heads: 2, sectors: 16, words_per_sector: 128 }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Timing {
+    pub settle_cycles: u64,                     // head settle after any seek
+    pub cylinder_cycles: u64,                   // per cylinder moved
+    pub rotation_cycles: u64,                   // one revolution
+}
+
+impl Default for Timing {
+    fn default() -> Self {
+        Timing { settle_cycles: 5_714, cylinder_cycles: 57, rotation_cycles: 14_286 }   // 10 ms, 0.1 ms, 2400 rpm
+    }
+}
+
+pub struct Disk {
+    file: Option<File>,
+    geometry: Geometry,                         // fixed at new, never zero
+    pub timing: Timing,
+    write_lock: bool,
+    cylinder: u16,                              // where the heads are
+    target_cylinder: u16,
+    head: u8,
+    sector: u8,
+    address: u16,
+    word_count: u16,
+    status: i16,
+    pending: Option<i16>,                       // function waiting to complete
+    done_at: u64,
+    int_enable: bool,
+    pub last_error: Option<String>,             // host file error, if any
+}
+
+impl Disk {
+    // panics on a dimension of zero, which the timing would divide by;
+    // a configuration refuses one before building the disk
+    pub fn new(geometry: Geometry) -> Self {
+        assert!(geometry.is_valid(), "disk geometry {:?} has a dimension of zero", geometry);
+        Disk {
+            file: None,
+            geometry,
+            timing: Timing::default(),
+            write_lock: true,
+            cylinder: 0,
+            target_cylinder: 0,
+            head: 0,
+            sector: 0,
+            address: 0,
+            word_count: 0,
+            status: 0,
+            pending: None,
+            done_at: 0,
+            int_enable: false,
+            last_error: None,
+        }
+    }
+
+    // attach a host image, creating it if writing is allowed
+    pub fn attach_file<P: AsRef<Path>>(&mut self, path: P, write_enable: bool) -> io::Result<()> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(write_enable)
+            .create(write_enable)
+            .truncate(false)
+            .open(path)?;
+        self.file = Some(file);
+        self.write_lock = !write_enable;
+        self.status = 0;
+        Ok(())
+    }
+
+    pub fn geometry(&self) -> Geometry {
+        self.geometry
+    }
+
+    pub fn detach_file(&mut self) {
+        self.file = None;
+    }
+
+    fn sector_cycles(&self) -> u64 {
+        (self.timing.rotation_cycles / self.geometry.sectors as u64).max(1)
+    }
+
+    fn seek_cycles(&self, from: u16, to: u16) -> u64 {
+        if from == to {
+            return 0;
+        }
+        self.timing.settle_cycles + from.abs_diff(to) as u64 * self.timing.cylinder_cycles
+    }
+
+    // cycles until sector starts passing under the heads at time now
+    fn latency_cycles(&self, now: u64, sector: u8) -> u64 {
+        let rotation = self.sector_cycles() * self.geometry.sectors as u64;
+        let angle = now % rotation;
+        let start = sector as u64 * self.sector_cycles();
+        (start + rotation - angle) % rotation
+    }
+
+    fn sectors_to_transfer(&self) -> u64 {
+        let words = self.geometry.words_per_sector as u64;
+        (self.word_count as u64).div_ceil(words)
+    }
+
+    // start a command, working out when it completes
+    fn start(&mut self, command: i16, now: u64) {
+        if self.pending.is_some() {
+            return;                                 // busy, command ignored
+        }
+        let function = command & 0x000F;
+        self.int_enable = command & INT_ENABLE != 0;
+        self.status = 0;
+        let seek = match function {
+            FN_RECALIBRATE => self.seek_cycles(self.cylinder, 0),
+            _ => self.seek_cycles(self.cylinder, self.target_cylinder),
+        };
+        let mut time = seek;
+        if function == FN_READ || function == FN_WRITE {
+            time += self.latency_cycles(now + seek, self.sector);
+            time += self.sectors_to_transfer() * self.sector_cycles();
+        }
+        self.pending = Some(function);
+        self.done_at = now + time.max(1);
+    }
+
+    fn complete(&mut self, function: i16, memory: &mut Memory) {
+        match function {
+            FN_SEEK | FN_READ | FN_WRITE => {
+                if self.target_cylinder >= self.geometry.cylinders {
+                    self.status |= ERROR | SEEK_ERROR;
+                    return;
+                }
+                self.cylinder = self.target_cylinder;
+            },
+            FN_RECALIBRATE => {
+                self.cylinder = 0;
+                self.target_cylinder = 0;
+                return;
+            },
+            _ => {
+                self.status |= ERROR;
+                return;
+            },
+        }
+        if function == FN_SEEK {
+            return;
+        }
+        if self.file.is_none() {
+            self.status |= ERROR;
+            return;
+        }
+        if function == FN_WRITE && self.write_lock {
+            self.status |= ERROR | WRITE_LOCK;
+            return;
+        }
+        let per_sector = self.geometry.words_per_sector as usize;
+        let mut remaining = self.word_count as usize;
+        while remaining > 0 {
+            if self.head >= self.geometry.heads || self.sector >= self.geometry.sectors {
+                self.status |= ERROR | ADDRESS_ERROR;
+                return;
+            }
+            let words = remaining.min(per_sector);
+            let result = if function == FN_READ {
+                self.read_sector(memory, words)
+            } else {
+                self.write_sector(memory, words)
+            };
+            if let Err(err) = result {
+                self.last_error = Some(err.to_string());
+                self.status |= ERROR;
+                return;
+            }
+            self.address = self.address.wrapping_add(words as u16) & 0x7FFF;
+            remaining -= words;
+            self.sector += 1;
+            if self.sector == self.geometry.sectors {
+                self.sector = 0;
+                self.head += 1;
+            }
+        }
+    }
+
+    fn read_sector(&mut self, memory: &mut Memory, words: usize) -> io::Result<()> {
+        let offset = self.geometry.offset(self.cylinder, self.head, self.sector) * 2;
+        let mut buffer = vec![0u8; words * 2];
+        let file = self.file.as_mut().expect("disk image attached");
+        file.seek(SeekFrom::Start(offset))?;
+        let mut filled = 0;
+        while filled < buffer.len() {                // short image reads as zeros
+            let n = file.read(&mut buffer[filled..])?;
+            if n == 0 { break; }
+            filled += n;
+        }
+        for (i, pair) in buffer.chunks(2).enumerate() {
+            memory.write((self.address as usize + i) & 0x7FFF, i16::from_be_bytes([pair[0], pair[1]]));
+        }
+        Ok(())
+    }
+
+    fn write_sector(&mut self, memory: &Memory, words: usize) -> io::Result<()> {
+        let offset = self.geometry.offset(self.cylinder, self.head, self.sector) * 2;
+        let mut buffer = vec![0u8; self.geometry.words_per_sector as usize * 2];
+        for i in 0..words {
+            let word = memory.core[(self.address as usize + i) & 0x7FFF];
+            buffer[i * 2..i * 2 + 2].copy_from_slice(&word.to_be_bytes());
+        }
+        let file = self.file.as_mut().expect("disk image attached");
+        file.seek(SeekFrom::Start(offset))?;
+        file.write_all(&buffer)
+    }
+}
+
+impl Default for Disk {
+    fn default() -> Self {
+        Self::new(Geometry::default())
+    }
+}
+
+impl Device for Disk {
+    fn name(&self) -> &str {
+        "disk"
+    }
+
+    fn registers(&self) -> u8 {
+        5
+    }
+
+    fn din(&mut self, reg: u8, _now: u64) -> i16 {
+        match reg {
+            0 => {
+                let mut status = self.status;
+                if self.pending.is_none() { status |= READY; } else { status |= BUSY; }
+                if self.write_lock { status |= WRITE_LOCK; }
+                status
+            },
+            1 => self.cylinder as i16,
+            2 => ((self.head as u16) << 8 | self.sector as u16) as i16,
+            3 => self.address as i16,
+            _ => self.word_count as i16,
+        }
+    }
+
+    fn dot(&mut self, reg: u8, value: i16, now: u64) {
+        if self.pending.is_some() && reg != 0 {
+            return;                                 // registers are locked while busy
+        }
+        let value = value as u16;
+        match reg {
+            0 => self.start(value as i16, now),
+            1 => self.target_cylinder = value,
+            2 => {
+                self.head = (value >> 8) as u8;
+                self.sector = (value & 0x00FF) as u8;
+            },
+            3 => self.address = value & 0x7FFF,
+            _ => self.word_count = value,
+        }
+    }
+
+    fn service(&mut self, now: u64, memory: &mut Memory) -> bool {
+        let Some(function) = self.pending else { return false };
+        if now < self.done_at {
+            return false;
+        }
+        self.complete(function, memory);
+        self.pending = None;
+        self.int_enable
+    }
+
+    fn busy(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    fn reset(&mut self) {
+        self.pending = None;
+        self.status = 0;
+        self.int_enable = false;
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
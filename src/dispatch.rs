@@ -0,0 +1,83 @@
+// Predecoded instruction dispatch
+//
+// The cache keeps, for every core word, the handler the decoder selected the
+// last time the word was fetched, with the address field and index flag of a
+// memory reference, so a run of long loops dispatches each instruction with
+// one indirect call instead of decoding it again.  The handler forms the
+// effective address once, from the reference it is passed.
+//
+// Each entry remembers the word it was decoded from, and every lookup
+// compares it with the word fetched.  Nothing has to be told when core
+// changes: after a store by the program, a DMA transfer by a device or a
+// deposit from the host the words differ and the word is decoded again.
+// Handlers do everything the decoder does after the fetch, so results are
+// the same with or without the cache.
+//
+// Cpu::run dispatches through the cache for long runs.  Setting cpu.dispatch
+// makes step use it too.
+
+use crate::cpu::{Cpu, Memory};
+
+pub type Handler = fn(&mut Cpu, &mut Memory, Reference);
+
+// the memory reference field of an instruction word, zero for the others
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub struct Reference {
+    pub address: u16,                           // 11 bit word or byte address
+    pub indexed: bool,
+}
+
+#[derive(Clone, Copy)]
+pub struct Decoded {
+    pub handler: Handler,
+    pub reference: Reference,
+}
+
+const CORE_WORDS:usize = 32_768;
+
+#[derive(Clone, Copy)]
+struct Entry {
+    word: u16,                                  // instruction the handler was decoded from
+    decoded: Option<Decoded>,                   // None until first fetched
+}
+
+pub struct DispatchCache {
+    entries: Vec<Entry>,
+    pub hits: u64,
+    pub decodes: u64,                           // fetches that had to decode
+}
+
+impl DispatchCache {
+    pub fn new() -> Self {
+        DispatchCache { entries: vec![Entry { word: 0, decoded: None }; CORE_WORDS], hits: 0, decodes: 0 }
+    }
+
+    // the decoded word fetched from address, decoding it with predecode
+    // when the entry is empty or core has changed since
+    #[inline]
+    pub fn lookup(&mut self, address: usize, word: u16, predecode: fn(u16) -> Decoded) -> Decoded {
+        let entry = &mut self.entries[address & (CORE_WORDS - 1)];
+        if let Some(decoded) = entry.decoded {
+            if entry.word == word {
+                self.hits += 1;
+                return decoded;
+            }
+        }
+        let decoded = predecode(word);
+        *entry = Entry { word, decoded: Some(decoded) };
+        self.decodes += 1;
+        decoded
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.iter_mut().for_each(|entry| entry.decoded = None);
+        self.hits = 0;
+        self.decodes = 0;
+    }
+}
+
+impl Default for DispatchCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
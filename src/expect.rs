@@ -0,0 +1,324 @@
+// Expect-style automation of the console teletype
+//
+// A Dialog plays the operator of a program that talks on the teletype.  It
+// types input, runs the machine until the output shows an expected string or
+// regular expression, and fails with a transcript of the session when the
+// output does not come within the timeout or the machine halts first.
+// Timeouts and waits are in emulated seconds, so a dialog behaves the same on
+// any host.  Each expect searches the output printed since the last match.
+//
+// From Rust
+//      let mut dialog = Dialog::new(&mut cpu, &mut memory)?;
+//      dialog.set_timeout(5.0);
+//      dialog.expect("READY")?;
+//      dialog.send("RUN 5\n");
+//      let found = dialog.expect_regex(r"LEVEL (\d+)")?;     // found[1] is the digits
+//
+// A script file has one command per line, comments from a semicolon:
+//      timeout 5               ; seconds of emulated time for each expect
+//      expect  "READY"
+//      send    "RUN 5\n"       ; \n types a carriage return, also \r \t \" \\
+//      expect  /LEVEL (\d+)/   ; regular expression between slashes
+//      wait    0.5             ; run for half a second
+//      halt                    ; run until the machine halts
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use regex::Regex;
+
+use crate::cpu::{cycles_to_seconds, seconds_to_cycles, Cpu, Memory, Mode};
+use crate::io::BusError;
+use crate::teletype::{Teletype, TELETYPE_ADDRESS};
+
+pub const DEFAULT_TIMEOUT:f64 = 10.0;           // emulated seconds
+
+const CHUNK:u64 = 1_000;                        // instructions between looks at the output
+
+#[derive(Debug)]
+pub enum ExpectError {
+    Io(io::Error),
+    Bus(BusError),
+    Syntax { line: usize, text: String },
+    BadPattern(regex::Error),
+    Timeout { waiting_for: String, seconds: f64, transcript: String },
+    Halted { waiting_for: String, pcr: u16, transcript: String },
+    Script { line: usize, err: Box<ExpectError> },  // a failed command of a script
+}
+
+impl fmt::Display for ExpectError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExpectError::Io(err) => write!(f, "script i/o error: {}", err),
+            ExpectError::Bus(err) => write!(f, "{}", err),
+            ExpectError::Syntax { line, text } => write!(f, "line {}: cannot make sense of '{}'", line, text),
+            ExpectError::BadPattern(err) => write!(f, "{}", err),
+            ExpectError::Timeout { waiting_for, seconds, transcript } =>
+                write!(f, "timed out after {:.3} s waiting for {}\n{}", seconds, waiting_for, transcript),
+            ExpectError::Halted { waiting_for, pcr, transcript } =>
+                write!(f, "machine halted at PCR {:04X} waiting for {}\n{}", pcr, waiting_for, transcript),
+            ExpectError::Script { line, err } => write!(f, "line {}: {}", line, err),
+        }
+    }
+}
+
+impl std::error::Error for ExpectError {}
+
+impl From<io::Error> for ExpectError {
+    fn from(err: io::Error) -> Self {
+        ExpectError::Io(err)
+    }
+}
+
+impl From<BusError> for ExpectError {
+    fn from(err: BusError) -> Self {
+        ExpectError::Bus(err)
+    }
+}
+
+impl From<regex::Error> for ExpectError {
+    fn from(err: regex::Error) -> Self {
+        ExpectError::BadPattern(err)
+    }
+}
+
+pub struct Dialog<'a> {
+    pub cpu: &'a mut Cpu,
+    pub memory: &'a mut Memory,
+    pub address: u8,                            // the teletype's device code
+    timeout: u64,                               // cycles
+    matched: usize,                             // output up to here has been matched
+    log: Vec<String>,                           // what the operator did, for the transcript
+}
+
+impl<'a> Dialog<'a> {
+    // a dialog on the teletype at the standard address, attached if there is none
+    pub fn new(cpu: &'a mut Cpu, memory: &'a mut Memory) -> Result<Self, ExpectError> {
+        if cpu.io.device_mut::<Teletype>(TELETYPE_ADDRESS).is_none() {
+            cpu.io.attach(TELETYPE_ADDRESS, None, Box::new(Teletype::new()))?;
+        }
+        Ok(Dialog::on(cpu, memory, TELETYPE_ADDRESS))
+    }
+
+    // a dialog on a teletype already attached at address
+    pub fn on(cpu: &'a mut Cpu, memory: &'a mut Memory, address: u8) -> Self {
+        let matched = cpu.io.device_mut::<Teletype>(address).map_or(0, |teletype| teletype.printed().len());
+        Dialog { cpu, memory, address, timeout: seconds_to_cycles(DEFAULT_TIMEOUT), matched, log: Vec::new() }
+    }
+
+    pub fn set_timeout(&mut self, seconds: f64) {
+        self.timeout = seconds_to_cycles(seconds);
+    }
+
+    fn teletype(&mut self) -> &mut Teletype {
+        self.cpu.io.device_mut::<Teletype>(self.address).expect("no teletype at the dialog's address")
+    }
+
+    fn note(&mut self, what: String) {
+        let line = format!("[{:9.3} s] {}", cycles_to_seconds(self.cpu.cycles), what);
+        self.log.push(line);
+    }
+
+    // everything the teletype has printed
+    pub fn output(&mut self) -> String {
+        self.teletype().printed_text()
+    }
+
+    pub fn send(&mut self, text: &str) {
+        self.teletype().type_text(text);
+        self.note(format!("sent {:?}", text));
+    }
+
+    // run for seconds of emulated time, or until the machine halts
+    pub fn wait(&mut self, seconds: f64) {
+        let until = self.cpu.cycles + seconds_to_cycles(seconds);
+        while self.cpu.mode == Mode::RUN && self.cpu.cycles < until {
+            self.cpu.run(self.memory, CHUNK);
+        }
+        self.note(format!("waited {} s", seconds));
+    }
+
+    // run until the machine halts, within the timeout
+    pub fn halt(&mut self) -> Result<(), ExpectError> {
+        let deadline = self.cpu.cycles + self.timeout;
+        while self.cpu.mode == Mode::RUN {
+            if self.cpu.cycles >= deadline {
+                return Err(ExpectError::Timeout {
+                    waiting_for: "a halt".to_string(),
+                    seconds: cycles_to_seconds(self.timeout),
+                    transcript: self.transcript(),
+                });
+            }
+            self.cpu.run(self.memory, CHUNK);
+        }
+        self.note(format!("halted at PCR {:04X}", self.cpu.pcr));
+        Ok(())
+    }
+
+    // Run until the output since the last match has text in it, returning it.
+    pub fn expect(&mut self, text: &str) -> Result<String, ExpectError> {
+        let pattern = Regex::new(&regex::escape(text))?;
+        self.wait_for(&pattern, format!("{:?}", text)).map(|mut found| found.swap_remove(0))
+    }
+
+    // Run until the output since the last match matches pattern, returning
+    // the match and its groups, empty for a group that took no part.
+    pub fn expect_regex(&mut self, pattern: &str) -> Result<Vec<String>, ExpectError> {
+        let regex = Regex::new(pattern)?;
+        self.wait_for(&regex, format!("/{}/", pattern))
+    }
+
+    fn wait_for(&mut self, pattern: &Regex, waiting_for: String) -> Result<Vec<String>, ExpectError> {
+        let deadline = self.cpu.cycles + self.timeout;
+        loop {
+            let output = self.output();
+            let unmatched = output.get(self.matched..).unwrap_or("");
+            if let Some(captures) = pattern.captures(unmatched) {
+                self.matched += captures.get(0).map_or(0, |found| found.end());
+                let found: Vec<String> = captures.iter()
+                    .map(|group| group.map_or(String::new(), |group| group.as_str().to_string()))
+                    .collect();
+                self.note(format!("found {} as {:?}", waiting_for, found[0]));
+                return Ok(found);
+            }
+            if self.cpu.mode != Mode::RUN {
+                return Err(ExpectError::Halted { waiting_for, pcr: self.cpu.pcr, transcript: self.transcript() });
+            }
+            if self.cpu.cycles >= deadline {
+                let seconds = cycles_to_seconds(self.timeout);
+                return Err(ExpectError::Timeout { waiting_for, seconds, transcript: self.transcript() });
+            }
+            self.cpu.run(self.memory, CHUNK);
+        }
+    }
+
+    // what the operator did, then everything printed with the unmatched part marked
+    pub fn transcript(&mut self) -> String {
+        let output = self.output().replace('\r', "");
+        let matched = self.output().get(..self.matched).unwrap_or("").replace('\r', "").len();
+        let mut transcript = String::from("--- dialog ---\n");
+        for line in &self.log {
+            transcript += line;
+            transcript.push('\n');
+        }
+        transcript += "--- teletype ---\n";
+        transcript += &output[..matched];
+        transcript += "<<< not matched >>>\n";
+        transcript += &output[matched..];
+        if !transcript.ends_with('\n') {
+            transcript.push('\n');
+        }
+        transcript
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Timeout(f64),
+    Send(String),
+    Expect(String),
+    ExpectRegex(String),
+    Wait(f64),
+    Halt,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Script {
+    pub commands: Vec<(usize, Command)>,        // line number and command
+}
+
+// a quoted string with escapes, and what follows it
+fn quoted(text: &str) -> Option<(String, &str)> {
+    let mut chars = text.strip_prefix('"')?.char_indices();
+    let mut out = String::new();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '"' => return Some((out, &text[index + 2..])),
+            '\\' => out.push(match chars.next()?.1 {
+                'n' => '\n',
+                'r' => '\r',
+                't' => '\t',
+                other => other,
+            }),
+            _ => out.push(c),
+        }
+    }
+    None
+}
+
+// a regular expression between slashes, \/ for a slash in it
+fn slashed(text: &str) -> Option<(String, &str)> {
+    let mut chars = text.strip_prefix('/')?.char_indices();
+    let mut out = String::new();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '/' => return Some((out, &text[index + 2..])),
+            '\\' => match chars.next()?.1 {
+                '/' => out.push('/'),
+                other => { out.push('\\'); out.push(other); },
+            },
+            _ => out.push(c),
+        }
+    }
+    None
+}
+
+impl Script {
+    pub fn parse(text: &str) -> Result<Script, ExpectError> {
+        let mut script = Script::default();
+        for (index, line) in text.lines().enumerate() {
+            let line_number = index + 1;
+            let syntax = || ExpectError::Syntax { line: line_number, text: line.trim().to_string() };
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with(';') {
+                continue;
+            }
+            let (word, rest) = trimmed.split_once(char::is_whitespace).unwrap_or((trimmed, ""));
+            let rest = rest.trim_start();
+            let (command, rest) = match word.to_ascii_lowercase().as_str() {
+                "send" => quoted(rest).map(|(text, rest)| (Command::Send(text), rest)).ok_or_else(syntax)?,
+                "expect" => match rest.chars().next() {
+                    Some('/') => slashed(rest).map(|(pattern, rest)| (Command::ExpectRegex(pattern), rest)),
+                    _ => quoted(rest).map(|(text, rest)| (Command::Expect(text), rest)),
+                }.ok_or_else(syntax)?,
+                "timeout" | "wait" => {
+                    let (number, rest) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                    let seconds: f64 = number.parse().ok().filter(|seconds: &f64| *seconds >= 0.0).ok_or_else(syntax)?;
+                    (if word.eq_ignore_ascii_case("wait") { Command::Wait(seconds) } else { Command::Timeout(seconds) }, rest)
+                },
+                "halt" => (Command::Halt, rest),
+                _ => return Err(syntax()),
+            };
+            let rest = rest.trim();
+            if !rest.is_empty() && !rest.starts_with(';') {
+                return Err(syntax());
+            }
+            if let Command::ExpectRegex(pattern) = &command {      // report bad patterns before running
+                Regex::new(pattern).map_err(|err| ExpectError::Script { line: line_number, err: Box::new(err.into()) })?;
+            }
+            script.commands.push((line_number, command));
+        }
+        Ok(script)
+    }
+
+    pub fn load_file<P: AsRef<Path>>(path: P) -> Result<Script, ExpectError> {
+        Script::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn run(&self, dialog: &mut Dialog) -> Result<(), ExpectError> {
+        for (line, command) in &self.commands {
+            let result = match command {
+                Command::Timeout(seconds) => { dialog.set_timeout(*seconds); Ok(()) },
+                Command::Send(text) => { dialog.send(text); Ok(()) },
+                Command::Expect(text) => dialog.expect(text).map(|_| ()),
+                Command::ExpectRegex(pattern) => dialog.expect_regex(pattern).map(|_| ()),
+                Command::Wait(seconds) => { dialog.wait(*seconds); Ok(()) },
+                Command::Halt => dialog.halt(),
+            };
+            result.map_err(|err| ExpectError::Script { line: *line, err: Box::new(err) })?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,100 @@
+// Emulator host calls for test programs
+//
+// Group 0F is not a 703 instruction.  With host calls enabled on the CPU,
+// TRAP (0F00 | function) asks the emulator to do something for the program;
+// without them it halts as the illegal instruction it is on the real machine.
+//
+//      TRAP 0x00   EXIT    stop the emulator, ACR is the exit status
+//      TRAP 0x01   PUTS    print the string at the word address in IXR, two
+//                          characters to a word, left byte first, to a zero byte
+//      TRAP 0x02   PUTC    print the character in the low byte of ACR
+//      TRAP 0x03   PUTHEX  print ACR as four hex digits
+//      TRAP 0x04   PASS    count a check as passed, ACR identifies it
+//      TRAP 0x05   FAIL    count a check as failed, ACR identifies it
+//
+// Registers are left as they were.  Output goes to standard output as it is
+// printed and is also kept for the host.  A call with another function is an
+// illegal instruction.
+
+use std::io::{self, Write};
+
+use crate::cpu::{Cpu, HaltReason, Memory};
+
+pub const EXIT:u8 = 0x00;                       // functions
+pub const PUTS:u8 = 0x01;
+pub const PUTC:u8 = 0x02;
+pub const PUTHEX:u8 = 0x03;
+pub const PASS:u8 = 0x04;
+pub const FAIL:u8 = 0x05;
+
+const MAX_STRING:usize = 4_096;                 // characters, in case the zero is missing
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Check {
+    pub pcr: u16,                               // address of the TRAP
+    pub code: u16,                              // ACR at the call
+}
+
+#[derive(Debug, Clone)]
+pub struct HostCalls {
+    pub echo: bool,                             // copy output to standard output
+    pub output: Vec<u8>,
+    pub passed: Vec<Check>,
+    pub failed: Vec<Check>,
+}
+
+impl HostCalls {
+    pub fn new() -> Self {
+        HostCalls { echo: true, output: Vec::new(), passed: Vec::new(), failed: Vec::new() }
+    }
+
+    fn print(&mut self, text: &[u8]) {
+        self.output.extend_from_slice(text);
+        if self.echo {
+            let mut out = io::stdout();
+            let _ = out.write_all(text).and_then(|_| out.flush());
+        }
+    }
+
+    pub fn output_text(&self) -> String {
+        String::from_utf8_lossy(&self.output).into_owned()
+    }
+
+    // carry out the TRAP just fetched; false for a function there is not
+    pub fn call(&mut self, cpu: &mut Cpu, memory: &Memory) -> bool {
+        let check = Check { pcr: cpu.pcr.wrapping_sub(1), code: cpu.acr as u16 };
+        match (cpu.mbr & 0x00FF) as u8 {
+            EXIT => cpu.halt(HaltReason::Exit(cpu.acr as u16)),
+            PUTS => {
+                let mut text = Vec::new();
+                let mut address = cpu.ixr as u16 as usize;
+                'string: while text.len() < MAX_STRING {
+                    let word = memory.core[address & (cpu.core_words - 1)] as u16;
+                    for byte in [(word >> 8) as u8, word as u8] {
+                        if byte == 0 {
+                            break 'string;
+                        }
+                        text.push(byte & 0x7F);
+                    }
+                    address += 1;
+                }
+                self.print(&text);
+            },
+            PUTC => self.print(&[(cpu.acr & 0x7F) as u8]),
+            PUTHEX => self.print(format!("{:04X}", cpu.acr as u16).as_bytes()),
+            PASS => self.passed.push(check),
+            FAIL => {
+                self.print(format!("FAIL {:04X} at {:04X}\n", check.code, check.pcr).as_bytes());
+                self.failed.push(check);
+            },
+            _ => return false,
+        }
+        true
+    }
+}
+
+impl Default for HostCalls {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,256 @@
+// Raytheon 703 instruction set
+//
+// Every instruction is one 16 bit word.  Memory reference instructions carry
+// the opcode in the top four bits, an index flag and an 11 bit address:
+//
+//      15..12  opcode 1..F     11  index       10..0  address in the page
+//
+// Words with a zero top digit are decoded on their high byte.  Groups 02..07
+// take a byte operand (a device address or a literal), as does group 0F, an
+// emulator trap that is not part of the 703.  Groups 00, 01, 08,
+// 09 and 0A are decoded again on the next digit and take a four bit operand,
+// a level, a memory bank or a shift count, or ignore it.
+//
+// OPCODES below is the one definition of the layout.  Instruction::decode
+// and encode, the CPU's dispatcher, the disassembler and the assembler all
+// work from it.
+
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Opcode {
+    JMP, JSX, STB, CMB, LDB, STX, STW, LDW, LDX, ADD, SUB, ORI, ORE, AND, CMW,
+    HLT, INRET, ENB, DSB, SLM, SGM, CEX, CXE, SML, SMU, MSK, UNM,
+    CLR, CMP, INV, CAX, CXA,
+    DIN, DOT, IXS, DXS, LLB, CLB, TRAP,
+    SAZ, SAP, SAM, SAO, SLS, SXE, SEQ, SNE, SGR, SLE, SNO, SSE, SS0, SS1, SS2, SS3,
+    SRA, SLA, SRAD, SLAD,
+    SRL, SLL, SRLD, SLLD, SRC, SLC, SRCD, SLCD, SRLL, SLLL, SRLR, SLLR, SRCL, SLCL, SRCR, SLCR,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand {
+    Address,                                    // 11 bit address and index flag
+    Byte,                                       // device address or literal, 8 bits
+    Level,                                      // interrupt level, 4 bits
+    Bank,                                       // memory bank for SML/SMU, 4 bits
+    Count,                                      // shift count, 4 bits
+    Unused,                                     // 4 bits the CPU ignores
+}
+
+impl Operand {
+    pub fn mask(self) -> u16 {
+        match self {
+            Operand::Address => 0x07FF,
+            Operand::Byte => 0x00FF,
+            _ => 0x000F,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpcodeInfo {
+    pub opcode: Opcode,
+    pub mnemonic: &'static str,
+    pub base: u16,                              // the word with a zero operand
+    pub operand: Operand,
+}
+
+const fn op(opcode: Opcode, mnemonic: &'static str, base: u16, operand: Operand) -> OpcodeInfo {
+    OpcodeInfo { opcode, mnemonic, base, operand }
+}
+
+use Opcode::*;
+use Operand::*;
+
+// in the order of Opcode
+pub const OPCODES: [OpcodeInfo; 75] = [
+    op(JMP,   "JMP",   0x1000, Address),        // jump
+    op(JSX,   "JSX",   0x2000, Address),        // jump and store PCR in index
+    op(STB,   "STB",   0x3000, Address),        // store byte
+    op(CMB,   "CMB",   0x4000, Address),        // compare byte
+    op(LDB,   "LDB",   0x5000, Address),        // load byte
+    op(STX,   "STX",   0x6000, Address),        // store index
+    op(STW,   "STW",   0x7000, Address),        // store word
+    op(LDW,   "LDW",   0x8000, Address),        // load word
+    op(LDX,   "LDX",   0x9000, Address),        // load index
+    op(ADD,   "ADD",   0xA000, Address),
+    op(SUB,   "SUB",   0xB000, Address),
+    op(ORI,   "ORI",   0xC000, Address),        // inclusive or
+    op(ORE,   "ORE",   0xD000, Address),        // exclusive or
+    op(AND,   "AND",   0xE000, Address),
+    op(CMW,   "CMW",   0xF000, Address),        // compare word
+    op(HLT,   "HLT",   0x0000, Unused),
+    op(INRET, "INRET", 0x0010, Level),          // interrupt return
+    op(ENB,   "ENB",   0x0020, Level),          // enable level
+    op(DSB,   "DSB",   0x0030, Level),          // disable level
+    op(SLM,   "SLM",   0x0040, Unused),         // set local mode
+    op(SGM,   "SGM",   0x0050, Unused),         // set global mode
+    op(CEX,   "CEX",   0x0060, Unused),         // copy extension to index
+    op(CXE,   "CXE",   0x0070, Unused),         // copy index to extension
+    op(SML,   "SML",   0x0080, Bank),           // set memory lower
+    op(SMU,   "SMU",   0x0090, Bank),           // set memory upper
+    op(MSK,   "MSK",   0x00A0, Unused),         // mask interrupts
+    op(UNM,   "UNM",   0x00B0, Unused),         // unmask interrupts
+    op(CLR,   "CLR",   0x0110, Unused),         // clear ACR
+    op(CMP,   "CMP",   0x0120, Unused),         // complement ACR
+    op(INV,   "INV",   0x0130, Unused),         // invert ACR
+    op(CAX,   "CAX",   0x0140, Unused),         // copy ACR to index
+    op(CXA,   "CXA",   0x0150, Unused),         // copy index to ACR
+    op(DIN,   "DIN",   0x0200, Byte),           // direct input
+    op(DOT,   "DOT",   0x0300, Byte),           // direct output
+    op(IXS,   "IXS",   0x0400, Byte),           // increment index and skip
+    op(DXS,   "DXS",   0x0500, Byte),           // decrement index and skip
+    op(LLB,   "LLB",   0x0600, Byte),           // load literal byte
+    op(CLB,   "CLB",   0x0700, Byte),           // compare literal byte
+    op(TRAP,  "TRAP",  0x0F00, Byte),           // emulator host call, see hostcall.rs
+    op(SAZ,   "SAZ",   0x0800, Unused),         // skips
+    op(SAP,   "SAP",   0x0810, Unused),
+    op(SAM,   "SAM",   0x0820, Unused),
+    op(SAO,   "SAO",   0x0830, Unused),
+    op(SLS,   "SLS",   0x0840, Unused),
+    op(SXE,   "SXE",   0x0850, Unused),
+    op(SEQ,   "SEQ",   0x0860, Unused),
+    op(SNE,   "SNE",   0x0870, Unused),
+    op(SGR,   "SGR",   0x0880, Unused),
+    op(SLE,   "SLE",   0x0890, Unused),
+    op(SNO,   "SNO",   0x08A0, Unused),
+    op(SSE,   "SSE",   0x08B0, Unused),
+    op(SS0,   "SS0",   0x08C0, Unused),
+    op(SS1,   "SS1",   0x08D0, Unused),
+    op(SS2,   "SS2",   0x08E0, Unused),
+    op(SS3,   "SS3",   0x08F0, Unused),
+    op(SRA,   "SRA",   0x0900, Count),          // arithmetic shifts
+    op(SLA,   "SLA",   0x0910, Count),
+    op(SRAD,  "SRAD",  0x0920, Count),
+    op(SLAD,  "SLAD",  0x0930, Count),
+    op(SRL,   "SRL",   0x0A00, Count),          // logical and circular shifts
+    op(SLL,   "SLL",   0x0A10, Count),
+    op(SRLD,  "SRLD",  0x0A20, Count),
+    op(SLLD,  "SLLD",  0x0A30, Count),
+    op(SRC,   "SRC",   0x0A40, Count),
+    op(SLC,   "SLC",   0x0A50, Count),
+    op(SRCD,  "SRCD",  0x0A60, Count),
+    op(SLCD,  "SLCD",  0x0A70, Count),
+    op(SRLL,  "SRLL",  0x0A80, Count),
+    op(SLLL,  "SLLL",  0x0A90, Count),
+    op(SRLR,  "SRLR",  0x0AA0, Count),
+    op(SLLR,  "SLLR",  0x0AB0, Count),
+    op(SRCL,  "SRCL",  0x0AC0, Count),
+    op(SLCL,  "SLCL",  0x0AD0, Count),
+    op(SRCR,  "SRCR",  0x0AE0, Count),
+    op(SLCR,  "SLCR",  0x0AF0, Count),
+];
+
+// Decode table, built from OPCODES.  Entries 0x000..0x0FF are words with a
+// zero top digit, indexed by bits 11..4; entries 0x100..0x10F are the memory
+// reference opcodes, indexed by the top digit.
+const DECODE: [Option<Opcode>; 0x110] = decode_table();
+
+const fn decode_table() -> [Option<Opcode>; 0x110] {
+    let mut table = [None; 0x110];
+    let mut i = 0;
+    while i < OPCODES.len() {
+        let info = &OPCODES[i];
+        assert!(info.opcode as usize == i, "OPCODES out of order");
+        match info.operand {
+            Address => table[0x100 + (info.base >> 12) as usize] = Some(info.opcode),
+            Byte => {
+                let mut digit = 0;
+                while digit < 16 {
+                    table[(info.base >> 4) as usize + digit] = Some(info.opcode);
+                    digit += 1;
+                }
+            },
+            _ => table[(info.base >> 4) as usize] = Some(info.opcode),
+        }
+        i += 1;
+    }
+    table
+}
+
+impl Opcode {
+    pub fn info(self) -> &'static OpcodeInfo {
+        &OPCODES[self as usize]
+    }
+
+    pub fn mnemonic(self) -> &'static str {
+        self.info().mnemonic
+    }
+
+    pub fn operand(self) -> Operand {
+        self.info().operand
+    }
+
+    // STB, CMB and LDB address a byte: twice the word address, plus one for
+    // the right byte, so their page is 1K words rather than 2K
+    pub fn byte_reference(self) -> bool {
+        matches!(self, STB | CMB | LDB)
+    }
+
+        pub fn from_mnemonic(mnemonic: &str) -> Option<Opcode> {
+        OPCODES.iter()
+            .find(|info| info.mnemonic.eq_ignore_ascii_case(mnemonic))
+            .map(|info| info.opcode)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Instruction {
+    pub opcode: Opcode,
+    pub indexed: bool,                          // memory reference through the index register
+    pub operand: u16,                           // address, byte, level, bank or count
+}
+
+impl Instruction {
+    pub fn new(opcode: Opcode, operand: u16) -> Self {
+        Instruction { opcode, indexed: false, operand: operand & opcode.operand().mask() }
+    }
+
+    pub fn indexed(opcode: Opcode, address: u16) -> Self {
+        Instruction { indexed: opcode.operand() == Address, ..Instruction::new(opcode, address) }
+    }
+
+    // None for a word that is not an instruction
+    #[inline]
+    pub fn decode(word: u16) -> Option<Instruction> {
+        let index = if word & 0xF000 != 0 { 0x100 + (word >> 12) as usize } else { (word >> 4) as usize };
+        let opcode = DECODE[index]?;
+        Some(Instruction {
+            opcode,
+            indexed: opcode.operand() == Address && word & 0x0800 != 0,
+            operand: word & opcode.operand().mask(),
+        })
+    }
+
+    pub fn encode(&self) -> u16 {
+        let info = self.opcode.info();
+        let index = if self.indexed && info.operand == Address { 0x0800 } else { 0 };
+        info.base | index | (self.operand & info.operand.mask())
+    }
+
+    // core word a memory reference at location refers to, before indexing
+    pub fn target(&self, location: u16) -> Option<u16> {
+        if self.opcode.operand() != Address {
+            return None;
+        }
+        Some(if self.opcode.byte_reference() {
+            (location & 0x7C00) | (self.operand >> 1)
+        } else {
+            (location & 0x7800) | self.operand
+        })
+    }
+}
+
+// assembler syntax: "LDW 0x0115,X", "DIN 0x20", "ENB 3", "CLR"
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mnemonic = self.opcode.mnemonic();
+        match self.opcode.operand() {
+            Address => write!(f, "{:<5} 0x{:04X}{}", mnemonic, self.operand, if self.indexed { ",X" } else { "" }),
+            Byte => write!(f, "{:<5} 0x{:02X}", mnemonic, self.operand),
+            Unused if self.operand == 0 => write!(f, "{}", mnemonic),
+            _ => write!(f, "{:<5} {}", mnemonic, self.operand),
+        }
+    }
+}
@@ -0,0 +1,66 @@
+// Interrupt event log
+//
+// With the log enabled the CPU records every interrupt request as it is
+// first seen at an instruction boundary, every entry into a level and every
+// exit from it, so the timing of a handler can be checked after a run.
+
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InterruptEventKind {
+    Requested,
+    Entered,
+    Exited,                                     // INRET
+    Disabled,                                   // DSB dropped the request and active state
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InterruptEvent {
+    pub cycle: u64,
+    pub instruction: u64,
+    pub level: u8,
+    pub kind: InterruptEventKind,
+    pub pcr: u16,                               // program counter at the event
+}
+
+impl fmt::Display for InterruptEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self.kind {
+            InterruptEventKind::Requested => "requested",
+            InterruptEventKind::Entered => "entered",
+            InterruptEventKind::Exited => "exited",
+            InterruptEventKind::Disabled => "disabled",
+        };
+        write!(f, "cycle {:>10}  inst {:>10}  level {:>2}  {:<9}  PCR {:04X}",
+               self.cycle, self.instruction, self.level, kind, self.pcr)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct InterruptLog {
+    pub enabled: bool,
+    pub events: Vec<InterruptEvent>,
+}
+
+impl InterruptLog {
+    pub fn new() -> Self {
+        InterruptLog { enabled: false, events: Vec::new() }
+    }
+
+    pub fn record(&mut self, event: InterruptEvent) {
+        if self.enabled {
+            self.events.push(event);
+        }
+    }
+
+    // events for one level, in order
+    pub fn level(&self, level: u8) -> impl Iterator<Item = &InterruptEvent> {
+        self.events.iter().filter(move |event| event.level == level)
+    }
+
+    pub fn print(&self) {
+        for event in &self.events {
+            println!("{}", event);
+        }
+    }
+}
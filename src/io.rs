@@ -0,0 +1,175 @@
+// The DIN/DOT input/output bus
+//
+// DIN (0x02nn) reads a word from bus address nn into the ACR and DOT (0x03nn)
+// writes the ACR to bus address nn.  A device is attached at a base address
+// and answers on as many consecutive addresses as it has registers.  Reads
+// from an address with nothing attached return zero and writes are ignored,
+// as on the real bus.
+//
+// Devices are serviced after every instruction with the current cycle count
+// so they can finish operations in emulated time, move data to and from core
+// and request interrupts.  A device attached with an interrupt level has its
+// requests posted to that bit of Cpu::int_req.
+
+use std::any::Any;
+use std::fmt;
+
+use crate::cpu::Memory;
+
+pub trait Device {
+    fn name(&self) -> &str;
+    fn registers(&self) -> u8 {                     // number of bus addresses used
+        1
+    }
+    fn din(&mut self, reg: u8, now: u64) -> i16;
+    fn dot(&mut self, reg: u8, value: i16, now: u64);
+    fn service(&mut self, _now: u64, _memory: &mut Memory) -> bool {   // true requests an interrupt
+        false
+    }
+    fn busy(&self) -> bool {
+        false
+    }
+    fn reset(&mut self) {}
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+struct Slot {
+    base: u8,
+    count: u8,
+    level: Option<u8>,                              // interrupt level, if wired
+    device: Box<dyn Device>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DeviceInfo {
+    pub base: u8,
+    pub count: u8,
+    pub level: Option<u8>,
+    pub name: String,
+    pub busy: bool,
+}
+
+#[derive(Debug)]
+pub enum BusError {
+    AddressInUse { base: u8, count: u8, owner: String },
+    AddressRange { base: u8, count: u8 },
+    BadLevel(u8),
+}
+
+impl fmt::Display for BusError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BusError::AddressInUse { base, count, owner } =>
+                write!(f, "bus addresses {:02X}..{:02X} overlap device {}",
+                       base, *base as u16 + *count as u16 - 1, owner),
+            BusError::AddressRange { base, count } =>
+                write!(f, "{} registers at bus address {:02X} run past address FF", count, base),
+            BusError::BadLevel(level) => write!(f, "interrupt level {} is not 0..15", level),
+        }
+    }
+}
+
+impl std::error::Error for BusError {}
+
+#[derive(Default)]
+pub struct IoBus {
+    slots: Vec<Slot>,
+}
+
+impl IoBus {
+    pub fn new() -> Self {
+        IoBus { slots: Vec::new() }
+    }
+
+    // attach a device at base, optionally wired to an interrupt level
+    pub fn attach(&mut self, base: u8, level: Option<u8>, device: Box<dyn Device>) -> Result<(), BusError> {
+        let count = device.registers().max(1);
+        if base as u16 + count as u16 > 0x100 {
+            return Err(BusError::AddressRange { base, count });
+        }
+        if let Some(level) = level {
+            if level > 15 {
+                return Err(BusError::BadLevel(level));
+            }
+        }
+        for slot in &self.slots {
+            if (base as u16) < slot.base as u16 + slot.count as u16 &&
+               (slot.base as u16) < base as u16 + count as u16 {
+                return Err(BusError::AddressInUse { base, count, owner: slot.device.name().to_string() });
+            }
+        }
+        self.slots.push(Slot { base, count, level, device });
+        Ok(())
+    }
+
+    // remove the device at base, handing it back
+    pub fn detach(&mut self, base: u8) -> Option<Box<dyn Device>> {
+        let index = self.slots.iter().position(|slot| slot.base == base)?;
+        Some(self.slots.remove(index).device)
+    }
+
+    fn slot_for(&mut self, address: u8) -> Option<&mut Slot> {
+        self.slots.iter_mut()
+            .find(|slot| address >= slot.base && (address - slot.base) < slot.count)
+    }
+
+    pub fn din(&mut self, address: u8, now: u64) -> i16 {
+        match self.slot_for(address) {
+            Some(slot) => slot.device.din(address - slot.base, now),
+            None => 0,                              // nothing answers, bus reads zero
+        }
+    }
+
+    pub fn dot(&mut self, address: u8, value: i16, now: u64) {
+        if let Some(slot) = self.slot_for(address) {
+            slot.device.dot(address - slot.base, value, now);
+        }
+    }
+
+    // service every device, returning the interrupt requests they raised
+    pub fn service(&mut self, now: u64, memory: &mut Memory) -> u16 {
+        let mut requests = 0;
+        for slot in self.slots.iter_mut() {
+            if slot.device.service(now, memory) {
+                if let Some(level) = slot.level {
+                    requests |= 0x0001 << level;
+                }
+            }
+        }
+        requests
+    }
+
+    pub fn reset(&mut self) {
+        for slot in self.slots.iter_mut() {
+            slot.device.reset();
+        }
+    }
+
+    // the device attached at base, if it is of type T
+    pub fn device_mut<T: Device + 'static>(&mut self, base: u8) -> Option<&mut T> {
+        self.slots.iter_mut()
+            .find(|slot| slot.base == base)
+            .and_then(|slot| slot.device.as_any_mut().downcast_mut::<T>())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    // busy line of each device, with its bus address
+    pub fn busy_lines(&self) -> impl Iterator<Item = (u8, bool)> + '_ {
+        self.slots.iter().map(|slot| (slot.base, slot.device.busy()))
+    }
+
+    pub fn devices(&self) -> Vec<DeviceInfo> {
+        self.slots.iter()
+            .map(|slot| DeviceInfo {
+                base: slot.base,
+                count: slot.count,
+                level: slot.level,
+                name: slot.device.name().to_string(),
+                busy: slot.device.busy(),
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,53 @@
+/*Rustheon Raytheon 703 emulator written in Rust
+
+MIT License
+Copyright (c) 2023 Darwin Geiselbrecht
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the "Software"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+*/
+
+#![allow(clippy::assign_op_pattern, clippy::upper_case_acronyms, clippy::unnecessary_cast)]
+
+pub mod cpu;
+pub mod absolute;
+pub mod io;
+pub mod papertape;
+pub mod boot;
+pub mod magtape;
+pub mod disk;
+pub mod printer;
+pub mod teletype;
+pub mod analog;
+pub mod digital;
+pub mod plant;
+pub mod schedule;
+pub mod interrupt;
+pub mod vcd;
+pub mod symbols;
+pub mod profile;
+pub mod dispatch;
+pub mod instruction;
+pub mod disasm;
+pub mod assembler;
+pub mod config;
+pub mod batch;
+pub mod expect;
+pub mod console;
+pub mod hostcall;
+pub mod asmtest;
+pub mod shadow;
+pub mod protect;
+pub mod selfmod;
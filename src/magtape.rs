@@ -0,0 +1,461 @@
+// Nine track magnetic tape unit backed by a SIMH .tap container
+//
+// A .tap file is a sequence of 32 bit little endian markers.  A data record is
+// its byte length, the data padded to an even length, then the length again.
+// A zero marker is a tape mark, FFFFFFFF is the end of medium and FFFFFFFE is
+// an erase gap.  Bit 31 of a record length flags a record read with errors.
+//
+// Registers
+//      DOT +0  command       function in bits 3..0, INT_ENABLE interrupts on completion
+//      DOT +1  core address  first word of the transfer
+//      DOT +2  word count    words to read or write
+//      DIN +0  status        READY, BUSY, ERROR, BOT, EOT, TAPE_MARK, LENGTH_ERROR, WRITE_LOCK
+//      DIN +1  core address  next word the transfer would have used
+//      DIN +2  byte count    length of the last record read or written, FFFF if longer
+//
+// Words go to and from tape high byte first.  A read stops at the word count
+// and flags LENGTH_ERROR if the record was longer; a short record leaves the
+// rest of the buffer alone.  A write of no words is an ERROR, since a zero
+// length is the tape mark in the container.  Commands take emulated time: a
+// start delay plus the time for every byte passed over.
+
+use std::any::Any;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::cpu::Memory;
+use crate::io::Device;
+
+pub const MAGTAPE_ADDRESS:u8 = 0x10;            // standard bus address
+
+pub const READY:i16 = 0x8000u16 as i16;         // status bits
+pub const BUSY:i16 = 0x4000;
+pub const ERROR:i16 = 0x2000;
+pub const BOT:i16 = 0x1000;
+pub const EOT:i16 = 0x0800;
+pub const TAPE_MARK:i16 = 0x0400;
+pub const LENGTH_ERROR:i16 = 0x0200;
+pub const WRITE_LOCK:i16 = 0x0100;
+
+pub const FN_READ:i16 = 1;                      // command functions
+pub const FN_WRITE:i16 = 2;
+pub const FN_WRITE_MARK:i16 = 3;
+pub const FN_REWIND:i16 = 4;
+pub const FN_SKIP_RECORD:i16 = 5;
+pub const FN_BACKSPACE_RECORD:i16 = 6;
+pub const FN_SKIP_FILE:i16 = 7;
+pub const FN_BACKSPACE_FILE:i16 = 8;
+pub const INT_ENABLE:i16 = 0x0100;
+
+pub const START_CYCLES:u64 = 2_857;             // 5 ms start/stop
+pub const BYTE_CYCLES:u64 = 19;                 // 800 bpi at 37.5 ips
+pub const REWIND_CYCLES:u64 = 1;                // per byte rewound
+
+const TAP_MARK:u32 = 0x0000_0000;
+const TAP_EOM:u32 = 0xFFFF_FFFF;
+const TAP_GAP:u32 = 0xFFFF_FFFE;
+const TAP_ERROR:u32 = 0x8000_0000;
+const TAP_LENGTH:u32 = 0x00FF_FFFF;
+
+// a record length, rather than a mark, gap, end of medium or reserved marker
+fn is_length(marker: u32) -> bool {
+    marker != TAP_MARK && marker & !(TAP_ERROR | TAP_LENGTH) == 0
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TapRecord {
+    Data { bytes: Vec<u8>, error: bool },
+    TapeMark,
+    EndOfMedium,                                // no more recorded data
+}
+
+// a .tap container held in memory and positioned between markers
+#[derive(Debug, Default)]
+pub struct TapImage {
+    data: Vec<u8>,
+    position: usize,
+}
+
+impl TapImage {
+    pub fn new() -> Self {
+        TapImage { data: Vec::new(), position: 0 }
+    }
+
+    pub fn from_bytes(data: Vec<u8>) -> Self {
+        TapImage { data, position: 0 }
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn at_bot(&self) -> bool {
+        self.position == 0
+    }
+
+    pub fn rewind(&mut self) -> usize {
+        let travelled = self.position;
+        self.position = 0;
+        travelled
+    }
+
+    fn marker_at(&self, offset: usize) -> Option<u32> {
+        let bytes = self.data.get(offset..offset + 4)?;
+        Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    // read forward over the next record, returning it and the bytes passed over
+    pub fn read_forward(&mut self) -> (TapRecord, usize) {
+        let start = self.position;
+        loop {
+            let marker = match self.marker_at(self.position) {
+                Some(marker) => marker,
+                None => return (TapRecord::EndOfMedium, self.position - start),
+            };
+            match marker {
+                TAP_GAP => { self.position += 4; },
+                TAP_MARK => {
+                    self.position += 4;
+                    return (TapRecord::TapeMark, self.position - start);
+                },
+                _ if !is_length(marker) => return (TapRecord::EndOfMedium, self.position - start),
+                _ => {
+                    let length = (marker & TAP_LENGTH) as usize;
+                    let padded = length + (length & 1);
+                    let first = self.position + 4;
+                    if first + padded + 4 > self.data.len() {
+                        return (TapRecord::EndOfMedium, self.position - start);  // truncated container
+                    }
+                    let bytes = self.data[first..first + length].to_vec();
+                    self.position = first + padded + 4;
+                    return (TapRecord::Data { bytes, error: marker & TAP_ERROR != 0 }, self.position - start);
+                },
+            }
+        }
+    }
+
+    // space backward over the previous record, returning it and the bytes
+    // passed over; None at the load point or at a marker that is not a
+    // record's, where the tape stops
+    pub fn read_backward(&mut self) -> (Option<TapRecord>, usize) {
+        let start = self.position;
+        loop {
+            if self.position < 4 {
+                self.position = 0;
+                return (None, start);                        // at load point
+            }
+            let Some(marker) = self.marker_at(self.position - 4) else {
+                return (None, start - self.position);        // past the end of the container
+            };
+            match marker {
+                TAP_GAP | TAP_EOM => { self.position -= 4; },
+                TAP_MARK => {
+                    self.position -= 4;
+                    return (Some(TapRecord::TapeMark), start - self.position);
+                },
+                _ if !is_length(marker) => return (None, start - self.position),
+                _ => {
+                    let length = (marker & TAP_LENGTH) as usize;
+                    let padded = length + (length & 1);
+                    if self.position < padded + 8 || self.marker_at(self.position - padded - 8) != Some(marker) {
+                        return (None, start - self.position);   // the leading length does not match
+                    }
+                    self.position -= padded + 8;
+                    let first = self.position + 4;
+                    let bytes = self.data[first..first + length].to_vec();
+                    return (Some(TapRecord::Data { bytes, error: marker & TAP_ERROR != 0 }), start - self.position);
+                },
+            }
+        }
+    }
+
+    // write a record or tape mark here, erasing everything after it; a record
+    // must have from 1 to FFFFFF bytes to have a length marker
+    pub fn write(&mut self, record: &TapRecord) -> io::Result<usize> {
+        if let TapRecord::Data { bytes, .. } = record {
+            if bytes.is_empty() || bytes.len() > TAP_LENGTH as usize {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                          format!("a .tap record cannot have {} bytes", bytes.len())));
+            }
+        }
+        self.data.truncate(self.position);
+        let start = self.position;
+        match record {
+            TapRecord::Data { bytes, error } => {
+                let mut marker = bytes.len() as u32;
+                if *error { marker |= TAP_ERROR; }
+                self.data.extend_from_slice(&marker.to_le_bytes());
+                self.data.extend_from_slice(bytes);
+                if bytes.len() & 1 != 0 { self.data.push(0); }
+                self.data.extend_from_slice(&marker.to_le_bytes());
+            },
+            TapRecord::TapeMark => self.data.extend_from_slice(&TAP_MARK.to_le_bytes()),
+            TapRecord::EndOfMedium => {},
+        }
+        self.position = self.data.len();
+        Ok(self.position - start)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Pending {
+    Idle,
+    Starting(i16),                              // waiting out the start delay
+    Moving,                                     // data moved, tape still in motion
+}
+
+pub struct MagTape {
+    image: TapImage,
+    path: Option<PathBuf>,                      // host file written back after every write
+    mounted: bool,
+    write_lock: bool,
+    address: u16,
+    word_count: u16,
+    byte_count: u32,
+    status: i16,                                // result bits of the last command
+    pending: Pending,
+    done_at: u64,
+    int_enable: bool,
+    pub start_cycles: u64,
+    pub byte_cycles: u64,
+    pub last_error: Option<String>,             // host file error, if any
+}
+
+impl MagTape {
+    pub fn new() -> Self {
+        MagTape {
+            image: TapImage::new(),
+            path: None,
+            mounted: false,
+            write_lock: true,
+            address: 0,
+            word_count: 0,
+            byte_count: 0,
+            status: 0,
+            pending: Pending::Idle,
+            done_at: 0,
+            int_enable: false,
+            start_cycles: START_CYCLES,
+            byte_cycles: BYTE_CYCLES,
+            last_error: None,
+        }
+    }
+
+    // mount a reel from a .tap file, creating it if writing is allowed
+    pub fn mount_file<P: AsRef<Path>>(&mut self, path: P, write_enable: bool) -> io::Result<()> {
+        let path = path.as_ref();
+        let data = match fs::read(path) {
+            Ok(data) => data,
+            Err(err) if err.kind() == io::ErrorKind::NotFound && write_enable => Vec::new(),
+            Err(err) => return Err(err),
+        };
+        self.mount(TapImage::from_bytes(data), !write_enable);
+        self.path = Some(path.to_path_buf());
+        Ok(())
+    }
+
+    pub fn mount(&mut self, image: TapImage, write_lock: bool) {
+        self.image = image;
+        self.path = None;
+        self.mounted = true;
+        self.write_lock = write_lock;
+        self.status = 0;
+    }
+
+    pub fn unmount(&mut self) -> TapImage {
+        self.mounted = false;
+        std::mem::take(&mut self.image)
+    }
+
+    pub fn image(&self) -> &TapImage {
+        &self.image
+    }
+
+    fn flush(&mut self) {
+        if let Some(path) = &self.path {
+            if let Err(err) = fs::write(path, self.image.bytes()) {
+                self.last_error = Some(format!("{}: {}", path.display(), err));
+            }
+        }
+    }
+
+    // start a command, working out how long it will take
+    fn start(&mut self, command: i16, now: u64) {
+        if self.pending != Pending::Idle {
+            return;                                 // busy, command ignored
+        }
+        self.int_enable = command & INT_ENABLE != 0;
+        self.pending = Pending::Starting(command & 0x000F);
+        self.done_at = now + self.start_cycles;
+    }
+
+    // carry out the command at its completion time
+    fn complete(&mut self, function: i16, memory: &mut Memory) {
+        self.status = 0;
+        if !self.mounted {
+            self.status = ERROR;
+            return;
+        }
+        match function {
+            FN_READ => {
+                let (record, _) = self.image.read_forward();
+                match record {
+                    TapRecord::Data { bytes, error } => {
+                        self.byte_count = bytes.len() as u32;
+                        let words = bytes.len().div_ceil(2);
+                        if words > self.word_count as usize {
+                            self.status |= LENGTH_ERROR;
+                        }
+                        for (i, pair) in bytes.chunks(2).take(self.word_count as usize).enumerate() {
+                            let low = if pair.len() > 1 { pair[1] } else { 0 };
+                            memory.write((self.address as usize + i) & 0x7FFF, i16::from_be_bytes([pair[0], low]));
+                        }
+                        self.address = self.address.wrapping_add(words.min(self.word_count as usize) as u16);
+                        if error { self.status |= ERROR; }
+                    },
+                    TapRecord::TapeMark => { self.byte_count = 0; self.status |= TAPE_MARK; },
+                    TapRecord::EndOfMedium => { self.byte_count = 0; self.status |= EOT | ERROR; },
+                }
+            },
+            FN_WRITE | FN_WRITE_MARK => {
+                if self.write_lock {
+                    self.status |= ERROR;
+                    return;
+                }
+                if function == FN_WRITE && self.word_count == 0 {
+                    self.status |= ERROR;                   // nothing to write, and not a tape mark
+                    return;
+                }
+                let record = if function == FN_WRITE {
+                    let mut bytes = Vec::with_capacity(self.word_count as usize * 2);
+                    for i in 0..self.word_count as usize {
+                        bytes.extend_from_slice(&memory.core[(self.address as usize + i) & 0x7FFF].to_be_bytes());
+                    }
+                    self.address = self.address.wrapping_add(self.word_count);
+                    self.byte_count = bytes.len() as u32;
+                    TapRecord::Data { bytes, error: false }
+                } else {
+                    TapRecord::TapeMark
+                };
+                if self.image.write(&record).is_err() {
+                    self.status |= ERROR;
+                    return;
+                }
+                self.flush();
+            },
+            FN_REWIND => { self.image.rewind(); },
+            FN_SKIP_RECORD => {
+                match self.image.read_forward().0 {
+                    TapRecord::TapeMark => self.status |= TAPE_MARK,
+                    TapRecord::EndOfMedium => self.status |= EOT,
+                    TapRecord::Data { .. } => {},
+                }
+            },
+            FN_BACKSPACE_RECORD => {
+                if let Some(TapRecord::TapeMark) = self.image.read_backward().0 {
+                    self.status |= TAPE_MARK;
+                }
+            },
+            FN_SKIP_FILE => loop {
+                match self.image.read_forward().0 {
+                    TapRecord::TapeMark => { self.status |= TAPE_MARK; break; },
+                    TapRecord::EndOfMedium => { self.status |= EOT; break; },
+                    TapRecord::Data { .. } => {},
+                }
+            },
+            FN_BACKSPACE_FILE => loop {
+                match self.image.read_backward().0 {
+                    Some(TapRecord::TapeMark) => { self.status |= TAPE_MARK; break; },
+                    None => break,
+                    Some(_) => {},
+                }
+            },
+            _ => { self.status |= ERROR; },        // unknown function
+        }
+    }
+
+    // cycles the tape motion of a command takes once started
+    fn motion_cycles(&self, before: usize, after: usize, function: i16) -> u64 {
+        let travelled = before.abs_diff(after) as u64;
+        if function == FN_REWIND {
+            travelled * REWIND_CYCLES
+        } else {
+            travelled * self.byte_cycles
+        }
+    }
+}
+
+impl Default for MagTape {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Device for MagTape {
+    fn name(&self) -> &str {
+        "magnetic tape"
+    }
+
+    fn registers(&self) -> u8 {
+        3
+    }
+
+    fn din(&mut self, reg: u8, _now: u64) -> i16 {
+        match reg {
+            0 => {
+                let mut status = self.status;
+                if self.pending == Pending::Idle { status |= READY; } else { status |= BUSY; }
+                if self.mounted && self.image.at_bot() { status |= BOT; }
+                if self.write_lock { status |= WRITE_LOCK; }
+                status
+            },
+            1 => self.address as i16,
+            _ => self.byte_count.min(0xFFFF) as u16 as i16,
+        }
+    }
+
+    fn dot(&mut self, reg: u8, value: i16, now: u64) {
+        match reg {
+            0 => self.start(value, now),
+            1 => self.address = value as u16 & 0x7FFF,
+            _ => self.word_count = value as u16,
+        }
+    }
+
+    fn service(&mut self, now: u64, memory: &mut Memory) -> bool {
+        if self.pending == Pending::Idle || now < self.done_at {
+            return false;
+        }
+        if let Pending::Starting(function) = self.pending {
+            let before = self.image.position();
+            self.complete(function, memory);
+            let motion = self.motion_cycles(before, self.image.position(), function);
+            if motion > 0 {
+                self.pending = Pending::Moving;         // hold busy while the tape moves
+                self.done_at = now + motion;
+                return false;
+            }
+        }
+        self.pending = Pending::Idle;
+        self.int_enable
+    }
+
+    fn busy(&self) -> bool {
+        self.pending != Pending::Idle
+    }
+
+    fn reset(&mut self) {
+        self.pending = Pending::Idle;
+        self.status = 0;
+        self.int_enable = false;
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
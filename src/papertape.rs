@@ -0,0 +1,238 @@
+// High speed paper tape reader and punch
+//
+// Reader registers
+//      DIN +0  data      frame in the low byte, reading it starts the next frame
+//      DIN +1  status    READY, BUSY, END_OF_TAPE
+//      DOT +0  control   RUN starts the reader moving, INT_ENABLE interrupts on READY
+//
+// Punch registers
+//      DOT +0  data      low byte is punched
+//      DIN +1  status    READY when the punch can take another frame
+//      DOT +1  control   INT_ENABLE interrupts when the punch becomes ready
+//
+// Status words keep READY in bit 15 so a program can test it with SAM.
+
+use std::any::Any;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::absolute::AbsoluteTape;
+use crate::cpu::Memory;
+use crate::io::Device;
+
+pub const READER_ADDRESS:u8 = 0x04;             // standard bus addresses
+pub const PUNCH_ADDRESS:u8 = 0x06;
+
+pub const READY:i16 = 0x8000u16 as i16;         // status bits
+pub const BUSY:i16 = 0x4000;
+pub const END_OF_TAPE:i16 = 0x2000;
+
+pub const RUN:i16 = 0x0001;                     // control bits
+pub const INT_ENABLE:i16 = 0x0002;
+
+pub const READER_FRAME_CYCLES:u64 = 1_905;      // 300 frames/second at 1.75 us per cycle
+pub const PUNCH_FRAME_CYCLES:u64 = 5_714;       // 100 frames/second
+
+pub struct PaperTapeReader {
+    frames: Vec<u8>,
+    position: usize,                            // next frame under the read head
+    buffer: u8,
+    ready: bool,
+    running: bool,
+    int_enable: bool,
+    next_frame_at: u64,                         // cycle when the next frame arrives
+    pub frame_cycles: u64,
+}
+
+impl PaperTapeReader {
+    pub fn new() -> Self {
+        PaperTapeReader {
+            frames: Vec::new(),
+            position: 0,
+            buffer: 0,
+            ready: false,
+            running: false,
+            int_enable: false,
+            next_frame_at: 0,
+            frame_cycles: READER_FRAME_CYCLES,
+        }
+    }
+
+    // mount a tape and position it at the start of the leader
+    pub fn mount(&mut self, frames: Vec<u8>) {
+        self.frames = frames;
+        self.position = 0;
+        self.ready = false;
+    }
+
+    pub fn mount_file<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        self.mount(fs::read(path)?);
+        Ok(())
+    }
+
+    pub fn mount_absolute(&mut self, tape: &AbsoluteTape) {
+        self.mount(tape.punch());
+    }
+
+    pub fn at_end(&self) -> bool {
+        self.position >= self.frames.len() && !self.ready
+    }
+}
+
+impl Default for PaperTapeReader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Device for PaperTapeReader {
+    fn name(&self) -> &str {
+        "paper tape reader"
+    }
+
+    fn registers(&self) -> u8 {
+        2
+    }
+
+    fn din(&mut self, reg: u8, now: u64) -> i16 {
+        match reg {
+            0 => {
+                self.ready = false;
+                self.next_frame_at = now + self.frame_cycles;
+                self.buffer as i16
+            },
+            _ => {
+                let mut status = 0;
+                if self.ready { status |= READY; }
+                if self.running && !self.ready { status |= BUSY; }
+                if self.at_end() { status |= END_OF_TAPE; }
+                status
+            },
+        }
+    }
+
+    fn dot(&mut self, reg: u8, value: i16, now: u64) {
+        if reg != 0 {
+            return;
+        }
+        let starting = !self.running && value & RUN != 0;
+        self.running = value & RUN != 0;
+        self.int_enable = value & INT_ENABLE != 0;
+        if starting {
+            self.next_frame_at = now + self.frame_cycles;
+        }
+    }
+
+    fn service(&mut self, now: u64, _memory: &mut Memory) -> bool {
+        if !self.running || self.ready || now < self.next_frame_at || self.position >= self.frames.len() {
+            return false;
+        }
+        self.buffer = self.frames[self.position];
+        self.position += 1;
+        self.ready = true;
+        self.int_enable
+    }
+
+    fn busy(&self) -> bool {
+        self.running && !self.ready && self.position < self.frames.len()
+    }
+
+    fn reset(&mut self) {
+        self.ready = false;
+        self.running = false;
+        self.int_enable = false;
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+pub struct PaperTapePunch {
+    frames: Vec<u8>,
+    busy_until: u64,
+    int_enable: bool,
+    int_pending: bool,                          // raise an interrupt when the frame is done
+    pub frame_cycles: u64,
+}
+
+impl PaperTapePunch {
+    pub fn new() -> Self {
+        PaperTapePunch {
+            frames: Vec::new(),
+            busy_until: 0,
+            int_enable: false,
+            int_pending: false,
+            frame_cycles: PUNCH_FRAME_CYCLES,
+        }
+    }
+
+    pub fn frames(&self) -> &[u8] {
+        &self.frames
+    }
+
+    // tear off the tape punched so far
+    pub fn take(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.frames)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, &self.frames)
+    }
+}
+
+impl Default for PaperTapePunch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Device for PaperTapePunch {
+    fn name(&self) -> &str {
+        "paper tape punch"
+    }
+
+    fn registers(&self) -> u8 {
+        2
+    }
+
+    fn din(&mut self, _reg: u8, now: u64) -> i16 {
+        if now >= self.busy_until { READY } else { BUSY }
+    }
+
+    fn dot(&mut self, reg: u8, value: i16, now: u64) {
+        match reg {
+            0 => {
+                self.frames.push((value & 0x00FF) as u8);
+                self.busy_until = now + self.frame_cycles;
+                self.int_pending = true;
+            },
+            _ => {
+                self.int_enable = value & INT_ENABLE != 0;
+            },
+        }
+    }
+
+    fn service(&mut self, now: u64, _memory: &mut Memory) -> bool {
+        if self.int_pending && now >= self.busy_until {
+            self.int_pending = false;
+            return self.int_enable;
+        }
+        false
+    }
+
+    fn busy(&self) -> bool {
+        self.int_pending
+    }
+
+    fn reset(&mut self) {
+        self.busy_until = 0;
+        self.int_enable = false;
+        self.int_pending = false;
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
@@ -0,0 +1,267 @@
+// Closed loop plant simulation
+//
+// A Plant is a model of the process a 703 program controls.  The simulation
+// runs the CPU and every step_cycles of emulated time steps the plant, which
+// reads what the program commanded on the D/A converters and digital outputs
+// and drives the A/D inputs and digital inputs the program will read next.
+//
+// Two reference models come with it: a tank whose level responds to an
+// inlet valve, and a motor with inertia driven by a voltage.
+
+use crate::analog::{AnalogInput, AnalogOutput, ADC_ADDRESS, DAC_ADDRESS};
+use crate::cpu::{cycles_to_seconds, seconds_to_cycles, Cpu, Memory, Mode};
+use crate::digital::{DigitalIo, DIGITAL_ADDRESS};
+use crate::io::IoBus;
+
+pub const STEP_SECONDS:f64 = 0.001;             // default plant step
+
+// bus addresses of the devices a plant is wired to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Wiring {
+    pub adc: u8,
+    pub dac: u8,
+    pub digital: u8,
+}
+
+impl Default for Wiring {
+    fn default() -> Self {
+        Wiring { adc: ADC_ADDRESS, dac: DAC_ADDRESS, digital: DIGITAL_ADDRESS }
+    }
+}
+
+// the plant's view of the process I/O; missing devices read as zero
+pub struct PlantIo<'a> {
+    bus: &'a mut IoBus,
+    wiring: Wiring,
+}
+
+impl<'a> PlantIo<'a> {
+    pub fn new(bus: &'a mut IoBus, wiring: Wiring) -> Self {
+        PlantIo { bus, wiring }
+    }
+
+    pub fn dac_volts(&mut self, channel: u8) -> f64 {
+        self.bus.device_mut::<AnalogOutput>(self.wiring.dac)
+            .map_or(0.0, |dac| dac.volts(channel))
+    }
+
+    pub fn set_adc_volts(&mut self, channel: u8, volts: f64) {
+        if let Some(adc) = self.bus.device_mut::<AnalogInput>(self.wiring.adc) {
+            adc.set_value(channel, volts);
+        }
+    }
+
+    pub fn output_bit(&mut self, group: u8, bit: u8) -> bool {
+        self.bus.device_mut::<DigitalIo>(self.wiring.digital)
+            .is_some_and(|digital| digital.output_bit(group, bit))
+    }
+
+    pub fn set_input_bit(&mut self, group: u8, bit: u8, on: bool) {
+        if let Some(digital) = self.bus.device_mut::<DigitalIo>(self.wiring.digital) {
+            digital.set_input_bit(group, bit, on);
+        }
+    }
+}
+
+pub trait Plant {
+    fn name(&self) -> &str;
+    // advance the model by dt seconds to time t, reading outputs and driving inputs
+    fn step(&mut self, t: f64, dt: f64, io: &mut PlantIo);
+}
+
+impl<P: Plant + ?Sized> Plant for Box<P> {
+    fn name(&self) -> &str {
+        (**self).name()
+    }
+
+    fn step(&mut self, t: f64, dt: f64, io: &mut PlantIo) {
+        (**self).step(t, dt, io)
+    }
+}
+
+pub struct Simulation<P: Plant> {
+    pub plant: P,
+    pub wiring: Wiring,
+    pub step_cycles: u64,
+    last_step_at: Option<u64>,
+}
+
+impl<P: Plant> Simulation<P> {
+    pub fn new(plant: P) -> Self {
+        Simulation {
+            plant,
+            wiring: Wiring::default(),
+            step_cycles: seconds_to_cycles(STEP_SECONDS),
+            last_step_at: None,
+        }
+    }
+
+    // step the plant if a step is due at the CPU's current cycle
+    pub fn catch_up(&mut self, cpu: &mut Cpu) {
+        let now = cpu.cycles;
+        let last = match self.last_step_at {
+            Some(last) => last,
+            None => {
+                self.step(cpu, now, 0);                 // drive the inputs before the program reads them
+                return;
+            },
+        };
+        if now - last >= self.step_cycles {
+            self.step(cpu, now, now - last);
+        }
+    }
+
+    fn step(&mut self, cpu: &mut Cpu, now: u64, elapsed: u64) {
+        let mut io = PlantIo::new(&mut cpu.io, self.wiring);
+        self.plant.step(cycles_to_seconds(now), cycles_to_seconds(elapsed), &mut io);
+        self.last_step_at = Some(now);
+    }
+
+    // run the program against the plant for the given emulated seconds or
+    // until it halts, returning the cycles run
+    pub fn run(&mut self, cpu: &mut Cpu, memory: &mut Memory, seconds: f64) -> u64 {
+        let start = cpu.cycles;
+        let end = start + seconds_to_cycles(seconds);
+        self.catch_up(cpu);
+        while cpu.mode == Mode::RUN && cpu.cycles < end {
+            cpu.step(memory);
+            self.catch_up(cpu);
+        }
+        cpu.cycles - start
+    }
+}
+
+// A tank filled through a proportional inlet valve and draining through an
+// outlet whose flow is proportional to level, which makes the level a first
+// order lag behind the valve.  A manual drain valve adds outflow, and a high
+// level switch closes near the top.
+//
+//      D/A channel   inlet valve, 0..10 V is closed..open
+//      A/D channel   level transmitter, 0..10 V is empty..full
+//      digital out   drain valve open
+//      digital in    high level switch
+pub struct TankLevel {
+    pub level: f64,                             // metres
+    pub height: f64,                            // full scale of the transmitter
+    pub area: f64,                              // square metres
+    pub max_inflow: f64,                        // cubic metres/second, valve wide open
+    pub outflow_per_metre: f64,                 // cubic metres/second per metre of level
+    pub drain_flow: f64,                        // extra outflow with the drain open
+    pub high_level: f64,                        // switch point
+    pub valve_channel: u8,
+    pub level_channel: u8,
+    pub drain_bit: (u8, u8),                    // (group, bit) of the drain valve output
+    pub high_switch_bit: (u8, u8),              // (group, bit) of the high level input
+}
+
+impl TankLevel {
+    pub fn new() -> Self {
+        TankLevel {
+            level: 0.0,
+            height: 4.0,
+            area: 0.2,
+            max_inflow: 0.06,
+            outflow_per_metre: 0.02,
+            drain_flow: 0.01,
+            high_level: 3.8,
+            valve_channel: 0,
+            level_channel: 0,
+            drain_bit: (0, 0),
+            high_switch_bit: (0, 0),
+        }
+    }
+
+    // level the tank settles at with the valve at a fraction open
+    pub fn steady_level(&self, valve: f64) -> f64 {
+        (valve.clamp(0.0, 1.0) * self.max_inflow / self.outflow_per_metre).min(self.height)
+    }
+}
+
+impl Default for TankLevel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Plant for TankLevel {
+    fn name(&self) -> &str {
+        "tank level"
+    }
+
+    fn step(&mut self, _t: f64, dt: f64, io: &mut PlantIo) {
+        let valve = (io.dac_volts(self.valve_channel) / 10.0).clamp(0.0, 1.0);
+        let mut outflow = self.level * self.outflow_per_metre;
+        if io.output_bit(self.drain_bit.0, self.drain_bit.1) {
+            outflow += self.drain_flow;
+        }
+        self.level += (valve * self.max_inflow - outflow) / self.area * dt;
+        self.level = self.level.clamp(0.0, self.height);
+        io.set_adc_volts(self.level_channel, self.level / self.height * 10.0);
+        io.set_input_bit(self.high_switch_bit.0, self.high_switch_bit.1, self.level >= self.high_level);
+    }
+}
+
+// A DC motor and load with inertia and viscous friction, powered through a
+// contactor.  Speed follows the drive voltage with a first order lag set by
+// the inertia, and the shaft angle is the integral of speed.
+//
+//      D/A channel   drive voltage, +/-10 V
+//      A/D channel   tachometer, +/-10 V is +/- full speed
+//      digital out   contactor closed
+//      digital in    at speed, within 5% of full speed
+pub struct Motor {
+    pub speed: f64,                             // radians/second
+    pub angle: f64,                             // radians
+    pub inertia: f64,                           // kg m^2
+    pub torque_per_volt: f64,                   // N m per volt
+    pub friction: f64,                          // N m per radian/second
+    pub full_speed: f64,                        // tachometer full scale
+    pub drive_channel: u8,
+    pub tach_channel: u8,
+    pub contactor_bit: (u8, u8),
+    pub at_speed_bit: (u8, u8),
+}
+
+impl Motor {
+    pub fn new() -> Self {
+        Motor {
+            speed: 0.0,
+            angle: 0.0,
+            inertia: 0.05,
+            torque_per_volt: 0.2,
+            friction: 0.01,
+            full_speed: 200.0,
+            drive_channel: 1,
+            tach_channel: 1,
+            contactor_bit: (0, 1),
+            at_speed_bit: (0, 1),
+        }
+    }
+
+    // speed the motor settles at for a drive voltage
+    pub fn steady_speed(&self, volts: f64) -> f64 {
+        volts * self.torque_per_volt / self.friction
+    }
+}
+
+impl Default for Motor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Plant for Motor {
+    fn name(&self) -> &str {
+        "motor"
+    }
+
+    fn step(&mut self, _t: f64, dt: f64, io: &mut PlantIo) {
+        let powered = io.output_bit(self.contactor_bit.0, self.contactor_bit.1);
+        let volts = if powered { io.dac_volts(self.drive_channel) } else { 0.0 };
+        let torque = volts * self.torque_per_volt - self.speed * self.friction;
+        self.speed += torque / self.inertia * dt;
+        self.angle += self.speed * dt;
+        io.set_adc_volts(self.tach_channel, self.speed / self.full_speed * 10.0);
+        io.set_input_bit(self.at_speed_bit.0, self.at_speed_bit.1, self.speed >= self.full_speed * 0.95);
+    }
+}
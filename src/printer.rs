@@ -0,0 +1,233 @@
+// Line printer
+//
+// Characters are collected in a 132 column line buffer and printed by a
+// carriage control command, which also moves the paper.  Output goes to a
+// host text file, stdout or a buffer kept in memory.
+//
+// Registers
+//      DOT +0  character     ASCII in the low byte, parity bit ignored
+//      DOT +1  carriage      print the buffer, then move the paper
+//                               0000        overprint, no paper motion
+//                               0001..000F  space that many lines
+//                               0100 | n    skip to carriage control channel n (1..12)
+//      DOT +2  control       INT_ENABLE interrupts when a print completes
+//      DIN +0  status        READY, BUSY, TOP_OF_FORM, LINE_FULL
+//
+// The carriage control tape has a channel 1 punch at the top of form and a
+// channel 12 punch near the bottom by default.  Skipping to top of form
+// writes a form feed to the output; other skips write blank lines.
+
+use std::any::Any;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::cpu::Memory;
+use crate::io::Device;
+
+pub const PRINTER_ADDRESS:u8 = 0x08;            // standard bus address
+pub const COLUMNS:usize = 132;
+pub const FORM_LINES:usize = 66;                // 11 inch form at 6 lines per inch
+
+pub const READY:i16 = 0x8000u16 as i16;         // status bits
+pub const BUSY:i16 = 0x4000;
+pub const TOP_OF_FORM:i16 = 0x1000;
+pub const LINE_FULL:i16 = 0x0800;
+
+pub const SKIP_TO_CHANNEL:i16 = 0x0100;         // carriage command
+pub const INT_ENABLE:i16 = 0x0002;              // control bit
+
+pub const LINE_CYCLES:u64 = 57_143;             // 600 lines per minute
+pub const SLEW_CYCLES:u64 = 4_762;              // per extra line of paper motion
+
+pub enum Sink {
+    Stdout,
+    File(BufWriter<File>),
+    Memory(Vec<u8>),
+}
+
+pub struct LinePrinter {
+    sink: Sink,
+    buffer: Vec<u8>,
+    form_line: usize,                           // line of the form at the print position
+    channels: Vec<u16>,                         // carriage tape punches for every form line
+    busy_until: u64,
+    int_pending: bool,
+    int_enable: bool,
+    pub line_cycles: u64,
+    pub slew_cycles: u64,
+    pub last_error: Option<String>,             // host output error, if any
+}
+
+impl LinePrinter {
+    pub fn new(sink: Sink) -> Self {
+        let mut channels = vec![0u16; FORM_LINES];
+        channels[0] |= 1 << 1;                  // channel 1, top of form
+        channels[FORM_LINES - 6] |= 1 << 12;    // channel 12, end of form
+        LinePrinter {
+            sink,
+            buffer: Vec::with_capacity(COLUMNS),
+            form_line: 0,
+            channels,
+            busy_until: 0,
+            int_pending: false,
+            int_enable: false,
+            line_cycles: LINE_CYCLES,
+            slew_cycles: SLEW_CYCLES,
+            last_error: None,
+        }
+    }
+
+    pub fn to_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(LinePrinter::new(Sink::File(BufWriter::new(File::create(path)?))))
+    }
+
+    // replace the carriage control tape with (channel, form line) punches
+    pub fn set_form(&mut self, lines: usize, punches: &[(u8, usize)]) {
+        self.channels = vec![0u16; lines.max(1)];
+        for (channel, line) in punches {
+            if (1..=12).contains(channel) && *line < self.channels.len() {
+                self.channels[*line] |= 1 << channel;
+            }
+        }
+        self.form_line = 0;
+    }
+
+    // everything printed so far, when printing to memory
+    pub fn output(&self) -> Option<&[u8]> {
+        match &self.sink {
+            Sink::Memory(data) => Some(data),
+            _ => None,
+        }
+    }
+
+    pub fn flush(&mut self) {
+        let result = match &mut self.sink {
+            Sink::Stdout => io::stdout().flush(),
+            Sink::File(file) => file.flush(),
+            Sink::Memory(_) => Ok(()),
+        };
+        if let Err(err) = result {
+            self.last_error = Some(err.to_string());
+        }
+    }
+
+    fn emit(&mut self, bytes: &[u8]) {
+        let result = match &mut self.sink {
+            Sink::Stdout => io::stdout().write_all(bytes),
+            Sink::File(file) => file.write_all(bytes),
+            Sink::Memory(data) => { data.extend_from_slice(bytes); Ok(()) },
+        };
+        if let Err(err) = result {
+            self.last_error = Some(err.to_string());
+        }
+    }
+
+    // lines of paper motion to reach the next punch in channel
+    fn lines_to_channel(&self, channel: u8) -> Option<usize> {
+        let form = self.channels.len();
+        (1..=form).find(|n| self.channels[(self.form_line + n) % form] & (1 << channel) != 0)
+    }
+
+    // print the buffer and move the paper, returning the lines moved
+    fn carriage(&mut self, command: i16) -> usize {
+        let mut line = std::mem::take(&mut self.buffer);
+        while line.last() == Some(&b' ') {
+            line.pop();
+        }
+        self.emit(&line);
+        let lines = if command & SKIP_TO_CHANNEL != 0 {
+            let channel = (command & 0x000F) as u8;
+            match self.lines_to_channel(channel) {
+                Some(lines) if channel == 1 && self.form_line + lines == self.channels.len() => {
+                    self.emit(b"\n\x0C");           // top of form on the next sheet
+                    self.form_line = 0;
+                    return lines;
+                },
+                Some(lines) => lines,
+                None => 1,                          // no punch in that channel, just space
+            }
+        } else {
+            (command & 0x000F) as usize
+        };
+        if lines == 0 {
+            self.emit(b"\r");                       // overprint the next line
+        } else {
+            for _ in 0..lines {
+                self.emit(b"\n");
+            }
+        }
+        self.form_line = (self.form_line + lines) % self.channels.len();
+        if matches!(self.sink, Sink::Stdout) {
+            self.flush();
+        }
+        lines
+    }
+}
+
+impl Device for LinePrinter {
+    fn name(&self) -> &str {
+        "line printer"
+    }
+
+    fn registers(&self) -> u8 {
+        3
+    }
+
+    fn din(&mut self, _reg: u8, now: u64) -> i16 {
+        let mut status = if now >= self.busy_until { READY } else { BUSY };
+        if self.form_line == 0 { status |= TOP_OF_FORM; }
+        if self.buffer.len() >= COLUMNS { status |= LINE_FULL; }
+        status
+    }
+
+    fn dot(&mut self, reg: u8, value: i16, now: u64) {
+        match reg {
+            0 => {
+                if self.buffer.len() < COLUMNS {
+                    let mut ch = (value & 0x007F) as u8;
+                    if !(0x20..0x7F).contains(&ch) { ch = b' '; }
+                    self.buffer.push(ch);
+                }
+            },
+            1 => {
+                if now < self.busy_until {
+                    return;                         // still printing, command lost
+                }
+                let lines = self.carriage(value);
+                self.busy_until = now + self.line_cycles + lines.saturating_sub(1) as u64 * self.slew_cycles;
+                self.int_pending = true;
+            },
+            _ => self.int_enable = value & INT_ENABLE != 0,
+        }
+    }
+
+    fn service(&mut self, now: u64, _memory: &mut Memory) -> bool {
+        if self.int_pending && now >= self.busy_until {
+            self.int_pending = false;
+            return self.int_enable;
+        }
+        false
+    }
+
+    fn busy(&self) -> bool {
+        self.int_pending
+    }
+
+    fn reset(&mut self) {
+        self.buffer.clear();
+        self.busy_until = 0;
+        self.int_pending = false;
+        self.int_enable = false;
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl Drop for LinePrinter {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
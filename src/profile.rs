@@ -0,0 +1,233 @@
+// Instruction level profiler
+//
+// With a profiler attached the CPU reports every instruction it executes:
+// the address, the cycles it took and the interrupt level it ran at (the
+// highest bit set in int_act, or base level when none is active).  The
+// cycles of an interrupt entry are charged to the level entered.
+//
+// Call stacks are followed through JSX linkage.  A JSX pushes a frame for
+// its target, and the frame is popped when execution reaches the word after
+// the JSX, which is where JMP 0,X returns to.  Each level keeps its own stack,
+// rooted at the level, so the folded stack output
+//
+//      base;MAIN;FILTER 1520
+//      int3;CLOCK 88
+//
+// shows where each level spends its cycles and can be fed to flamegraph.pl
+// or any other flame graph tool.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::cpu::cycles_to_seconds;
+use crate::symbols::SymbolTable;
+
+pub const BASE_LEVEL:usize = 16;                // index of base level in per-level tables
+const LEVELS:usize = 17;
+const CORE_WORDS:usize = 32_768;
+const MAX_DEPTH:usize = 256;                    // frames kept for a level, deeper calls are not followed
+
+// per-level table index for an int_act value
+pub fn active_level(int_act: u16) -> usize {
+    if int_act == 0 {
+        BASE_LEVEL
+    } else {
+        15 - int_act.leading_zeros() as usize
+    }
+}
+
+fn level_name(level: usize) -> String {
+    if level == BASE_LEVEL { "base".to_string() } else { format!("int{}", level) }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HotSpot {
+    pub address: u16,
+    pub count: u64,
+    pub cycles: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SymbolTime {
+    pub name: String,
+    pub count: u64,
+    pub cycles: u64,
+}
+
+pub struct Profiler {
+    counts: Vec<u64>,                           // executions of each core word
+    cycles: Vec<u64>,                           // cycles spent executing it
+    level_instructions: [u64; LEVELS],
+    level_cycles: [u64; LEVELS],
+    stacks: Vec<Vec<u16>>,                      // per level: the level, then JSX targets
+    returns: Vec<Vec<u16>>,                     // per level: return address of each frame
+    folded: HashMap<Vec<u16>, u64>,             // cycles by stack
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Profiler {
+            counts: vec![0; CORE_WORDS],
+            cycles: vec![0; CORE_WORDS],
+            level_instructions: [0; LEVELS],
+            level_cycles: [0; LEVELS],
+            stacks: (0..LEVELS).map(|level| vec![level as u16]).collect(),
+            returns: vec![Vec::new(); LEVELS],
+            folded: HashMap::new(),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        *self = Profiler::new();
+    }
+
+    fn charge(&mut self, level: usize, cycles: u64) {
+        self.level_cycles[level] += cycles;
+        let stack = &self.stacks[level];
+        match self.folded.get_mut(stack.as_slice()) {
+            Some(total) => *total += cycles,
+            None => { self.folded.insert(stack.clone(), cycles); },
+        }
+    }
+
+    // an instruction at address took cycles with int_act as it was before it
+    // ran; call is the target if it was a JSX
+    pub fn record(&mut self, address: u16, int_act: u16, cycles: u64, call: Option<u16>) {
+        let level = active_level(int_act);
+        let returns = &mut self.returns[level];
+        if let Some(depth) = returns.iter().rposition(|back| *back == address) {
+            returns.truncate(depth);                    // returned from that frame and any above it
+            self.stacks[level].truncate(depth + 1);
+        }
+        let index = address as usize & (CORE_WORDS - 1);
+        self.counts[index] += 1;
+        self.cycles[index] += cycles;
+        self.level_instructions[level] += 1;
+        self.charge(level, cycles);
+        if let Some(target) = call {
+            if self.returns[level].len() < MAX_DEPTH {
+                self.stacks[level].push(target);
+                self.returns[level].push(address.wrapping_add(1));
+            }
+        }
+    }
+
+    // an interrupt was entered, leaving int_act with it the highest level
+    pub fn enter(&mut self, int_act: u16, cycles: u64) {
+        let level = active_level(int_act);
+        self.stacks[level].truncate(1);
+        self.returns[level].clear();
+        self.charge(level, cycles);
+    }
+
+    pub fn total_instructions(&self) -> u64 {
+        self.level_instructions.iter().sum()
+    }
+
+    pub fn total_cycles(&self) -> u64 {
+        self.level_cycles.iter().sum()
+    }
+
+    // (instructions, cycles) at a level, BASE_LEVEL for base
+    pub fn level(&self, level: usize) -> (u64, u64) {
+        (self.level_instructions[level], self.level_cycles[level])
+    }
+
+    pub fn count(&self, address: u16) -> u64 {
+        self.counts[address as usize & (CORE_WORDS - 1)]
+    }
+
+    // every executed address, most cycles first
+    pub fn hot_spots(&self) -> Vec<HotSpot> {
+        let mut spots: Vec<HotSpot> = (0..CORE_WORDS)
+            .filter(|index| self.counts[*index] != 0)
+            .map(|index| HotSpot { address: index as u16, count: self.counts[index], cycles: self.cycles[index] })
+            .collect();
+        spots.sort_by(|a, b| b.cycles.cmp(&a.cycles).then(a.address.cmp(&b.address)));
+        spots
+    }
+
+    // executed code totalled by the symbol it is in, most cycles first
+    pub fn by_symbol(&self, symbols: &SymbolTable) -> Vec<SymbolTime> {
+        let mut totals: HashMap<String, (u64, u64)> = HashMap::new();
+        for spot in self.hot_spots() {
+            let name = match symbols.containing(spot.address) {
+                Some((name, _)) => name.to_string(),
+                None => "(no symbol)".to_string(),
+            };
+            let total = totals.entry(name).or_insert((0, 0));
+            total.0 += spot.count;
+            total.1 += spot.cycles;
+        }
+        let mut times: Vec<SymbolTime> = totals.into_iter()
+            .map(|(name, (count, cycles))| SymbolTime { name, count, cycles })
+            .collect();
+        times.sort_by(|a, b| b.cycles.cmp(&a.cycles).then(a.name.cmp(&b.name)));
+        times
+    }
+
+    // ranked report of the top entries of each table
+    pub fn report(&self, symbols: Option<&SymbolTable>, top: usize) -> String {
+        let total = self.total_cycles().max(1);
+        let percent = |cycles: u64| cycles as f64 * 100.0 / total as f64;
+        let mut out = String::new();
+        out += &format!("Profile: {} instructions, {} cycles, {:.6} seconds\n",
+                        self.total_instructions(), self.total_cycles(), cycles_to_seconds(self.total_cycles()));
+        out += "\nTime by level\n  level    instructions        cycles       %\n";
+        for level in (0..LEVELS).rev() {
+            let (instructions, cycles) = self.level(level);
+            if cycles != 0 {
+                out += &format!("  {:<6} {:>14} {:>13} {:>7.2}\n",
+                                level_name(level), instructions, cycles, percent(cycles));
+            }
+        }
+        out += "\nHot spots\n  address  location              count        cycles       %\n";
+        for spot in self.hot_spots().iter().take(top) {
+            let location = symbols.map_or(String::new(), |symbols| symbols.describe(spot.address));
+            out += &format!("  {:04X}     {:<16} {:>10} {:>13} {:>7.2}\n",
+                            spot.address, location, spot.count, spot.cycles, percent(spot.cycles));
+        }
+        if let Some(symbols) = symbols {
+            out += "\nBy symbol\n  symbol                     count        cycles       %\n";
+            for time in self.by_symbol(symbols).iter().take(top) {
+                out += &format!("  {:<20} {:>11} {:>13} {:>7.2}\n",
+                                time.name, time.count, time.cycles, percent(time.cycles));
+            }
+        }
+        out
+    }
+
+    // folded stacks, one "frame;frame;frame cycles" line per stack, sorted
+    pub fn write_folded<W: Write>(&self, out: &mut W, symbols: Option<&SymbolTable>) -> io::Result<()> {
+        let mut lines: Vec<(String, u64)> = self.folded.iter()
+            .filter(|(_, cycles)| **cycles != 0)
+            .map(|(stack, cycles)| {
+                let mut frames = vec![level_name(stack[0] as usize)];
+                frames.extend(stack[1..].iter().map(|target| match symbols {
+                    Some(symbols) => symbols.describe(*target),
+                    None => format!("{:04X}", target),
+                }));
+                (frames.join(";"), *cycles)
+            })
+            .collect();
+        lines.sort();
+        for (stack, cycles) in lines {
+            writeln!(out, "{} {}", stack, cycles)?;
+        }
+        Ok(())
+    }
+
+    pub fn save_folded<P: AsRef<Path>>(&self, path: P, symbols: Option<&SymbolTable>) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        self.write_folded(&mut out, symbols)?;
+        out.flush()
+    }
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,132 @@
+// Write protected core
+//
+// Ranges of core can be protected against the running program.  A store by
+// STW, STX or STB, or the PCR and status saved by an interrupt entry, into a
+// protected word is caught, and the range says what happens:
+//
+//      halt    report the store and halt the machine without making it
+//      log     report the store, make it and carry on
+//      drop    carry on without making it or a word
+//
+// An interrupt entry halted by a store is not made: the level stays
+// inactive and the PCR and status are left as they were for the report.
+//
+// The loader, console deposits and devices write through Memory::write and
+// are not checked.  A range is given as "0200-02FF" with an optional
+// ":halt", ":log" or ":drop", halt when absent, or in a configuration as
+//
+//      [[protect]]
+//      start = 0x0200
+//      end = 0x02FF                    # inclusive, start alone when absent
+//      action = "log"
+
+use std::fmt;
+
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProtectAction {
+    #[default]
+    Halt,
+    Log,
+    Drop,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProtectedRange {
+    pub start: u16,
+    pub end: Option<u16>,
+    #[serde(default)]
+    pub action: ProtectAction,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RangeError(pub String);
+
+impl fmt::Display for RangeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "bad protected range '{}', expected 0200-02FF[:halt|:log|:drop]", self.0)
+    }
+}
+
+impl std::error::Error for RangeError {}
+
+impl ProtectedRange {
+    pub fn new(start: u16, end: u16, action: ProtectAction) -> Self {
+        ProtectedRange { start, end: Some(end), action }
+    }
+
+    pub fn parse(text: &str) -> Result<ProtectedRange, RangeError> {
+        let error = || RangeError(text.to_string());
+        let (range, action) = match text.split_once(':') {
+            Some((range, "halt")) => (range, ProtectAction::Halt),
+            Some((range, "log")) => (range, ProtectAction::Log),
+            Some((range, "drop")) => (range, ProtectAction::Drop),
+            Some(_) => return Err(error()),
+            None => (text, ProtectAction::Halt),
+        };
+        let address = |text: &str| {
+            let text = text.trim();
+            let digits = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")).unwrap_or(text);
+            u16::from_str_radix(digits, 16).ok().filter(|address| *address <= 0x7FFF)
+        };
+        let (start, end) = match range.split_once('-') {
+            Some((start, end)) => (address(start).ok_or_else(error)?, address(end).ok_or_else(error)?),
+            None => {
+                let start = address(range).ok_or_else(error)?;
+                (start, start)
+            },
+        };
+        if end < start {
+            return Err(error());
+        }
+        Ok(ProtectedRange::new(start, end, action))
+    }
+
+    pub fn last(&self) -> u16 {
+        self.end.unwrap_or(self.start)
+    }
+
+    pub fn contains(&self, address: usize) -> bool {
+        (self.start as usize..=self.last() as usize).contains(&address)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Violation {
+    pub address: u16,                           // protected word
+    pub pcr: u16,                               // instruction that stored
+    pub word: u16,                              // word it would have stored
+    pub action: ProtectAction,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Store of {:04X} to protected core {:04X} at PCR {:04X}", self.word, self.address, self.pcr)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Protection {
+    pub ranges: Vec<ProtectedRange>,
+    pub violations: Vec<Violation>,             // halted and logged stores
+}
+
+impl Protection {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // None when the store may be made, else what to do instead
+    pub fn check(&mut self, address: usize, word: i16, pcr: u16) -> Option<ProtectAction> {
+        let range = self.ranges.iter().find(|range| range.contains(address))?;
+        let violation = Violation { address: address as u16, pcr, word: word as u16, action: range.action };
+        if violation.action != ProtectAction::Drop {
+            println!("{}", violation);
+            self.violations.push(violation);
+        }
+        Some(violation.action)
+    }
+}
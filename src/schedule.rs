@@ -0,0 +1,141 @@
+// Scheduled interrupt injection
+//
+// Embedding code schedules interrupt requests at a cycle or at an
+// instruction count, once or repeating with a period in the same unit.
+// The CPU polls the scheduler in check_interrupts at every instruction
+// boundary and posts each due request to int_req, where the normal
+// priority logic decides which level is entered.
+//
+// When several requests come due at the same boundary they fire in order
+// of their due time (cycle requests before instruction count requests) and
+// then in the order they were scheduled, so a run is reproducible.  Every
+// firing is kept in a history for checking afterwards.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum When {
+    Cycle(u64),
+    Instruction(u64),
+}
+
+impl When {
+    fn reached(&self, cycles: u64, instructions: u64) -> bool {
+        match self {
+            When::Cycle(cycle) => cycles >= *cycle,
+            When::Instruction(count) => instructions >= *count,
+        }
+    }
+
+    fn later(&self, period: u64) -> When {
+        match self {
+            When::Cycle(cycle) => When::Cycle(cycle + period),
+            When::Instruction(count) => When::Instruction(count + period),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScheduledInterrupt {
+    pub id: u32,
+    pub level: u8,
+    pub when: When,
+    pub every: Option<u64>,                     // period, in the unit of when
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Firing {
+    pub id: u32,
+    pub level: u8,
+    pub cycle: u64,                             // when it was posted
+    pub instruction: u64,
+}
+
+#[derive(Debug, Default)]
+pub struct InterruptScheduler {
+    pending: Vec<ScheduledInterrupt>,
+    next_id: u32,
+    pub history: Vec<Firing>,
+}
+
+impl InterruptScheduler {
+    pub fn new() -> Self {
+        InterruptScheduler { pending: Vec::new(), next_id: 1, history: Vec::new() }
+    }
+
+    // schedule a request for level, returning an id for cancel()
+    pub fn schedule(&mut self, level: u8, when: When, every: Option<u64>) -> u32 {
+        let id = self.next_id.max(1);
+        self.next_id = id + 1;
+        self.pending.push(ScheduledInterrupt { id, level: level & 0x0F, when, every: every.filter(|period| *period > 0) });
+        id
+    }
+
+    pub fn at_cycle(&mut self, level: u8, cycle: u64) -> u32 {
+        self.schedule(level, When::Cycle(cycle), None)
+    }
+
+    pub fn at_instruction(&mut self, level: u8, count: u64) -> u32 {
+        self.schedule(level, When::Instruction(count), None)
+    }
+
+    pub fn every_cycles(&mut self, level: u8, first: u64, period: u64) -> u32 {
+        self.schedule(level, When::Cycle(first), Some(period))
+    }
+
+    pub fn every_instructions(&mut self, level: u8, first: u64, period: u64) -> u32 {
+        self.schedule(level, When::Instruction(first), Some(period))
+    }
+
+    pub fn cancel(&mut self, id: u32) -> bool {
+        let before = self.pending.len();
+        self.pending.retain(|event| event.id != id);
+        self.pending.len() != before
+    }
+
+    pub fn clear(&mut self) {
+        self.pending.clear();
+    }
+
+    // nothing left to fire
+    pub fn is_idle(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    pub fn pending(&self) -> &[ScheduledInterrupt] {
+        &self.pending
+    }
+
+    // post everything due at this boundary, returning the int_req bits to set
+    pub fn due(&mut self, cycles: u64, instructions: u64) -> u16 {
+        if !self.pending.iter().any(|event| event.when.reached(cycles, instructions)) {
+            return 0;
+        }
+        let mut fired: Vec<ScheduledInterrupt> = Vec::new();
+        let mut index = 0;
+        while index < self.pending.len() {
+            let event = self.pending[index];
+            if !event.when.reached(cycles, instructions) {
+                index += 1;
+                continue;
+            }
+            fired.push(event);
+            match event.every {
+                Some(period) => {
+                    let mut next = event.when.later(period);
+                    while next.reached(cycles, instructions) {
+                        next = next.later(period);      // a late boundary fires a periodic event once
+                    }
+                    self.pending[index].when = next;
+                    index += 1;
+                },
+                None => { self.pending.remove(index); },
+            }
+        }
+        fired.sort_by_key(|event| (event.when, event.id));
+        let mut requests = 0;
+        for event in fired {
+            requests |= 0x0001 << event.level;
+            self.history.push(Firing { id: event.id, level: event.level, cycle: cycles, instruction: instructions });
+        }
+        requests
+    }
+}
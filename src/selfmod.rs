@@ -0,0 +1,99 @@
+// Self-modifying code
+//
+// 703 programs often store into their own instructions: an address planted
+// in a JMP for a return, a count in a shift, a switch turned into a jump.
+// With a code watch on Memory every word fetched as an instruction is
+// marked, and each later program store into a marked word is recorded and
+// reported with the location, the PCR of the storing instruction and the
+// instruction before and after:
+//
+//      Store at PCR 0105 modifies 0110: JMP   0x0120 -> JMP   0x0134
+//
+// The summary for the run lists each modified location with the number of
+// stores into it and the instructions that made them.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::disasm;
+use crate::symbols::SymbolTable;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CodeStore {
+    pub address: u16,                           // instruction stored into
+    pub pcr: u16,                               // instruction that stored
+    pub old: u16,
+    pub new: u16,
+}
+
+impl fmt::Display for CodeStore {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Store at PCR {:04X} modifies {:04X}: {} -> {}", self.pcr, self.address,
+               disasm::disassemble_at(self.address, self.old, None),
+               disasm::disassemble_at(self.address, self.new, None))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CodeWatch {
+    pub echo: bool,                             // print each store as it happens
+    pub stores: Vec<CodeStore>,
+    fetched: Vec<bool>,
+}
+
+impl CodeWatch {
+    pub fn new() -> Self {
+        CodeWatch { echo: true, stores: Vec::new(), fetched: vec![false; 32_768] }
+    }
+
+    pub fn fetched(&mut self, address: usize) {
+        self.fetched[address & 0x7FFF] = true;
+    }
+
+    pub fn is_code(&self, address: usize) -> bool {
+        self.fetched[address & 0x7FFF]
+    }
+
+    // a program store of new over old at address
+    pub fn store(&mut self, address: usize, old: i16, new: i16, pcr: u16) {
+        if !self.is_code(address) {
+            return;
+        }
+        let store = CodeStore { address: (address & 0x7FFF) as u16, pcr, old: old as u16, new: new as u16 };
+        if self.echo {
+            println!("{}", store);
+        }
+        self.stores.push(store);
+    }
+
+    // a line for the run and one for each modified location, named from symbols
+    pub fn summary(&self, symbols: Option<&SymbolTable>) -> String {
+        let mut locations: BTreeMap<u16, Vec<u16>> = BTreeMap::new();
+        for store in &self.stores {
+            let writers = locations.entry(store.address).or_default();
+            if !writers.contains(&store.pcr) {
+                writers.push(store.pcr);
+            }
+        }
+        let plural = |count: usize| if count == 1 { "" } else { "s" };
+        let mut out = format!("{} store{} into instructions at {} location{}\n", self.stores.len(),
+                              plural(self.stores.len()), locations.len(), plural(locations.len()));
+        for (address, writers) in &locations {
+            let count = self.stores.iter().filter(|store| store.address == *address).count();
+            let name = |address: u16| match symbols.and_then(|symbols| symbols.containing(address)) {
+                Some((name, 0)) => format!("{:04X} {}", address, name),
+                Some((name, offset)) => format!("{:04X} {}+{}", address, name, offset),
+                None => format!("{:04X}", address),
+            };
+            let writers: Vec<String> = writers.iter().map(|pcr| name(*pcr)).collect();
+            out += &format!("    {:<16} {:>6} store{} by {}\n", name(*address), count, plural(count), writers.join(", "));
+        }
+        out
+    }
+}
+
+impl Default for CodeWatch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
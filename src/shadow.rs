@@ -0,0 +1,64 @@
+// Shadow map of written core
+//
+// Old programs sometimes work only because of what the last program left in
+// core.  With a shadow map on Memory every word written by the loader, a
+// console deposit, a DMA transfer, an interrupt entry or a program store is
+// marked, and the first read of an unmarked word as an operand or as an
+// instruction is reported with the address and the PCR of the instruction
+// that read it.  A word is reported once; after that it counts as known.
+
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UninitializedRead {
+    pub address: u16,                           // core address read
+    pub pcr: u16,                               // instruction that read it
+    pub fetch: bool,                            // read as an instruction
+}
+
+impl fmt::Display for UninitializedRead {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let what = if self.fetch { "Fetch from" } else { "Read of" };
+        write!(f, "{} uninitialized core {:04X} at PCR {:04X}", what, self.address, self.pcr)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ShadowMap {
+    pub echo: bool,                             // print each read as it happens
+    pub reads: Vec<UninitializedRead>,
+    known: Vec<bool>,                           // written, or already reported
+}
+
+impl ShadowMap {
+    pub fn new() -> Self {
+        ShadowMap { echo: true, reads: Vec::new(), known: vec![false; 32_768] }
+    }
+
+    pub fn written(&mut self, address: usize) {
+        self.known[address & 0x7FFF] = true;
+    }
+
+    pub fn is_written(&self, address: usize) -> bool {
+        self.known[address & 0x7FFF]
+    }
+
+    pub fn read(&mut self, address: usize, pcr: u16, fetch: bool) {
+        let address = address & 0x7FFF;
+        if self.known[address] {
+            return;
+        }
+        self.known[address] = true;
+        let read = UninitializedRead { address: address as u16, pcr, fetch };
+        if self.echo {
+            println!("{}", read);
+        }
+        self.reads.push(read);
+    }
+}
+
+impl Default for ShadowMap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,123 @@
+// Symbol table, names for core addresses
+//
+// A symbol file has one symbol per line, a name and a hexadecimal address
+// separated by blanks:
+//
+//      START   0100
+//      LOOP    0104            ; comments run from a semicolon to end of line
+//
+// The address may carry a 0x prefix.  Blank lines are skipped.  An address
+// that is not itself a symbol is described by the nearest symbol below it,
+// so code is attributed to the routine it is in.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+#[derive(Debug)]
+pub enum SymbolError {
+    Io(io::Error),
+    Syntax { line: usize, text: String },
+    BadAddress { line: usize, text: String },
+}
+
+impl fmt::Display for SymbolError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SymbolError::Io(err) => write!(f, "symbol file i/o error: {}", err),
+            SymbolError::Syntax { line, text } =>
+                write!(f, "line {}: expected a name and an address, found '{}'", line, text),
+            SymbolError::BadAddress { line, text } =>
+                write!(f, "line {}: '{}' is not a core address", line, text),
+        }
+    }
+}
+
+impl std::error::Error for SymbolError {}
+
+impl From<io::Error> for SymbolError {
+    fn from(err: io::Error) -> Self {
+        SymbolError::Io(err)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SymbolTable {
+    by_address: BTreeMap<u16, String>,          // first name defined at each address
+    by_name: HashMap<String, u16>,
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        SymbolTable { by_address: BTreeMap::new(), by_name: HashMap::new() }
+    }
+
+    pub fn parse(text: &str) -> Result<SymbolTable, SymbolError> {
+        let mut table = SymbolTable::new();
+        for (index, line) in text.lines().enumerate() {
+            let line_number = index + 1;
+            let line = line.split(';').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() != 2 {
+                return Err(SymbolError::Syntax { line: line_number, text: line.to_string() });
+            }
+            let digits = fields[1].trim_start_matches("0x").trim_start_matches("0X");
+            match u16::from_str_radix(digits, 16) {
+                Ok(address) if address < 0x8000 => table.insert(fields[0], address),
+                _ => return Err(SymbolError::BadAddress { line: line_number, text: fields[1].to_string() }),
+            }
+        }
+        Ok(table)
+    }
+
+    pub fn load_file<P: AsRef<Path>>(path: P) -> Result<SymbolTable, SymbolError> {
+        SymbolTable::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn insert(&mut self, name: &str, address: u16) {
+        self.by_name.insert(name.to_string(), address);
+        self.by_address.entry(address).or_insert_with(|| name.to_string());
+    }
+
+    pub fn len(&self) -> usize {
+        self.by_name.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_name.is_empty()
+    }
+
+    pub fn lookup(&self, name: &str) -> Option<u16> {
+        self.by_name.get(name).copied()
+    }
+
+    // the symbol defined exactly at address
+    pub fn name_at(&self, address: u16) -> Option<&str> {
+        self.by_address.get(&address).map(|name| name.as_str())
+    }
+
+    // the nearest symbol at or below address and the offset from it
+    pub fn containing(&self, address: u16) -> Option<(&str, u16)> {
+        self.by_address.range(..=address).next_back()
+            .map(|(base, name)| (name.as_str(), address - base))
+    }
+
+    // "NAME", "NAME+n" or the address in hex when no symbol is below it
+    pub fn describe(&self, address: u16) -> String {
+        match self.containing(address) {
+            Some((name, 0)) => name.to_string(),
+            Some((name, offset)) => format!("{}+{:X}", name, offset),
+            None => format!("{:04X}", address),
+        }
+    }
+
+    // symbols in address order
+    pub fn iter(&self) -> impl Iterator<Item = (u16, &str)> {
+        self.by_address.iter().map(|(address, name)| (*address, name.as_str()))
+    }
+}
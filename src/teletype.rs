@@ -0,0 +1,181 @@
+// Console teletype, an ASR-33 on the DIN/DOT bus
+//
+// Registers
+//      DIN +0  keyboard data     character in the low byte, reading it frees the keyboard
+//      DIN +1  keyboard status   READY when a character is waiting
+//      DIN +2  printer status    READY when the printer can take another character
+//      DOT +0  printer data      low byte is printed
+//      DOT +1  control           KEY_INT_ENABLE, PRINT_INT_ENABLE
+//
+// Status words keep READY in bit 15 so a program can test it with SAM.
+//
+// Nobody sits at the keyboard.  The host types into it, and characters come
+// in at the line rate once the program has read the one before.  Everything
+// printed is kept, parity stripped, for the host to read or save.
+
+use std::any::Any;
+use std::collections::VecDeque;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::cpu::Memory;
+use crate::io::Device;
+
+pub const TELETYPE_ADDRESS:u8 = 0x01;           // standard bus address
+
+pub const READY:i16 = 0x8000u16 as i16;         // status bit
+
+pub const KEY_INT_ENABLE:i16 = 0x0001;          // control bits
+pub const PRINT_INT_ENABLE:i16 = 0x0002;
+
+pub const CHARACTER_CYCLES:u64 = 57_143;        // 10 characters/second at 1.75 us per cycle
+
+pub struct Teletype {
+    typed: VecDeque<u8>,                        // typed ahead, not yet sent
+    key: u8,
+    key_ready: bool,
+    next_key_at: u64,                           // cycle when the next character can arrive
+    key_int_enable: bool,
+    printed: Vec<u8>,
+    print_busy_until: u64,
+    print_int_enable: bool,
+    print_int_pending: bool,
+    pub echo: bool,                             // copy printing to standard output
+    pub character_cycles: u64,
+}
+
+impl Teletype {
+    pub fn new() -> Self {
+        Teletype {
+            typed: VecDeque::new(),
+            key: 0,
+            key_ready: false,
+            next_key_at: 0,
+            key_int_enable: false,
+            printed: Vec::new(),
+            print_busy_until: 0,
+            print_int_enable: false,
+            print_int_pending: false,
+            echo: false,
+            character_cycles: CHARACTER_CYCLES,
+        }
+    }
+
+    pub fn type_bytes(&mut self, bytes: &[u8]) {
+        self.typed.extend(bytes);
+    }
+
+    // newlines are sent as carriage returns, as the keyboard has no line feed key
+    pub fn type_text(&mut self, text: &str) {
+        self.typed.extend(text.bytes().map(|byte| if byte == b'\n' { b'\r' } else { byte }));
+    }
+
+    pub fn type_file<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        self.type_text(&String::from_utf8_lossy(&fs::read(path)?));
+        Ok(())
+    }
+
+    // characters typed that the program has not read yet
+    pub fn typed_ahead(&self) -> usize {
+        self.typed.len() + self.key_ready as usize
+    }
+
+    pub fn printed(&self) -> &[u8] {
+        &self.printed
+    }
+
+    pub fn printed_text(&self) -> String {
+        String::from_utf8_lossy(&self.printed).into_owned()
+    }
+
+    pub fn take_printed(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.printed)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, &self.printed)
+    }
+}
+
+impl Default for Teletype {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Device for Teletype {
+    fn name(&self) -> &str {
+        "teletype"
+    }
+
+    fn registers(&self) -> u8 {
+        3
+    }
+
+    fn din(&mut self, reg: u8, now: u64) -> i16 {
+        match reg {
+            0 => {
+                if self.key_ready {
+                    self.key_ready = false;
+                    self.next_key_at = now + self.character_cycles;
+                }
+                self.key as i16
+            },
+            1 => if self.key_ready { READY } else { 0 },
+            _ => if now >= self.print_busy_until { READY } else { 0 },
+        }
+    }
+
+    fn dot(&mut self, reg: u8, value: i16, now: u64) {
+        match reg {
+            0 => {
+                let character = (value & 0x007F) as u8;
+                self.printed.push(character);
+                if self.echo {
+                    let mut out = io::stdout();
+                    let _ = out.write_all(&[character]).and_then(|_| out.flush());
+                }
+                self.print_busy_until = now + self.character_cycles;
+                self.print_int_pending = true;
+            },
+            _ => {
+                self.key_int_enable = value & KEY_INT_ENABLE != 0;
+                self.print_int_enable = value & PRINT_INT_ENABLE != 0;
+            },
+        }
+    }
+
+    fn service(&mut self, now: u64, _memory: &mut Memory) -> bool {
+        let mut request = false;
+        if !self.key_ready && now >= self.next_key_at {
+            if let Some(key) = self.typed.pop_front() {
+                self.key = key;
+                self.key_ready = true;
+                request |= self.key_int_enable;
+            }
+        }
+        if self.print_int_pending && now >= self.print_busy_until {
+            self.print_int_pending = false;
+            request |= self.print_int_enable;
+        }
+        request
+    }
+
+    fn busy(&self) -> bool {
+        self.print_int_pending
+    }
+
+    fn reset(&mut self) {
+        self.key_ready = false;
+        self.next_key_at = 0;
+        self.key_int_enable = false;
+        self.print_busy_until = 0;
+        self.print_int_enable = false;
+        self.print_int_pending = false;
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
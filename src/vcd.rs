@@ -0,0 +1,193 @@
+// Value Change Dump of interrupt and register activity
+//
+// The writer samples the CPU at every instruction boundary and writes the
+// signals that changed, for viewing in GTKWave or any other VCD viewer.
+// Time is in nanoseconds, a memory cycle being 1750 ns, since VCD only
+// allows timescales of 1, 10 or 100 of a unit.
+//
+// Signals
+//      interrupts.req_N, act_N, enb_N     one wire for each bit of int_req, int_act, int_enb
+//      interrupts.masked                  the mask flip/flop
+//      registers.pcr, registers.acr       16 bit vectors
+//      devices.busy_AA_name               busy line of the device at bus address AA
+//
+// Busy lines are declared in bus address order with identifier codes that
+// follow from the address, not from the order devices were attached, so
+// dumps of the same machine compare equal.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::cpu::CYCLE_NS;
+use crate::io::DeviceInfo;
+
+// the state the writer samples
+pub struct VcdSample<'a> {
+    pub cycle: u64,
+    pub int_req: u16,
+    pub int_act: u16,
+    pub int_enb: u16,
+    pub int_masked: bool,
+    pub pcr: u16,
+    pub acr: i16,
+    pub busy: &'a [(u8, bool)],                 // busy line by bus address
+}
+
+pub struct VcdWriter {
+    out: Box<dyn Write>,
+    devices: Vec<u8>,                           // bus addresses of the busy lines declared
+    last: Option<Vec<u64>>,                     // value of every signal at the last sample
+    last_time: Option<u64>,
+    pub error: Option<io::Error>,               // first write error, after which output stops
+}
+
+// identifier code for signal n, printable ASCII from '!'
+fn code(mut n: usize) -> String {
+    let mut code = String::new();
+    loop {
+        code.push((b'!' + (n % 94) as u8) as char);
+        n /= 94;
+        if n == 0 {
+            return code;
+        }
+        n -= 1;
+    }
+}
+
+fn wire_name(device: &DeviceInfo) -> String {
+    let name: String = device.name.chars()
+        .map(|ch| if ch.is_ascii_alphanumeric() { ch } else { '_' })
+        .collect();
+    format!("busy_{:02X}_{}", device.base, name)
+}
+
+const SIGNALS_PER_REGISTER:usize = 16;
+const MASKED:usize = 3 * SIGNALS_PER_REGISTER;  // signal numbers after the interrupt bits
+const PCR:usize = MASKED + 1;
+const ACR:usize = MASKED + 2;
+const FIRST_DEVICE:usize = MASKED + 3;
+
+// identifier code of the busy line of the device at bus address base
+fn device_code(base: u8) -> String {
+    code(FIRST_DEVICE + base as usize)
+}
+
+impl VcdWriter {
+    // start a dump, declaring a busy line for each attached device
+    pub fn new(out: Box<dyn Write>, devices: &[DeviceInfo]) -> io::Result<Self> {
+        let mut devices = devices.to_vec();
+        devices.sort_by_key(|device| device.base);
+        let bases = devices.iter().map(|device| device.base).collect();
+        let mut writer = VcdWriter { out, devices: bases, last: None, last_time: None, error: None };
+        writer.header(&devices)?;
+        Ok(writer)
+    }
+
+    pub fn create<P: AsRef<Path>>(path: P, devices: &[DeviceInfo]) -> io::Result<Self> {
+        let file = File::create(path)?;
+        VcdWriter::new(Box::new(BufWriter::new(file)), devices)
+    }
+
+    fn header(&mut self, devices: &[DeviceInfo]) -> io::Result<()> {
+        let out = &mut self.out;
+        writeln!(out, "$version rustheon Raytheon 703 emulator $end")?;
+        writeln!(out, "$comment one memory cycle is {} ns $end", CYCLE_NS)?;
+        writeln!(out, "$timescale 1 ns $end")?;
+        writeln!(out, "$scope module rustheon $end")?;
+        writeln!(out, "$scope module interrupts $end")?;
+        for (register, name) in ["req", "act", "enb"].iter().enumerate() {
+            for bit in (0..SIGNALS_PER_REGISTER).rev() {
+                writeln!(out, "$var wire 1 {} {}_{} $end", code(register * SIGNALS_PER_REGISTER + bit), name, bit)?;
+            }
+        }
+        writeln!(out, "$var wire 1 {} masked $end", code(MASKED))?;
+        writeln!(out, "$upscope $end")?;
+        writeln!(out, "$scope module registers $end")?;
+        writeln!(out, "$var wire 16 {} pcr [15:0] $end", code(PCR))?;
+        writeln!(out, "$var wire 16 {} acr [15:0] $end", code(ACR))?;
+        writeln!(out, "$upscope $end")?;
+        writeln!(out, "$scope module devices $end")?;
+        for device in devices {
+            writeln!(out, "$var wire 1 {} {} $end", device_code(device.base), wire_name(device))?;
+        }
+        writeln!(out, "$upscope $end")?;
+        writeln!(out, "$upscope $end")?;
+        writeln!(out, "$enddefinitions $end")
+    }
+
+    fn values(&self, sample: &VcdSample) -> Vec<u64> {
+        let mut values = Vec::with_capacity(FIRST_DEVICE + self.devices.len());
+        for register in [sample.int_req, sample.int_act, sample.int_enb] {
+            for bit in 0..SIGNALS_PER_REGISTER {
+                values.push((register >> bit) as u64 & 1);
+            }
+        }
+        values.push(sample.int_masked as u64);
+        values.push(sample.pcr as u64);
+        values.push(sample.acr as u16 as u64);
+        for base in &self.devices {
+            let busy = sample.busy.iter().any(|(at, busy)| at == base && *busy);
+            values.push(busy as u64);
+        }
+        values
+    }
+
+    fn write_value(&mut self, signal: usize, value: u64) -> io::Result<()> {
+        let code = match signal.checked_sub(FIRST_DEVICE) {
+            Some(device) => device_code(self.devices[device]),
+            None => code(signal),
+        };
+        if signal == PCR || signal == ACR {
+            writeln!(self.out, "b{:016b} {}", value, code)
+        } else {
+            writeln!(self.out, "{}{}", value, code)
+        }
+    }
+
+    fn write_sample(&mut self, sample: &VcdSample) -> io::Result<()> {
+        let values = self.values(sample);
+        let changed: Vec<usize> = match &self.last {
+            Some(last) => (0..values.len()).filter(|i| values[*i] != last[*i]).collect(),
+            None => (0..values.len()).collect(),
+        };
+        if changed.is_empty() {
+            return Ok(());
+        }
+        if self.last_time != Some(sample.cycle) {
+            writeln!(self.out, "#{}", sample.cycle * CYCLE_NS)?;
+            self.last_time = Some(sample.cycle);
+        }
+        if self.last.is_none() {
+            writeln!(self.out, "$dumpvars")?;
+        }
+        for signal in &changed {
+            self.write_value(*signal, values[*signal])?;
+        }
+        if self.last.is_none() {
+            writeln!(self.out, "$end")?;
+        }
+        self.last = Some(values);
+        Ok(())
+    }
+
+    // record the signals that changed since the last sample
+    pub fn sample(&mut self, sample: &VcdSample) {
+        if self.error.is_some() {
+            return;
+        }
+        if let Err(err) = self.write_sample(sample) {
+            self.error = Some(err);
+        }
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+impl Drop for VcdWriter {
+    fn drop(&mut self) {
+        let _ = self.out.flush();
+    }
+}
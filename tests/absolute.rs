@@ -0,0 +1,92 @@
+// Absolute tape format: parsing, checks and a punch and load round trip
+
+use rustheon::absolute::{checksum, AbsError, AbsoluteTape, Block, LEADER_FRAMES, MAX_BLOCK_WORDS, SOB_FRAME};
+use rustheon::cpu::Memory;
+
+// one block of two words at 0x0100 and a transfer record to 0x0100, by hand
+fn hand_punched() -> Vec<u8> {
+    let sum = checksum(2, 0x0100, &[0x1234, -1]);
+    let mut frames = vec![0, 0, SOB_FRAME, 0x00, 0x02, 0x01, 0x00, 0x12, 0x34, 0xFF, 0xFF];
+    frames.extend_from_slice(&sum.to_be_bytes());
+    frames.extend_from_slice(&[0, SOB_FRAME, 0x00, 0x00, 0x01, 0x00]);
+    frames.extend_from_slice(&checksum(0, 0x0100, &[]).to_be_bytes());
+    frames.extend_from_slice(&[0, 0, 0]);
+    frames
+}
+
+#[test]
+fn parses_blocks_and_transfer() {
+    let tape = AbsoluteTape::parse(&hand_punched()).unwrap();
+    assert_eq!(tape.blocks, vec![Block { address: 0x0100, words: vec![0x1234, -1] }]);
+    assert_eq!(tape.transfer, Some(0x0100));
+}
+
+#[test]
+fn blank_tape_is_empty() {
+    assert_eq!(AbsoluteTape::parse(&[0; 10]).unwrap(), AbsoluteTape::default());
+}
+
+#[test]
+fn rejects_bad_checksum() {
+    let mut frames = hand_punched();
+    frames[8] ^= 0x01;                                  // a data bit
+    assert!(matches!(AbsoluteTape::parse(&frames),
+                     Err(AbsError::Checksum { block: 0, address: 0x0100, .. })));
+}
+
+#[test]
+fn rejects_short_tape() {
+    let frames = hand_punched();
+    assert!(matches!(AbsoluteTape::parse(&frames[..9]), Err(AbsError::UnexpectedEnd { block: 0, .. })));
+}
+
+#[test]
+fn rejects_frame_that_is_not_start_of_block() {
+    assert!(matches!(AbsoluteTape::parse(&[0, 0x42]),
+                     Err(AbsError::BadFrame { block: 0, offset: 1, frame: 0x42 })));
+}
+
+#[test]
+fn rejects_long_block_and_block_past_core() {
+    let frames = [SOB_FRAME, 0x00, (MAX_BLOCK_WORDS + 1) as u8, 0x01, 0x00];
+    assert!(matches!(AbsoluteTape::parse(&frames), Err(AbsError::BadWordCount { .. })));
+    let frames = [SOB_FRAME, 0x00, 0x02, 0x7F, 0xFF];
+    assert!(matches!(AbsoluteTape::parse(&frames), Err(AbsError::AddressRange { address: 0x7FFF, count: 2, .. })));
+}
+
+#[test]
+fn rejects_data_after_transfer() {
+    let mut frames = hand_punched();
+    let data = frames[2..13].to_vec();                 // the data block again, after the transfer
+    frames.extend_from_slice(&data);
+    assert!(matches!(AbsoluteTape::parse(&frames), Err(AbsError::DataAfterTransfer { .. })));
+}
+
+#[test]
+fn punch_and_load_round_trip() {
+    let mut memory = Memory::new();
+    for (i, word) in memory.core[0x0200..0x0300].iter_mut().enumerate() {
+        *word = (i as i16).wrapping_mul(0x0101);
+    }
+    let tape = AbsoluteTape::from_memory(&memory, 0x0200, 0x02FF, Some(0x0200));
+    let frames = tape.punch();
+    assert!(frames[..LEADER_FRAMES].iter().all(|frame| *frame == 0));
+    let parsed = AbsoluteTape::parse(&frames).unwrap();
+    assert_eq!(parsed.blocks.len(), 0x100 / MAX_BLOCK_WORDS);   // split into full blocks
+
+    let mut loaded = Memory::new();
+    assert_eq!(parsed.load(&mut loaded), Some(0x0200));
+    assert_eq!(loaded.core[0x0200..0x0300], memory.core[0x0200..0x0300]);
+    assert_eq!(loaded.core[0x01FF], 0);
+    assert_eq!(loaded.core[0x0300], 0);
+}
+
+#[test]
+fn empty_range_punches_no_blocks() {
+    let memory = Memory::new();
+    let tape = AbsoluteTape::from_memory(&memory, 0x0300, 0x0200, None);
+    assert!(tape.blocks.is_empty());
+    assert_eq!(AbsoluteTape::parse(&tape.punch()).unwrap(), tape);
+    let tape = AbsoluteTape::from_memory(&memory, 0x7FFE, 0xFFFF, None);
+    assert_eq!(tape.blocks[0].words.len(), 2);
+}
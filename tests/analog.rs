@@ -0,0 +1,124 @@
+// Analog subsystem: CSV headings, comments and bad rows, linear
+// interpolation between samples, value columns mapped onto channels, the A/D
+// converter's scaling at and beyond full scale, and the D/A recording saved
+// as CSV
+
+use rustheon::analog::{parse_csv, AnalogInput, AnalogOutput, CsvError, TimeSeries, CONVERSION_CYCLES,
+                       OVERRANGE};
+use rustheon::cpu::{seconds_to_cycles, Memory};
+use rustheon::io::Device;
+
+// convert a channel, finishing at the given emulated second
+fn convert(adc: &mut AnalogInput, channel: u8, seconds: f64) -> i16 {
+    let done = seconds_to_cycles(seconds).max(CONVERSION_CYCLES);
+    adc.dot(0, channel as i16, done - CONVERSION_CYCLES);
+    adc.service(done, &mut Memory::new());
+    adc.din(0, done)
+}
+
+#[test]
+fn csv_headings_comments_and_blank_lines_are_skipped() {
+    let series = parse_csv("time,level,flow\n# commissioning run\n\n0, 1.0, -2\n2.0,3.0,-4\n").unwrap();
+    assert_eq!(series.len(), 2);
+    assert_eq!(series[0], TimeSeries::new(vec![(0.0, 1.0), (2.0, 3.0)]));
+    assert_eq!(series[1], TimeSeries::new(vec![(0.0, -2.0), (2.0, -4.0)]));
+
+    let series = parse_csv("0,5\n1,6\n").unwrap();            // no headings
+    assert_eq!(series, vec![TimeSeries::new(vec![(0.0, 5.0), (1.0, 6.0)])]);
+}
+
+#[test]
+fn csv_bad_rows_are_reported_by_line() {
+    match parse_csv("t,a,b\n0,1,2\n1,2\n") {
+        Err(CsvError::ShortRow { line: 3, columns: 2, expected: 3 }) => {},
+        other => panic!("{:?}", other),
+    }
+    match parse_csv("t,a\n0,1\n\n1,high\n") {
+        Err(CsvError::BadNumber { line: 4, column: 2, text }) => assert_eq!(text, "high"),
+        other => panic!("{:?}", other),
+    }
+    match parse_csv("0,1\nnext,2\n") {                         // only the first row can be headings
+        Err(CsvError::BadNumber { line: 2, column: 1, .. }) => {},
+        other => panic!("{:?}", other),
+    }
+}
+
+#[test]
+fn series_interpolates_and_holds_the_ends() {
+    let series = TimeSeries::new(vec![(2.0, 4.0), (0.0, 0.0), (3.0, -2.0)]);   // sorted by time
+    assert_eq!(series.at(-1.0), 0.0);
+    assert_eq!(series.at(0.5), 1.0);
+    assert_eq!(series.at(2.0), 4.0);
+    assert_eq!(series.at(2.5), 1.0);
+    assert_eq!(series.at(10.0), -2.0);
+    assert_eq!(TimeSeries::default().at(1.0), 0.0);
+}
+
+#[test]
+fn csv_columns_map_onto_consecutive_channels() {
+    let path = std::env::temp_dir().join(format!("rustheon-analog-{}.csv", std::process::id()));
+    std::fs::write(&path, "seconds,a,b\n0,0,-5\n0.014,5,-5\n").unwrap();
+    let mut adc = AnalogInput::new();
+    let loaded = adc.load_csv(&path, 3);
+    let _ = std::fs::remove_file(&path);
+    assert_eq!(loaded.unwrap(), 2);
+
+    assert_eq!(convert(&mut adc, 3, 0.014), 0x4000);           // 5 V is half of full scale
+    assert_eq!(convert(&mut adc, 3, 0.007), 0x2000);           // 2.5 V half way
+    assert_eq!(convert(&mut adc, 4, 0.007), 0xC000u16 as i16);
+    assert_eq!(convert(&mut adc, 2, 0.007), 0);                 // neighbouring channels are not driven
+    assert_eq!(convert(&mut adc, 5, 0.007), 0);
+}
+
+#[test]
+fn conversion_clamps_at_full_scale() {
+    let mut adc = AnalogInput::new();
+    for (volts, value, overrange) in [(10.0, 0x7FF0, true), (9.999, 0x7FF0, false), (12.0, 0x7FF0, true),
+                                      (-10.0, 0x8000u16 as i16, false), (-15.0, 0x8000u16 as i16, true)] {
+        adc.set_value(0, volts);
+        assert_eq!(convert(&mut adc, 0, 0.0), value, "{} V", volts);
+        assert_eq!(adc.din(1, 0) & OVERRANGE != 0, overrange, "{} V", volts);
+    }
+    adc.set_value(0, f64::NAN);
+    assert_eq!(convert(&mut adc, 0, 0.0), 0);
+    assert_eq!(adc.din(1, 0) & OVERRANGE, OVERRANGE);
+}
+
+#[test]
+fn conversion_drops_the_bits_below_resolution() {
+    let mut adc = AnalogInput::new();
+    let lsb = 10.0 / 2048.0;                                    // 12 bits including sign
+    for (volts, value) in [(lsb * 0.9, 0), (lsb * 1.5, 0x0010), (-lsb * 0.1, 0xFFF0u16 as i16),
+                           (-lsb, 0xFFF0u16 as i16), (-lsb * 1.5, 0xFFE0u16 as i16)] {
+        adc.set_value(0, volts);
+        assert_eq!(convert(&mut adc, 0, 0.0), value, "{} V", volts);    // toward minus full scale
+    }
+    adc.resolution = 16;
+    adc.set_value(0, -10.0 / 32768.0 * 3.0);
+    assert_eq!(convert(&mut adc, 0, 0.0), -3);
+}
+
+#[test]
+fn recording_saves_as_csv() {
+    let mut dac = AnalogOutput::new(2);
+    dac.dot(0, 0x4000, 0);
+    dac.dot(1, 0x8000u16 as i16, 4_000);
+    dac.dot(1, -1, 8_000);
+    dac.dot(2, 0x1234, 8_000);                                  // no such channel, not recorded
+    let path = std::env::temp_dir().join(format!("rustheon-dac-{}.csv", std::process::id()));
+    let saved = dac.save_csv(&path);
+    let text = std::fs::read_to_string(&path);
+    let _ = std::fs::remove_file(&path);
+    saved.unwrap();
+    assert_eq!(text.unwrap(), "seconds,cycle,channel,value,volts\n\
+                               0.000000000,0,0,16384,5.000000\n\
+                               0.007000000,4000,1,-32768,-10.000000\n\
+                               0.014000000,8000,1,-1,-0.000305\n");
+    assert_eq!(dac.volts(1), -10.0 / 32768.0);
+
+    dac.clear_recording();
+    dac.record = false;
+    dac.dot(0, 0, 9_000);
+    assert!(dac.recording().is_empty());
+    assert_eq!(dac.value(0), 0);
+}
@@ -0,0 +1,119 @@
+// Subroutine unit tests: core locations with offsets, the return through
+// the sentinel, a subroutine that never returns, and the TAP and JUnit text
+
+use std::fs;
+use std::path::PathBuf;
+
+use rustheon::asmtest::{self, TestError, TestFile, TestResult};
+
+const PROGRAM: &str = "
+        ORG     0x0100
+TABLE   DATA    0,0,0,0
+DOUBLE  LDW     TABLE+2         ; RESULT and ACR = TABLE+2 doubled
+        ADD     TABLE+2
+        STW     RESULT
+        JMP     0,X
+SPIN    JMP     SPIN
+STOP    HLT
+        ORG     0x03FF
+RESULT  DATA    0
+";
+
+const TESTS: &str = r#"
+image = "math.s"
+
+[[test]]
+name = "doubles"
+call = "DOUBLE"
+memory = { "TABLE+2" = 21 }
+expect = { acr = 42, ixr = 0x7FFF, memory = { "0x0400-1" = 42, "TABLE" = [0, 0, 21] } }
+
+[[test]]
+name = "wrong <answer>"
+call = 0x0104
+memory = { "TABLE+2" = 1 }
+expect = { acr = 3, memory = { RESULT = 3 } }
+
+[[test]]
+name = "spins"
+call = "SPIN"
+max_instructions = 50
+
+[[test]]
+name = "stops"
+call = "STOP-0"
+"#;
+
+// the program and test file in a directory of their own
+fn write_files(name: &str, tests: &str) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!("rustheon-asmtest-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    fs::write(directory.join("math.s"), PROGRAM).unwrap();
+    fs::write(directory.join("math.toml"), tests).unwrap();
+    directory
+}
+
+fn run(name: &str, tests: &str) -> Result<Vec<TestResult>, TestError> {
+    let directory = write_files(name, tests);
+    let results = TestFile::load_file(directory.join("math.toml")).and_then(|file| file.run());
+    fs::remove_dir_all(&directory).unwrap();
+    results
+}
+
+#[test]
+fn runs_cases_through_the_sentinel() {
+    let results = run("cases", TESTS).unwrap();
+    let failures: Vec<&[String]> = results.iter().map(|result| result.failures.as_slice()).collect();
+    assert_eq!(failures, vec![
+        &[][..],
+        &["ACR = 0002, expected 0003".to_string(), "RESULT = 0002, expected 0003".to_string()][..],
+        &["did not return within 50 instructions".to_string()][..],
+        &["halted at 0109".to_string()][..],
+    ]);
+    assert_eq!(results[0].instructions, 4);
+    assert_eq!(results[2].instructions, 50);
+}
+
+#[test]
+fn reports_tap() {
+    let results = run("tap", TESTS).unwrap();
+    let tap = asmtest::tap(&results);
+    let expected = format!("TAP version 13\n1..4\n\
+                            ok 1 - doubles\n\
+                            not ok 2 - wrong <answer>\n  ---\n  message: |\n\
+                            \x20   ACR = 0002, expected 0003\n    RESULT = 0002, expected 0003\n\
+                            \x20 instructions: 4\n  cycles: {}\n  ...\n\
+                            not ok 3 - spins\n  ---\n  message: |\n\
+                            \x20   did not return within 50 instructions\n\
+                            \x20 instructions: 50\n  cycles: {}\n  ...\n\
+                            not ok 4 - stops\n  ---\n  message: |\n\
+                            \x20   halted at 0109\n\
+                            \x20 instructions: 1\n  cycles: {}\n  ...\n",
+                           results[1].cycles, results[2].cycles, results[3].cycles);
+    assert_eq!(tap, expected);
+}
+
+#[test]
+fn reports_junit() {
+    let results = run("junit", TESTS).unwrap();
+    let xml = asmtest::junit("math", &results);
+    assert!(xml.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+                             <testsuite name=\"math\" tests=\"4\" failures=\"3\">\n\
+                             \x20 <testcase name=\"doubles\" classname=\"math\" time=\""), "{}", xml);
+    assert!(xml.contains("<testcase name=\"wrong &lt;answer&gt;\""), "{}", xml);
+    assert!(xml.contains("<failure message=\"ACR = 0002, expected 0003\">ACR = 0002, expected 0003\n\
+                          RESULT = 0002, expected 0003</failure>"), "{}", xml);
+    assert!(xml.ends_with("</testcase>\n</testsuite>\n"), "{}", xml);
+}
+
+#[test]
+fn refuses_bad_locations() {
+    for location in ["TABLE+x", "NOWHERE", "0x8000", "0x0000-1", "0xZZ"] {
+        let tests = format!("image = \"math.s\"\n[[test]]\nname = \"bad\"\ncall = \"DOUBLE\"\nmemory = {{ \"{}\" = 1 }}\n",
+                            location);
+        match run("bad", &tests) {
+            Err(TestError::BadLocation { test, text }) => assert_eq!((test.as_str(), text.as_str()), ("bad", location)),
+            other => panic!("{}: expected a bad location, got {:?}", location, other),
+        }
+    }
+}
@@ -0,0 +1,101 @@
+// Assembler: symbols fixed on the first pass must not refer forward, a
+// memory reference must be in the page of the instruction, and a program
+// assembled outside page 0 runs where it was assembled
+
+use rustheon::assembler::{assemble, AsmError};
+use rustheon::cpu::{Cpu, HaltReason, Memory, Mode};
+use rustheon::instruction::{Instruction, Opcode};
+
+#[test]
+fn assembles_backward_references() {
+    let source = " ORG 0x0100\nN EQU 4\nSTART JMP L\n BSS N\nL CLR\nA EQU N+1\n DATA A\n END START\n";
+    let assembly = assemble(source).unwrap();
+    assert_eq!(assembly.words[&0x0100], Instruction::new(Opcode::JMP, 0x0105).encode());
+    assert_eq!(assembly.words[&0x0105], Instruction::new(Opcode::CLR, 0).encode());
+    assert_eq!(assembly.words[&0x0106], 5);
+    assert_eq!(assembly.transfer, Some(0x0100));
+}
+
+#[test]
+fn rejects_forward_reference_in_equ() {
+    let result = assemble("A EQU B\nB EQU 5\n DATA A\n");
+    assert!(matches!(result, Err(AsmError::UndefinedSymbol { line: 1, ref name }) if name == "B"));
+}
+
+#[test]
+fn rejects_forward_reference_in_bss() {
+    let result = assemble(" JMP L\n BSS N\nL CLR\nN EQU 4\n");
+    assert!(matches!(result, Err(AsmError::UndefinedSymbol { line: 2, ref name }) if name == "N"));
+}
+
+#[test]
+fn rejects_forward_reference_in_org() {
+    let result = assemble(" ORG START\nSTART CLR\n");
+    assert!(matches!(result, Err(AsmError::UndefinedSymbol { line: 1, ref name }) if name == "START"));
+}
+
+#[test]
+fn data_and_instructions_may_refer_forward() {
+    let assembly = assemble(" JMP L\n DATA L\nL CLR\n").unwrap();
+    assert_eq!(assembly.words[&0x0000], Instruction::new(Opcode::JMP, 0x0002).encode());
+    assert_eq!(assembly.words[&0x0001], 0x0002);
+}
+
+#[test]
+fn rejects_page_0_operand_from_another_page() {
+    let result = assemble(" ORG 0x0900\n JMP 0x0010\n");
+    assert!(matches!(result, Err(AsmError::OutOfPage { line: 2, address: 0x0010, location: 0x0900 })));
+}
+
+#[test]
+fn operand_in_the_page_of_the_instruction() {
+    let assembly = assemble(" ORG 0x0100\n JMP 0x0010\n ORG 0x0910\n JMP 0x0900\n").unwrap();
+    assert_eq!(assembly.words[&0x0100], Instruction::new(Opcode::JMP, 0x0010).encode());
+    assert_eq!(assembly.words[&0x0910], Instruction::new(Opcode::JMP, 0x0100).encode());
+}
+
+#[test]
+fn byte_reference_takes_a_byte_address() {
+    let assembly = assemble(" ORG 0x0500\n LDB 0x0A01\n").unwrap();
+    assert_eq!(assembly.words[&0x0500], Instruction::new(Opcode::LDB, 0x0201).encode());
+}
+
+#[test]
+fn rejects_byte_reference_outside_the_1k_page() {
+    let result = assemble(" ORG 0x0500\n LDB 0x0601\n");
+    assert!(matches!(result, Err(AsmError::OutOfPage { line: 2, address: 0x0601, location: 0x0500 })));
+}
+
+#[test]
+fn program_runs_in_page_1() {
+    let source = "        ORG     0x0900
+START   LDW     A
+        ADD     B
+        STW     SUM
+        JMP     NEXT
+        HLT
+NEXT    LDB     TEXT+TEXT+1     ; right byte of TEXT
+        STB     OUT+OUT         ; into the left byte of OUT
+        HLT
+A       DATA    2
+B       DATA    3
+SUM     DATA    0
+TEXT    DATA    0x4142
+OUT     DATA    0
+        END     START
+";
+    let assembly = assemble(source).unwrap();
+    let mut memory = Memory::new();
+    let mut cpu = Cpu::new();
+    cpu.set_pcr(assembly.load(&mut memory).unwrap());
+    cpu.mode = Mode::RUN;
+    cpu.run(&mut memory, 100);
+
+    let sum = assembly.symbols.lookup("SUM").unwrap() as usize;
+    let out = assembly.symbols.lookup("OUT").unwrap() as usize;
+    assert_eq!(cpu.halt_reason, Some(HaltReason::Instruction));
+    assert_eq!(cpu.pcr, assembly.symbols.lookup("NEXT").unwrap() + 3);
+    assert_eq!(memory.core[sum], 5);
+    assert_eq!(memory.core[out] as u16, 0x4200);
+    assert_eq!(memory.core[0x0100..0x0110], [0; 16]);      // nothing landed in page 0
+}
@@ -0,0 +1,116 @@
+// Batch runs: parsing expectations and captures, exit codes, the limits
+// and command line input escapes
+
+use std::path::PathBuf;
+
+use rustheon::assembler::assemble;
+use rustheon::batch::{self, Batch, BatchError, Capture, Expect, Failure, Outcome, Output, Stop, Target};
+use rustheon::cpu::{Cpu, HaltReason, Memory, Mode};
+
+fn machine(source: &str) -> (Cpu, Box<Memory>) {
+    let assembly = assemble(source).unwrap();
+    let mut cpu = Cpu::new();
+    let mut memory = Box::new(Memory::new());
+    cpu.pcr = assembly.load(&mut memory).unwrap();
+    cpu.mode = Mode::RUN;
+    (cpu, memory)
+}
+
+fn outcome(stop: Stop) -> Outcome {
+    Outcome { stop, pcr: 0, instructions: 0, cycles: 0, failures: Vec::new(), checks_passed: 0, checks_failed: 0 }
+}
+
+#[test]
+fn parses_expectations() {
+    assert_eq!(Expect::parse("acr=0x0005").unwrap(), Expect { target: Target::Acr, value: 5 });
+    assert_eq!(Expect::parse("IXR=-1").unwrap(), Expect { target: Target::Ixr, value: 0xFFFF });
+    assert_eq!(Expect::parse("pcr=261").unwrap(), Expect { target: Target::Pcr, value: 0x0105 });
+    assert_eq!(Expect::parse("status=0").unwrap(), Expect { target: Target::Status, value: 0 });
+    assert_eq!(Expect::parse("0x0018=0x1234").unwrap(), Expect { target: Target::Core(0x0018), value: 0x1234 });
+    for bad in ["acr", "acr=", "acr=0x10000", "acr=-32769", "sp=1", "0x8000=1", "24=1"] {
+        assert!(matches!(Expect::parse(bad), Err(BatchError::BadExpect(_))), "{}", bad);
+    }
+}
+
+#[test]
+fn parses_captures() {
+    assert_eq!(Capture::parse("teletype=console.txt").unwrap(),
+               Capture { output: Output::Teletype, path: PathBuf::from("console.txt") });
+    assert_eq!(Capture::parse("Punch=out.tape").unwrap().output, Output::Punch);
+    assert_eq!(Capture::parse("printer=listing.txt").unwrap().output, Output::Printer);
+    assert_eq!(Capture::parse("dac=dac.csv").unwrap().output, Output::Dac);
+    for bad in ["teletype", "teletype=", "reader=in.tape"] {
+        assert!(matches!(Capture::parse(bad), Err(BatchError::BadCapture(_))), "{}", bad);
+    }
+}
+
+#[test]
+fn exit_codes() {
+    assert_eq!(outcome(Stop::Halted(HaltReason::Instruction)).exit_code(), batch::EXIT_PASS);
+    assert_eq!(outcome(Stop::Halted(HaltReason::Exit(0))).exit_code(), batch::EXIT_PASS);
+    assert_eq!(outcome(Stop::Halted(HaltReason::Exit(2))).exit_code(), batch::EXIT_EXPECT_FAILED);
+    assert_eq!(outcome(Stop::Halted(HaltReason::Illegal)).exit_code(), batch::EXIT_ILLEGAL);
+    assert_eq!(outcome(Stop::Halted(HaltReason::Protected(0x0100))).exit_code(), batch::EXIT_PROTECTED);
+    assert_eq!(outcome(Stop::InstructionLimit).exit_code(), batch::EXIT_LIMIT);
+    assert_eq!(outcome(Stop::CycleLimit).exit_code(), batch::EXIT_LIMIT);
+
+    let mut failed = outcome(Stop::Halted(HaltReason::Instruction));
+    failed.failures.push(Failure { expect: Expect { target: Target::Acr, value: 1 }, actual: 0 });
+    assert_eq!(failed.exit_code(), batch::EXIT_EXPECT_FAILED);
+    let mut failed = outcome(Stop::Halted(HaltReason::Exit(0)));
+    failed.checks_failed = 1;
+    assert_eq!(failed.exit_code(), batch::EXIT_EXPECT_FAILED);
+    let mut failed = outcome(Stop::Halted(HaltReason::Illegal));
+    failed.checks_failed = 1;
+    assert_eq!(failed.exit_code(), batch::EXIT_ILLEGAL);
+}
+
+#[test]
+fn runs_to_halt_and_checks() {
+    let (mut cpu, mut memory) = machine(" ORG 0x0100\nSTART LDW FIVE\n HLT\nFIVE DATA 5\n END START\n");
+    let batch = Batch {
+        max_instructions: Some(1_000),
+        expects: vec![Expect::parse("acr=5").unwrap(), Expect::parse("pcr=0x0102").unwrap(),
+                      Expect::parse("ixr=7").unwrap()],
+        ..Batch::new()
+    };
+    let outcome = batch.execute(&mut cpu, &mut memory).unwrap();
+    assert_eq!(outcome.stop, Stop::Halted(HaltReason::Instruction));
+    assert_eq!(outcome.instructions, 2);
+    assert_eq!(outcome.failures, vec![Failure { expect: Expect { target: Target::Ixr, value: 7 }, actual: 0 }]);
+    assert_eq!(outcome.exit_code(), batch::EXIT_EXPECT_FAILED);
+}
+
+#[test]
+fn stops_at_the_instruction_limit() {
+    let (mut cpu, mut memory) = machine(" ORG 0x0100\nLOOP JMP LOOP\n END LOOP\n");
+    let batch = Batch { max_instructions: Some(250_001), ..Batch::new() };
+    assert_eq!(batch.run(&mut cpu, &mut memory), Stop::InstructionLimit);
+    assert_eq!(cpu.instructions, 250_001);
+    assert_eq!(batch.run(&mut cpu, &mut memory), Stop::InstructionLimit);   // counted from each start
+    assert_eq!(cpu.instructions, 500_002);
+}
+
+#[test]
+fn stops_just_past_the_cycle_limit() {
+    let (mut cpu, mut memory) = machine(" ORG 0x0100\nLOOP JMP LOOP\n END LOOP\n");
+    let batch = Batch { max_cycles: Some(300_001), ..Batch::new() };
+    assert_eq!(batch.run(&mut cpu, &mut memory), Stop::CycleLimit);
+    assert!(cpu.cycles >= 300_001 && cpu.cycles < 300_001 + 5, "{} cycles", cpu.cycles);
+}
+
+#[test]
+fn the_first_limit_reached_stops_the_run() {
+    let (mut cpu, mut memory) = machine(" ORG 0x0100\nLOOP JMP LOOP\n END LOOP\n");
+    let batch = Batch { max_instructions: Some(1_000), max_cycles: Some(1_000_000), ..Batch::new() };
+    assert_eq!(batch.run(&mut cpu, &mut memory), Stop::InstructionLimit);
+    let batch = Batch { max_instructions: Some(1_000_000), max_cycles: Some(1_000), ..Batch::new() };
+    assert_eq!(batch.run(&mut cpu, &mut memory), Stop::CycleLimit);
+}
+
+#[test]
+fn unescapes_input() {
+    assert_eq!(batch::unescape(r"RUN 5\n"), "RUN 5\n");
+    assert_eq!(batch::unescape(r"A\rB\tC\\D\x"), "A\rB\tC\\Dx");
+    assert_eq!(batch::unescape("trailing\\"), "trailing\\");
+}
@@ -0,0 +1,88 @@
+// Front panel LOAD: an absolute tape punched from core boots through the
+// paper tape reader into the same core and starts at its transfer address,
+// and the bootstrap halts where its header says on a bad tape
+
+use rustheon::absolute::{AbsoluteTape, LEADER_FRAMES, MAX_BLOCK_WORDS};
+use rustheon::assembler::assemble;
+use rustheon::boot::{self, BOOT_ADDRESS};
+use rustheon::cpu::{Cpu, HaltReason, Memory, Mode};
+use rustheon::papertape::{PaperTapeReader, READER_ADDRESS};
+
+const PROGRAM:&str = "
+ ORG 0x0200
+START LDW VALUE
+ STW RESULT
+ HLT
+VALUE DATA 0x1234
+RESULT DATA 0
+ BSS 100
+LAST DATA 0x7777
+ END START
+";
+
+// the program in core and punched from it with a transfer address
+fn program(transfer: Option<u16>) -> (Memory, Vec<u8>) {
+    let assembly = assemble(PROGRAM).unwrap();
+    let mut memory = Memory::new();
+    assembly.load(&mut memory);
+    let last = assembly.symbols.lookup("LAST").unwrap();
+    let tape = AbsoluteTape::from_memory(&memory, 0x0200, last, transfer);
+    (memory, tape.punch())
+}
+
+// mount frames on the reader, press LOAD and run until the machine stops
+fn boot(frames: Vec<u8>) -> (Cpu, Memory) {
+    let mut cpu = Cpu::new();
+    let mut memory = Memory::new();
+    let mut reader = PaperTapeReader::new();
+    reader.mount(frames);
+    cpu.io.attach(READER_ADDRESS, None, Box::new(reader)).unwrap();
+    boot::load(&mut cpu, &mut memory, READER_ADDRESS);
+    cpu.run(&mut memory, 10_000_000);
+    assert_ne!(cpu.mode, Mode::RUN, "still running at PCR {:04X}", cpu.pcr);
+    (cpu, memory)
+}
+
+#[test]
+fn boots_a_tape_and_starts_at_the_transfer() {
+    let (punched, frames) = program(Some(0x0200));
+    assert!(frames.len() > LEADER_FRAMES + 2 * MAX_BLOCK_WORDS * 2, "more than one block");
+    let (cpu, memory) = boot(frames);
+
+    let bootstrap = boot::bootstrap(READER_ADDRESS);
+    let base = BOOT_ADDRESS as usize;
+    let code = 0x0084 - base;                                   // the bootstrap's code, before its data
+    assert_eq!(&memory.core[base..base + code], &bootstrap[..code]);
+    assert_eq!(cpu.halt_reason, Some(HaltReason::Instruction));
+    assert_eq!(cpu.pcr, 0x0203, "halted after the program's HLT");
+    assert_eq!(cpu.acr, 0x1234);
+    assert_eq!(memory.core[0x0204], 0x1234, "RESULT was stored by the program");
+    let end = 0x0205 + 100;                                     // LAST
+    assert_eq!(&memory.core[0x0200..0x0204], &punched.core[0x0200..0x0204]);
+    assert_eq!(&memory.core[0x0205..=end], &punched.core[0x0205..=end]);
+    assert_eq!(memory.core[end], 0x7777);
+    assert_eq!(memory.core[end + 1], 0);
+}
+
+#[test]
+fn load_only_tape_stops_after_loading() {
+    let (punched, frames) = program(Some(0x8200));
+    let (cpu, memory) = boot(frames);
+    assert_eq!(cpu.pcr, 0x005B);
+    assert_eq!(&memory.core[0x0200..0x0300], &punched.core[0x0200..0x0300]);
+}
+
+#[test]
+fn bad_tapes_halt_the_bootstrap() {
+    let (_, frames) = program(Some(0x0200));
+    let mut corrupt = frames.clone();
+    corrupt[LEADER_FRAMES + 1 + 4 + 1] ^= 0x10;                 // a data frame of the first block
+    assert_eq!(boot(corrupt).0.pcr, 0x006C);
+
+    let (_, frames) = program(None);                            // no transfer record
+    assert_eq!(boot(frames).0.pcr, 0x0083);
+
+    let mut stray = vec![0u8; 4];
+    stray.push(0x42);                                           // not a start of block
+    assert_eq!(boot(stray).0.pcr, 0x004A);
+}
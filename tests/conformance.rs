@@ -0,0 +1,361 @@
+// Instruction set conformance
+//
+// One row per behaviour: the machine state before, one instruction placed at
+// 0x0100 and stepped, and the registers, flags and core expected after.  Every
+// opcode in the decode table has at least one row, and the skips, compares
+// and shifts have a row for each way they can go.  Fields left at BASE and
+// NONE are zero before and not checked after.
+//
+// The groups follow the Raytheon 703 programming reference: the addressing
+// section for word and byte pages, indexing and local/global mode, then its
+// memory reference, generic, register, byte operand, skip and shift
+// instruction sections.  Each group below names the section it checks.
+
+use rustheon::cpu::{Cpu, HaltReason, Memory, Mode};
+use rustheon::hostcall::HostCalls;
+use rustheon::instruction::{Instruction, Opcode, Opcode::*, OPCODES};
+
+const ORIGIN: u16 = 0x0100;
+const NEXT: u16 = ORIGIN + 1;
+const SKIP: u16 = ORIGIN + 2;
+
+const NEG: u16 = 0x0400;                                // status flags
+const EQL: u16 = 0x0200;
+const OVF: u16 = 0x0100;
+const GBL: u16 = 0x0080;
+
+struct Case {
+    name: &'static str,
+    origin: u16,                                        // where the instruction is placed
+    op: Opcode,
+    operand: u16,
+    indexed: bool,
+    acr: u16,
+    ixr: u16,
+    status: u16,
+    int_enb: u16,
+    int_act: u16,
+    switches: [bool; 4],
+    external_sense: bool,
+    host_calls: bool,
+    memory: &'static [(u16, u16)],
+    expect: Expect,
+}
+
+struct Expect {
+    acr: Option<u16>,
+    ixr: Option<u16>,
+    status: Option<u16>,
+    pcr: Option<u16>,
+    int_enb: Option<u16>,
+    int_act: Option<u16>,
+    masked: Option<bool>,
+    halt: Option<HaltReason>,                           // None: still running
+    memory: &'static [(u16, u16)],
+}
+
+const BASE: Case = Case {
+    name: "", origin: ORIGIN, op: HLT, operand: 0, indexed: false,
+    acr: 0, ixr: 0, status: 0, int_enb: 0, int_act: 0,
+    switches: [false; 4], external_sense: false, host_calls: false,
+    memory: &[], expect: NONE,
+};
+
+const NONE: Expect = Expect {
+    acr: None, ixr: None, status: None, pcr: Some(NEXT), int_enb: None, int_act: None,
+    masked: None, halt: None, memory: &[],
+};
+
+const CASES: &[Case] = &[
+    // memory reference instructions.  Per the addressing section a word
+    // reference takes its low 11 bits from the instruction and the 2K page
+    // from the EXR, which follows the page the instruction was fetched from.
+    Case { name: "jmp", op: JMP, operand: 0x0234, expect: Expect { pcr: Some(0x0234), ..NONE }, ..BASE },
+    Case { name: "jmp indexed", op: JMP, operand: 0x0010, indexed: true, ixr: 0x0200, status: GBL,
+           expect: Expect { pcr: Some(0x0210), ..NONE }, ..BASE },
+    Case { name: "jmp negative index", op: JMP, operand: 0x0210, indexed: true, ixr: 0xFFF0, status: GBL,
+           expect: Expect { pcr: Some(0x0200), ..NONE }, ..BASE },
+    Case { name: "jsx", op: JSX, operand: 0x0300,
+           expect: Expect { pcr: Some(0x0300), ixr: Some(NEXT), status: Some(GBL), ..NONE }, ..BASE },
+    Case { name: "stw", op: STW, operand: 0x0200, acr: 0x1234,
+           expect: Expect { memory: &[(0x0200, 0x1234)], ..NONE }, ..BASE },
+    Case { name: "ldw", op: LDW, operand: 0x0200, memory: &[(0x0200, 0x8001)],
+           expect: Expect { acr: Some(0x8001), ..NONE }, ..BASE },
+    Case { name: "ldw indexed local", op: LDW, operand: 0x0010, indexed: true, ixr: 1, status: 0x1000,
+           memory: &[(0x0811, 0x4242)], expect: Expect { acr: Some(0x4242), ..NONE }, ..BASE },
+    Case { name: "ldw indexed global", op: LDW, operand: 0x0010, indexed: true, ixr: 1, status: 0x1000 | GBL,
+           memory: &[(0x0011, 0x2424), (0x0811, 0x4242)], expect: Expect { acr: Some(0x2424), ..NONE }, ..BASE },
+    Case { name: "jmp page 1", origin: 0x0900, op: JMP, operand: 0x0234, status: 0x1000,
+           expect: Expect { pcr: Some(0x0A34), ..NONE }, ..BASE },
+    Case { name: "jmp page 3", origin: 0x1900, op: JMP, operand: 0x0034, status: 0x3000,
+           expect: Expect { pcr: Some(0x1834), ..NONE }, ..BASE },
+    Case { name: "ldw page 1", origin: 0x0900, op: LDW, operand: 0x0200, status: 0x1000,
+           memory: &[(0x0200, 0x1111), (0x0A00, 0x8001)],
+           expect: Expect { acr: Some(0x8001), pcr: Some(0x0901), ..NONE }, ..BASE },
+    Case { name: "stw page 1", origin: 0x0900, op: STW, operand: 0x0200, acr: 0x1234, status: 0x1000,
+           expect: Expect { memory: &[(0x0200, 0x0000), (0x0A00, 0x1234)], pcr: Some(0x0901), ..NONE }, ..BASE },
+    Case { name: "stx", op: STX, operand: 0x0200, ixr: 0x5678,
+           expect: Expect { memory: &[(0x0200, 0x5678)], ..NONE }, ..BASE },
+    Case { name: "ldx", op: LDX, operand: 0x0200, memory: &[(0x0200, 0xFFFF)],
+           expect: Expect { ixr: Some(0xFFFF), ..NONE }, ..BASE },
+    Case { name: "add", op: ADD, operand: 0x0200, acr: 2, status: OVF, memory: &[(0x0200, 3)],
+           expect: Expect { acr: Some(5), status: Some(0), ..NONE }, ..BASE },
+    Case { name: "add overflow", op: ADD, operand: 0x0200, acr: 0x7FFF, memory: &[(0x0200, 1)],
+           expect: Expect { acr: Some(0x8000), status: Some(OVF), ..NONE }, ..BASE },
+    Case { name: "sub", op: SUB, operand: 0x0200, acr: 5, status: OVF, memory: &[(0x0200, 7)],
+           expect: Expect { acr: Some(0xFFFE), status: Some(0), ..NONE }, ..BASE },
+    Case { name: "sub overflow", op: SUB, operand: 0x0200, acr: 0x8000, memory: &[(0x0200, 1)],
+           expect: Expect { acr: Some(0x7FFF), status: Some(OVF), ..NONE }, ..BASE },
+    Case { name: "ori", op: ORI, operand: 0x0200, acr: 0x00F0, memory: &[(0x0200, 0x0F0F)],
+           expect: Expect { acr: Some(0x0FFF), ..NONE }, ..BASE },
+    Case { name: "ore", op: ORE, operand: 0x0200, acr: 0x00FF, memory: &[(0x0200, 0x0F0F)],
+           expect: Expect { acr: Some(0x0FF0), ..NONE }, ..BASE },
+    Case { name: "and", op: AND, operand: 0x0200, acr: 0x00FF, memory: &[(0x0200, 0x0F0F)],
+           expect: Expect { acr: Some(0x000F), ..NONE }, ..BASE },
+    Case { name: "cmw less", op: CMW, operand: 0x0200, acr: 1, status: EQL, memory: &[(0x0200, 2)],
+           expect: Expect { status: Some(NEG), ..NONE }, ..BASE },
+    Case { name: "cmw equal", op: CMW, operand: 0x0200, acr: 5, status: NEG, memory: &[(0x0200, 5)],
+           expect: Expect { status: Some(EQL), ..NONE }, ..BASE },
+    Case { name: "cmw greater", op: CMW, operand: 0x0200, acr: 0x7000, status: NEG | EQL, memory: &[(0x0200, 0x8000)],
+           expect: Expect { status: Some(0), ..NONE }, ..BASE },
+    // a byte reference names a byte, so its page is 1K words: the operand
+    // is a byte address and the low bit picks the left or right half
+    Case { name: "stb left", op: STB, operand: 0x0400, acr: 0x1234, memory: &[(0x0200, 0xABCD)],
+           expect: Expect { memory: &[(0x0200, 0x34CD)], ..NONE }, ..BASE },
+    Case { name: "stb right", op: STB, operand: 0x0401, acr: 0x1234, memory: &[(0x0200, 0xABCD)],
+           expect: Expect { memory: &[(0x0200, 0xAB34)], ..NONE }, ..BASE },
+    Case { name: "ldb left", op: LDB, operand: 0x0400, acr: 0x1200, memory: &[(0x0200, 0xABCD)],
+           expect: Expect { acr: Some(0x12AB), ..NONE }, ..BASE },
+    Case { name: "ldb right", op: LDB, operand: 0x0401, acr: 0x1200, memory: &[(0x0200, 0xABCD)],
+           expect: Expect { acr: Some(0x12CD), ..NONE }, ..BASE },
+    Case { name: "ldb indexed local", op: LDB, operand: 0x0001, indexed: true, status: 0x0800,
+           memory: &[(0x0000, 0x0011), (0x0400, 0x00EE)], expect: Expect { acr: Some(0x00EE), ..NONE }, ..BASE },
+    Case { name: "ldb indexed global", op: LDB, operand: 0x0001, indexed: true, status: 0x0800 | GBL,
+           memory: &[(0x0000, 0x0011), (0x0400, 0x00EE)], expect: Expect { acr: Some(0x0011), ..NONE }, ..BASE },
+    Case { name: "ldb negative index", op: LDB, operand: 0x0403, indexed: true, ixr: 0xFFFE, status: GBL,
+           memory: &[(0x0200, 0xABCD)], expect: Expect { acr: Some(0x00CD), ..NONE }, ..BASE },
+    Case { name: "cmb equal", op: CMB, operand: 0x0401, acr: 0x0005, status: GBL | NEG, memory: &[(0x0200, 0x0005)],
+           expect: Expect { status: Some(GBL | EQL), ..NONE }, ..BASE },
+    Case { name: "cmb less", op: CMB, operand: 0x0400, acr: 0x0003, status: GBL | EQL, memory: &[(0x0200, 0x0500)],
+           expect: Expect { status: Some(GBL | NEG), ..NONE }, ..BASE },
+    Case { name: "cmb greater", op: CMB, operand: 0x0401, acr: 0x0007, status: EQL, memory: &[(0x0200, 0x0005)],
+           expect: Expect { status: Some(0), ..NONE }, ..BASE },
+
+    // generic instructions
+    Case { name: "hlt", op: HLT, expect: Expect { halt: Some(HaltReason::Instruction), ..NONE }, ..BASE },
+    Case { name: "inret", op: INRET, operand: 3, int_act: 0x0008,
+           memory: &[(0x000C, 0x0456), (0x000E, GBL | OVF)],
+           expect: Expect { pcr: Some(0x0456), status: Some(GBL | OVF), int_act: Some(0), ..NONE }, ..BASE },
+    Case { name: "enb", op: ENB, operand: 5, expect: Expect { int_enb: Some(0x0020), ..NONE }, ..BASE },
+    Case { name: "dsb", op: DSB, operand: 5, int_enb: 0x0021, int_act: 0x0020,
+           expect: Expect { int_enb: Some(0x0001), int_act: Some(0), ..NONE }, ..BASE },
+    Case { name: "slm", op: SLM, status: GBL | EQL, expect: Expect { status: Some(EQL), ..NONE }, ..BASE },
+    Case { name: "sgm", op: SGM, status: EQL, expect: Expect { status: Some(GBL | EQL), ..NONE }, ..BASE },
+    Case { name: "cex", op: CEX, ixr: 0x1234, status: 0x5800 | GBL,
+           expect: Expect { ixr: Some(0x5A34), ..NONE }, ..BASE },
+    Case { name: "cxe", op: CXE, ixr: 0xA800, status: GBL, expect: Expect { status: Some(0xA800 | GBL), ..NONE }, ..BASE },
+    Case { name: "sml", op: SML, operand: 5, expect: Expect { status: Some(0x2800), ..NONE }, ..BASE },
+    Case { name: "smu", op: SMU, operand: 5, expect: Expect { status: Some(0xA800), ..NONE }, ..BASE },
+    Case { name: "msk", op: MSK, expect: Expect { masked: Some(true), ..NONE }, ..BASE },
+    Case { name: "unm", op: UNM, expect: Expect { masked: Some(false), ..NONE }, ..BASE },
+
+    // register instructions
+    Case { name: "clr", op: CLR, acr: 0x1234, expect: Expect { acr: Some(0), ..NONE }, ..BASE },
+    Case { name: "cmp", op: CMP, acr: 5, status: OVF, expect: Expect { acr: Some(0xFFFB), status: Some(0), ..NONE }, ..BASE },
+    Case { name: "cmp most negative", op: CMP, acr: 0x8000,
+           expect: Expect { acr: Some(0x8000), status: Some(OVF), ..NONE }, ..BASE },
+    Case { name: "inv", op: INV, acr: 0x00FF, expect: Expect { acr: Some(0xFF00), ..NONE }, ..BASE },
+    Case { name: "cax", op: CAX, acr: 0x1234, expect: Expect { ixr: Some(0x1234), ..NONE }, ..BASE },
+    Case { name: "cxa", op: CXA, ixr: 0x5678, expect: Expect { acr: Some(0x5678), ..NONE }, ..BASE },
+
+    // byte operand instructions
+    Case { name: "din empty bus", op: DIN, operand: 0x20, acr: 0x1234, expect: Expect { acr: Some(0), ..NONE }, ..BASE },
+    Case { name: "dot empty bus", op: DOT, operand: 0x20, acr: 0x1234, expect: Expect { acr: Some(0x1234), ..NONE }, ..BASE },
+    Case { name: "ixs no skip", op: IXS, operand: 2, ixr: 0xFFFD, expect: Expect { ixr: Some(0xFFFF), ..NONE }, ..BASE },
+    Case { name: "ixs skip", op: IXS, operand: 2, ixr: 0xFFFF,
+           expect: Expect { ixr: Some(1), pcr: Some(SKIP), ..NONE }, ..BASE },
+    Case { name: "ixs wraps", op: IXS, operand: 1, ixr: 0x7FFF, expect: Expect { ixr: Some(0x8000), ..NONE }, ..BASE },
+    Case { name: "dxs no skip", op: DXS, operand: 1, ixr: 1, expect: Expect { ixr: Some(0), ..NONE }, ..BASE },
+    Case { name: "dxs skip", op: DXS, operand: 1, ixr: 0,
+           expect: Expect { ixr: Some(0xFFFF), pcr: Some(SKIP), ..NONE }, ..BASE },
+    Case { name: "dxs wraps", op: DXS, operand: 1, ixr: 0x8000, expect: Expect { ixr: Some(0x7FFF), ..NONE }, ..BASE },
+    Case { name: "llb", op: LLB, operand: 0x5A, acr: 0x12FF, expect: Expect { acr: Some(0x125A), ..NONE }, ..BASE },
+    Case { name: "clb equal", op: CLB, operand: 0x05, acr: 0x7705, status: GBL | NEG,
+           expect: Expect { status: Some(GBL | EQL), ..NONE }, ..BASE },
+    Case { name: "clb less", op: CLB, operand: 0x05, acr: 0x0003, expect: Expect { status: Some(NEG), ..NONE }, ..BASE },
+    Case { name: "clb signed", op: CLB, operand: 0x05, acr: 0x00FF, expect: Expect { status: Some(NEG), ..NONE }, ..BASE },
+    Case { name: "clb greater", op: CLB, operand: 0x05, acr: 0x0007, status: EQL,
+           expect: Expect { status: Some(0), ..NONE }, ..BASE },
+    Case { name: "trap without host calls", op: TRAP, operand: 0x04,
+           expect: Expect { halt: Some(HaltReason::Illegal), ..NONE }, ..BASE },
+    Case { name: "trap exit", op: TRAP, operand: 0x00, acr: 7, host_calls: true,
+           expect: Expect { halt: Some(HaltReason::Exit(7)), acr: Some(7), ..NONE }, ..BASE },
+    Case { name: "trap unknown function", op: TRAP, operand: 0x7F, host_calls: true,
+           expect: Expect { halt: Some(HaltReason::Illegal), ..NONE }, ..BASE },
+
+    // skip instructions
+    Case { name: "saz skip", op: SAZ, acr: 0, expect: Expect { pcr: Some(SKIP), ..NONE }, ..BASE },
+    Case { name: "saz", op: SAZ, acr: 1, ..BASE },
+    Case { name: "saz skip wraps", origin: 0x7FFE, op: SAZ, acr: 0, expect: Expect { pcr: Some(0x0000), ..NONE }, ..BASE },
+    Case { name: "saz at the last word", origin: 0x7FFF, op: SAZ, acr: 1, expect: Expect { pcr: Some(0x0000), ..NONE }, ..BASE },
+    Case { name: "sap skip", op: SAP, acr: 1, expect: Expect { pcr: Some(SKIP), ..NONE }, ..BASE },
+    Case { name: "sap", op: SAP, acr: 0x8000, ..BASE },
+    Case { name: "sam skip", op: SAM, acr: 0xFFFF, expect: Expect { pcr: Some(SKIP), ..NONE }, ..BASE },
+    Case { name: "sam", op: SAM, acr: 0, ..BASE },
+    Case { name: "sao skip", op: SAO, acr: 3, expect: Expect { pcr: Some(SKIP), ..NONE }, ..BASE },
+    Case { name: "sao", op: SAO, acr: 2, ..BASE },
+    Case { name: "sls skip", op: SLS, status: NEG, expect: Expect { pcr: Some(SKIP), ..NONE }, ..BASE },
+    Case { name: "sls", op: SLS, status: EQL, ..BASE },
+    Case { name: "sxe skip", op: SXE, ixr: 2, expect: Expect { pcr: Some(SKIP), ..NONE }, ..BASE },
+    Case { name: "sxe", op: SXE, ixr: 1, ..BASE },
+    Case { name: "seq skip", op: SEQ, status: EQL, expect: Expect { pcr: Some(SKIP), ..NONE }, ..BASE },
+    Case { name: "seq", op: SEQ, status: NEG, ..BASE },
+    Case { name: "sne skip", op: SNE, status: NEG, expect: Expect { pcr: Some(SKIP), ..NONE }, ..BASE },
+    Case { name: "sne", op: SNE, status: EQL, ..BASE },
+    Case { name: "sgr skip", op: SGR, status: 0, expect: Expect { pcr: Some(SKIP), ..NONE }, ..BASE },
+    Case { name: "sgr less", op: SGR, status: NEG, ..BASE },
+    Case { name: "sgr equal", op: SGR, status: EQL, ..BASE },
+    Case { name: "sle skip less", op: SLE, status: NEG, expect: Expect { pcr: Some(SKIP), ..NONE }, ..BASE },
+    Case { name: "sle skip equal", op: SLE, status: EQL, expect: Expect { pcr: Some(SKIP), ..NONE }, ..BASE },
+    Case { name: "sle", op: SLE, status: 0, ..BASE },
+    Case { name: "sno skip", op: SNO, status: 0, expect: Expect { pcr: Some(SKIP), ..NONE }, ..BASE },
+    Case { name: "sno", op: SNO, status: OVF, ..BASE },
+    Case { name: "sse skip", op: SSE, external_sense: true, expect: Expect { pcr: Some(SKIP), ..NONE }, ..BASE },
+    Case { name: "sse", op: SSE, ..BASE },
+    Case { name: "ss0 skip", op: SS0, switches: [true, false, false, false], expect: Expect { pcr: Some(SKIP), ..NONE }, ..BASE },
+    Case { name: "ss0", op: SS0, switches: [false, true, true, true], ..BASE },
+    Case { name: "ss1 skip", op: SS1, switches: [false, true, false, false], expect: Expect { pcr: Some(SKIP), ..NONE }, ..BASE },
+    Case { name: "ss1", op: SS1, switches: [true, false, true, true], ..BASE },
+    Case { name: "ss2 skip", op: SS2, switches: [false, false, true, false], expect: Expect { pcr: Some(SKIP), ..NONE }, ..BASE },
+    Case { name: "ss2", op: SS2, switches: [true, true, false, true], ..BASE },
+    Case { name: "ss3 skip", op: SS3, switches: [false, false, false, true], expect: Expect { pcr: Some(SKIP), ..NONE }, ..BASE },
+    Case { name: "ss3", op: SS3, switches: [true, true, true, false], ..BASE },
+
+    // shift instructions, arithmetic
+    Case { name: "sra", op: SRA, operand: 4, acr: 0x8010, expect: Expect { acr: Some(0xF801), ..NONE }, ..BASE },
+    Case { name: "sla", op: SLA, operand: 2, acr: 0x0003, status: OVF,
+           expect: Expect { acr: Some(0x000C), status: Some(0), ..NONE }, ..BASE },
+    Case { name: "sla overflow", op: SLA, operand: 1, acr: 0x4000,
+           expect: Expect { acr: Some(0x8000), status: Some(OVF), ..NONE }, ..BASE },
+    Case { name: "srad", op: SRAD, operand: 4, acr: 0x8001, ixr: 0x0002,
+           expect: Expect { acr: Some(0xF800), ixr: Some(0x1000), ..NONE }, ..BASE },
+    Case { name: "slad", op: SLAD, operand: 4, acr: 0x0001, ixr: 0x8000,
+           expect: Expect { acr: Some(0x0018), ixr: Some(0x0000), status: Some(0), ..NONE }, ..BASE },
+
+    // shift instructions, logical and circular
+    Case { name: "srl", op: SRL, operand: 4, acr: 0x8010, expect: Expect { acr: Some(0x0801), ..NONE }, ..BASE },
+    Case { name: "sll", op: SLL, operand: 4, acr: 0x8421, expect: Expect { acr: Some(0x4210), ..NONE }, ..BASE },
+    Case { name: "srld", op: SRLD, operand: 4, acr: 0x8001, ixr: 0x0002,
+           expect: Expect { acr: Some(0x0800), ixr: Some(0x1000), ..NONE }, ..BASE },
+    Case { name: "slld", op: SLLD, operand: 4, acr: 0x1234, ixr: 0x5678,
+           expect: Expect { acr: Some(0x2345), ixr: Some(0x6780), ..NONE }, ..BASE },
+    Case { name: "src", op: SRC, operand: 4, acr: 0x1234, expect: Expect { acr: Some(0x4123), ..NONE }, ..BASE },
+    Case { name: "slc", op: SLC, operand: 4, acr: 0x1234, expect: Expect { acr: Some(0x2341), ..NONE }, ..BASE },
+    Case { name: "srcd", op: SRCD, operand: 4, acr: 0x1234, ixr: 0x5678,
+           expect: Expect { acr: Some(0x8123), ixr: Some(0x4567), ..NONE }, ..BASE },
+    Case { name: "slcd", op: SLCD, operand: 4, acr: 0x1234, ixr: 0x5678,
+           expect: Expect { acr: Some(0x2345), ixr: Some(0x6781), ..NONE }, ..BASE },
+    Case { name: "srll", op: SRLL, operand: 4, acr: 0xF0AA, expect: Expect { acr: Some(0x0FAA), ..NONE }, ..BASE },
+    Case { name: "srll past the byte", op: SRLL, operand: 9, acr: 0xF0AA, expect: Expect { acr: Some(0x00AA), ..NONE }, ..BASE },
+    Case { name: "slll", op: SLLL, operand: 4, acr: 0x0FAA, expect: Expect { acr: Some(0xF0AA), ..NONE }, ..BASE },
+    Case { name: "slll past the byte", op: SLLL, operand: 8, acr: 0x0FAA, expect: Expect { acr: Some(0x00AA), ..NONE }, ..BASE },
+    Case { name: "srlr", op: SRLR, operand: 4, acr: 0xAAF0, expect: Expect { acr: Some(0xAA0F), ..NONE }, ..BASE },
+    Case { name: "srlr past the byte", op: SRLR, operand: 15, acr: 0xAAF0, expect: Expect { acr: Some(0xAA00), ..NONE }, ..BASE },
+    Case { name: "sllr", op: SLLR, operand: 4, acr: 0xAA0F, expect: Expect { acr: Some(0xAAF0), ..NONE }, ..BASE },
+    Case { name: "sllr past the byte", op: SLLR, operand: 12, acr: 0xAA0F, expect: Expect { acr: Some(0xAA00), ..NONE }, ..BASE },
+    Case { name: "srcl", op: SRCL, operand: 1, acr: 0x01AA, expect: Expect { acr: Some(0x80AA), ..NONE }, ..BASE },
+    Case { name: "slcl", op: SLCL, operand: 1, acr: 0x80AA, expect: Expect { acr: Some(0x01AA), ..NONE }, ..BASE },
+    Case { name: "srcr", op: SRCR, operand: 1, acr: 0xAA01, expect: Expect { acr: Some(0xAA80), ..NONE }, ..BASE },
+    Case { name: "slcr", op: SLCR, operand: 1, acr: 0xAA80, expect: Expect { acr: Some(0xAA01), ..NONE }, ..BASE },
+];
+
+// run one case, returning what differed
+fn run(case: &Case) -> Vec<String> {
+    let mut cpu = Cpu::new();
+    let mut memory = Memory::new();
+    let instruction = if case.indexed {
+        Instruction::indexed(case.op, case.operand)
+    } else {
+        Instruction::new(case.op, case.operand)
+    };
+    for (address, word) in case.memory {
+        memory.core[*address as usize] = *word as i16;
+    }
+    memory.core[case.origin as usize] = instruction.encode() as i16;
+    cpu.acr = case.acr as i16;
+    cpu.ixr = case.ixr as i16;
+    cpu.status = case.status;
+    cpu.int_enb = case.int_enb;
+    cpu.int_act = case.int_act;
+    cpu.sense_switches = case.switches;
+    cpu.external_sense = case.external_sense;
+    if case.host_calls {
+        let mut host = HostCalls::new();
+        host.echo = false;
+        cpu.host_calls = Some(host);
+    }
+    cpu.pcr = case.origin;
+    cpu.mode = Mode::RUN;
+    cpu.step(&mut memory);
+
+    let expect = &case.expect;
+    let mut differences = Vec::new();
+    let mut check = |what: &str, got: u16, want: Option<u16>| {
+        if let Some(want) = want {
+            if got != want {
+                differences.push(format!("{} {:04X}, expected {:04X}", what, got, want));
+            }
+        }
+    };
+    check("acr", cpu.acr as u16, expect.acr);
+    check("ixr", cpu.ixr as u16, expect.ixr);
+    check("status", cpu.status, expect.status);
+    check("pcr", cpu.pcr, expect.pcr);
+    check("int_enb", cpu.int_enb, expect.int_enb);
+    check("int_act", cpu.int_act, expect.int_act);
+    for (address, word) in expect.memory {
+        check(&format!("core {:04X}", address), memory.core[*address as usize] as u16, Some(*word));
+    }
+    if let Some(masked) = expect.masked {
+        if cpu.int_masked != masked {
+            differences.push(format!("masked {}, expected {}", cpu.int_masked, masked));
+        }
+    }
+    if cpu.halt_reason != expect.halt {
+        differences.push(format!("halt {:?}, expected {:?}", cpu.halt_reason, expect.halt));
+    }
+    differences
+}
+
+#[test]
+fn every_case_conforms() {
+    let mut failures = Vec::new();
+    for case in CASES {
+        for difference in run(case) {
+            failures.push(format!("{:<24} {}", case.name, difference));
+        }
+    }
+    assert!(failures.is_empty(), "\n{}\n", failures.join("\n"));
+}
+
+#[test]
+fn every_opcode_has_a_case() {
+    let missing: Vec<&str> = OPCODES.iter()
+        .filter(|info| !CASES.iter().any(|case| case.op == info.opcode))
+        .map(|info| info.mnemonic)
+        .collect();
+    assert!(missing.is_empty(), "no case for {}", missing.join(" "));
+}
+
+#[test]
+fn unassigned_words_halt_as_illegal() {
+    for word in [0x00C0u16, 0x0100, 0x0160, 0x0B00, 0x0E00] {
+        let mut cpu = Cpu::new();
+        let mut memory = Memory::new();
+        memory.core[ORIGIN as usize] = word as i16;
+        cpu.pcr = ORIGIN;
+        cpu.mode = Mode::RUN;
+        cpu.step(&mut memory);
+        assert_eq!(cpu.halt_reason, Some(HaltReason::Illegal), "word {:04X}", word);
+    }
+}
@@ -0,0 +1,148 @@
+// Console scripts: stepping, registers and core read and written by
+// script, breakpoints with and without actions, and the scheduler functions
+// posting requests at the cycles and instruction counts asked for
+
+use rhai::INT;
+use rustheon::assembler::assemble;
+use rustheon::console::Console;
+use rustheon::cpu::{Cpu, Memory};
+
+// counts COUNT up by one each time round until it reaches TEN
+const COUNTER:&str = "
+ ORG 0x0100
+START CLR
+LOOP ADD ONE
+TOP STW COUNT
+ SUB TEN
+ SAZ
+ JMP AGAIN
+ HLT
+AGAIN LDW COUNT
+ JMP LOOP
+ONE DATA 1
+TEN DATA 10
+COUNT DATA 0
+ END START
+";
+
+// a console on a machine with the counter loaded and its symbols
+fn counter() -> Console {
+    let assembly = assemble(COUNTER).unwrap();
+    let mut memory = Memory::new();
+    let start = assembly.load(&mut memory).unwrap();
+    let mut cpu = Cpu::new();
+    cpu.set_pcr(start);
+    Console::new(cpu, memory, Some(assembly.symbols))
+}
+
+// a console on a machine with a run of CLRs at 0x0100
+fn console() -> Console {
+    let mut memory = Memory::new();
+    for word in &mut memory.core[0x0100..0x0200] {
+        *word = 0x0110;
+    }
+    let mut cpu = Cpu::new();
+    cpu.set_pcr(0x0100);
+    Console::new(cpu, memory, None)
+}
+
+fn ints(value: rhai::Dynamic) -> Vec<INT> {
+    value.into_array().unwrap().into_iter().map(|item| item.as_int().unwrap()).collect()
+}
+
+#[test]
+fn scheduled_interrupts_fire_and_are_reported() {
+    let mut console = console();
+    let levels = console.eval(r#"
+        interrupt_every_cycles(2, 10, 10);
+        interrupt_at_instruction(4, 5);
+        let id = interrupt_at_cycle(6, 1000);
+        if !cancel_interrupt(id) { throw "not cancelled"; }
+        run(30);
+        fired().map(|firing| firing.level)
+    "#).unwrap();
+    assert_eq!(ints(levels), [4, 2, 2, 2]);
+    let cycles = console.eval("fired().map(|firing| firing.cycle)").unwrap();
+    assert_eq!(ints(cycles), [5, 10, 20, 30]);
+    assert_eq!(console.machine().cpu.int_req, 0x0001 << 4 | 0x0001 << 2);
+}
+
+#[test]
+fn scheduling_checks_its_arguments() {
+    let mut console = console();
+    assert!(console.eval("interrupt_at_cycle(16, 0)").is_err());
+    assert!(console.eval("interrupt_every_instructions(1, -1, 10)").is_err());
+}
+
+#[test]
+fn steps_and_reads_registers() {
+    let mut console = counter();
+    assert_eq!(console.eval("step()").unwrap().as_int().unwrap(), 0x0101);
+    let pcr = console.eval("step(2); reg(\"pcr\")").unwrap();
+    assert_eq!(pcr.as_int().unwrap(), 0x0103);
+    assert_eq!(console.eval("[reg(\"acr\"), instructions(), peek(symbol(\"COUNT\"))]").map(ints).unwrap(), [1, 3, 1]);
+
+    assert!(console.eval("set_reg(\"acr\", -1); set_reg(\"ixr\", 0x1234)").is_ok());
+    assert_eq!(console.eval("[reg(\"acr\"), reg(\"ixr\")]").map(ints).unwrap(), [0xFFFF, 0x1234]);
+    assert_eq!(console.machine().cpu.acr, -1);
+    assert!(console.eval("set_reg(\"pcr\", symbol(\"START\"))").is_ok());
+    assert_eq!(console.machine().cpu.pcr, 0x0100);
+    assert!(console.eval("reg(\"xyz\")").is_err());
+    assert!(console.eval("set_reg(\"mbr\", 0)").is_err(), "mbr is read only");
+}
+
+#[test]
+fn reads_and_writes_core() {
+    let mut console = counter();
+    assert!(console.eval("poke(symbol(\"TEN\"), 3); poke(0x0300, 0xFFFF)").is_ok());
+    assert_eq!(console.machine().memory.core[0x0300], -1);
+    assert_eq!(console.eval("run()").unwrap().into_string().unwrap(), "halt");
+    assert_eq!(console.eval("[peek(symbol(\"COUNT\")), peek(0x0300)]").map(ints).unwrap(), [3, 0xFFFF]);
+    assert_eq!(console.eval("halt_reason()").unwrap().into_string().unwrap(), "hlt");
+    let dump = console.eval("dump(symbol(\"ONE\"), 3)").unwrap().into_string().unwrap();
+    assert_eq!(dump, "0109  0001 0003 0003\n");
+    assert_eq!(console.eval("describe(0x010A)").unwrap().into_string().unwrap(), "TEN");
+    assert!(console.eval("peek(0x8000)").is_err());
+    assert!(console.eval("poke(-1, 0)").is_err());
+    assert!(console.eval("symbol(\"NOWHERE\")").is_err());
+}
+
+#[test]
+fn breakpoints_stop_the_run_or_call_their_action() {
+    let mut console = counter();
+    let stopped = console.eval(r#"
+        break_at(symbol("TOP"));
+        let first = run_from(symbol("START"));
+        let count = peek(symbol("COUNT"));
+        let again = run();
+        [first, reg("pcr"), count, again, peek(symbol("COUNT"))]
+    "#).unwrap().into_array().unwrap();
+    assert_eq!(stopped[0].clone().into_string().unwrap(), "break");
+    assert_eq!(stopped[1].as_int().unwrap(), 0x0102, "stopped before the STW at TOP");
+    assert_eq!(stopped[2].as_int().unwrap(), 0);
+    assert_eq!(stopped[3].clone().into_string().unwrap(), "break", "a run carries on from a breakpoint");
+    assert_eq!(stopped[4].as_int().unwrap(), 1);
+    assert_eq!(console.eval("breakpoints()").map(ints).unwrap(), [0x0102]);
+
+    let hits = console.eval(r#"
+        break_at(symbol("TOP"), |pcr| { poke(0x0300, peek(0x0300) + 1); pcr == 0x0102 });
+        let ended = run();
+        if ended != "halt" { throw ended; }
+        peek(0x0300)
+    "#).unwrap();
+    assert_eq!(hits.as_int().unwrap(), 8, "the action ran at every pass after the one the run started from");
+
+    let stopped = console.eval(r#"
+        poke(symbol("COUNT"), 0);
+        break_at(symbol("AGAIN"), |pcr| peek(symbol("COUNT")) < 4);
+        [run_from(symbol("START")), peek(symbol("COUNT"))]
+    "#).unwrap().into_array().unwrap();
+    assert_eq!(stopped[0].clone().into_string().unwrap(), "break", "an action returning false stops the run");
+    assert_eq!(stopped[1].as_int().unwrap(), 4);
+
+    assert!(console.eval("clear_break(symbol(\"TOP\"))").unwrap().as_bool().unwrap());
+    assert!(!console.eval("clear_break(symbol(\"TOP\"))").unwrap().as_bool().unwrap());
+    assert!(console.eval("clear_breaks()").unwrap().is_unit());
+    assert!(console.eval("breakpoints()").map(ints).unwrap().is_empty());
+    assert!(console.eval("break_at(0x8000)").is_err());
+}
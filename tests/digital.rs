@@ -0,0 +1,85 @@
+// Digital I/O module: input and output groups through DIN and DOT, change
+// latches selected by the masks, and input changes scheduled in emulated time
+
+use rustheon::cpu::Memory;
+use rustheon::digital::DigitalIo;
+use rustheon::io::Device;
+
+#[test]
+fn groups_read_and_write() {
+    let mut digital = DigitalIo::new(3);
+    assert_eq!(digital.registers(), 6);
+    digital.set_input(0, 0x8001);
+    digital.set_input(2, 0x00F0);
+    digital.set_input_bit(2, 0, true);
+    digital.set_input_bit(2, 4, false);
+    digital.set_input(3, 0xFFFF);                               // no such group
+    assert_eq!(digital.din(0, 0), 0x8001u16 as i16);
+    assert_eq!(digital.din(1, 0), 0);
+    assert_eq!(digital.din(2, 0), 0x00E1);
+
+    digital.dot(1, 0x1234, 0);
+    digital.dot(2, -1, 0);
+    assert_eq!(digital.output(0), 0);
+    assert_eq!(digital.output(1), 0x1234);
+    assert_eq!(digital.output(2), 0xFFFF);
+    assert!(digital.output_bit(1, 2) && !digital.output_bit(1, 0));
+    assert_eq!(digital.output(3), 0);
+}
+
+#[test]
+fn masked_changes_latch_until_read() {
+    let mut digital = DigitalIo::new(2);
+    let mut memory = Memory::new();
+    digital.dot(2 + 1, 0x000F, 0);                              // change mask of group 1
+    digital.set_input(0, 0x0001);                               // group 0 is not selected
+    digital.set_input(1, 0x0030);                               // bits outside the mask
+    assert!(!digital.service(10, &mut memory));
+
+    digital.set_input(1, 0x0032);
+    assert!(digital.service(20, &mut memory));
+    assert!(!digital.service(30, &mut memory), "a change requests once");
+    digital.set_input(1, 0x0036);
+    digital.set_input_bit(1, 1, false);
+    assert!(digital.service(40, &mut memory));
+    assert_eq!(digital.din(2, 50), 0);
+    assert_eq!(digital.din(3, 50), 0x0006);                     // both changes, on and off
+    assert_eq!(digital.din(3, 60), 0, "reading clears the latch");
+}
+
+#[test]
+fn scheduled_changes_apply_at_their_cycle() {
+    let mut digital = DigitalIo::default();
+    let mut memory = Memory::new();
+    digital.dot(4, 0x0003, 0);                                  // change mask of group 0
+    digital.schedule_bit(2_000, 0, 1, true);
+    digital.schedule(1_000, 0, 0x00FF, 0x0081);
+    digital.schedule_bit(2_000, 0, 0, false);                   // after the 1 at the same cycle
+    assert_eq!(digital.pending_changes(), 3);
+
+    assert!(!digital.service(999, &mut memory));
+    assert_eq!(digital.input(0), 0);
+    assert!(digital.service(1_000, &mut memory));
+    assert_eq!(digital.input(0), 0x0081);
+    assert_eq!(digital.din(4, 1_000), 0x0001);
+    assert!(digital.service(2_500, &mut memory));
+    assert_eq!(digital.input(0), 0x0082);
+    assert_eq!(digital.din(4, 2_500), 0x0003);
+    assert_eq!(digital.pending_changes(), 0);
+}
+
+#[test]
+fn reset_clears_outputs_masks_and_latches() {
+    let mut digital = DigitalIo::new(1);
+    let mut memory = Memory::new();
+    digital.dot(0, 0x00FF, 0);
+    digital.dot(1, -1, 0);
+    digital.set_input(0, 0x0100);
+    digital.service(1, &mut memory);
+    digital.reset();
+    assert_eq!(digital.output(0), 0);
+    assert_eq!(digital.din(1, 2), 0);
+    assert_eq!(digital.din(0, 2), 0x0100, "inputs follow the plant, not reset");
+    digital.set_input(0, 0);
+    assert!(!digital.service(3, &mut memory), "masks are cleared");
+}
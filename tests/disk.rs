@@ -0,0 +1,75 @@
+// Disk geometry and transfers: zero dimensions are refused by a configuration
+// and by Disk::new, and words written to a sector read back after a seek
+
+use rustheon::config::{ConfigError, MachineConfig};
+use rustheon::cpu::{Cpu, Memory};
+use rustheon::disk::{Disk, Geometry, ERROR, FN_READ, FN_SEEK, FN_WRITE, READY};
+use rustheon::io::Device;
+
+#[test]
+fn configuration_refuses_zero_geometry() {
+    for setting in ["sectors = 0", "heads = 0", "cylinders = 0", "words_per_sector = 0"] {
+        let config = MachineConfig::parse(&format!("[[device]]\ntype = \"disk\"\n{}\n", setting)).unwrap();
+        let result = config.build(&mut Cpu::new(), &mut Memory::new());
+        assert!(matches!(result, Err(ConfigError::DiskGeometry(_))), "{}", setting);
+    }
+}
+
+#[test]
+#[should_panic(expected = "dimension of zero")]
+fn disk_refuses_zero_sectors() {
+    Disk::new(Geometry { sectors: 0, ..Geometry::default() });
+}
+
+// run a command on the disk to completion, returning the time and status
+fn command(disk: &mut Disk, memory: &mut Memory, now: u64, function: i16) -> (u64, i16) {
+    let mut now = now;
+    disk.dot(0, function, now);
+    while disk.busy() {
+        now += 1_000;
+        disk.service(now, memory);
+    }
+    (now, disk.din(0, now))
+}
+
+#[test]
+fn seek_write_and_read_round_trip() {
+    let path = std::env::temp_dir().join(format!("rustheon-disk-{}.img", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let mut disk = Disk::new(Geometry { cylinders: 4, heads: 2, sectors: 4, words_per_sector: 4 });
+    disk.attach_file(&path, true).unwrap();
+    let mut memory = Memory::new();
+
+    disk.dot(1, 2, 0);                                      // cylinder 2
+    let (now, status) = command(&mut disk, &mut memory, 0, FN_SEEK);
+    assert_eq!(status & (READY | ERROR), READY);
+    assert_eq!(disk.din(1, now), 2);
+
+    for i in 0..6 {                                         // a sector and a half
+        memory.core[0x0200 + i] = 0x1100 + i as i16;
+    }
+    disk.dot(2, 0x0003, now);                               // head 0, last sector of the track
+    disk.dot(3, 0x0200, now);
+    disk.dot(4, 6, now);
+    let (now, status) = command(&mut disk, &mut memory, now, FN_WRITE);
+    assert_eq!(status & ERROR, 0, "{:?}", disk.last_error);
+    assert_eq!(disk.din(2, now), 0x0101);                   // on into head 1
+    assert_eq!(disk.din(3, now), 0x0206);
+
+    disk.dot(1, 0, now);                                    // away and back again
+    let (now, _) = command(&mut disk, &mut memory, now, FN_SEEK);
+    disk.dot(1, 2, now);
+    disk.dot(2, 0x0003, now);
+    disk.dot(3, 0x0400, now);
+    disk.dot(4, 8, now);
+    let (now, status) = command(&mut disk, &mut memory, now, FN_READ);
+    assert_eq!(status & ERROR, 0);
+    assert_eq!(disk.din(1, now), 2);
+    assert_eq!(&memory.core[0x0400..0x0408], &[0x1100, 0x1101, 0x1102, 0x1103, 0x1104, 0x1105, 0, 0]);
+
+    let image = std::fs::read(&path).unwrap();
+    let _ = std::fs::remove_file(&path);
+    let offset = ((2 * 2) * 4 + 3) * 4 * 2;                 // cylinder 2, head 0, sector 3
+    assert_eq!(image.len(), offset + 2 * 4 * 2);            // the written sector fills with zeros
+    assert_eq!(&image[offset..offset + 4], &[0x11, 0x00, 0x11, 0x01]);
+}
@@ -0,0 +1,105 @@
+// Decoders agree: the instruction table decodes every word as the nested
+// match decoder it replaced did, stepping and the dispatch cache run every
+// instruction word to the same machine state, and the cache decodes a word
+// again after the program stores over it
+
+use rustheon::cpu::{Cpu, Memory, Mode};
+use rustheon::dispatch::DispatchCache;
+use rustheon::instruction::Instruction;
+
+const ORIGIN:usize = 0x0100;
+
+fn machine(word: u16) -> (Cpu, Box<Memory>) {
+    let mut cpu = Cpu::new();
+    let mut memory = Box::new(Memory::new());
+    for (i, value) in memory.core.iter_mut().enumerate().take(0x0400) {
+        *value = (i as i16).wrapping_mul(0x1357);
+    }
+    memory.core[ORIGIN] = word as i16;
+    cpu.acr = 0x1234;
+    cpu.ixr = 0x0042;
+    cpu.status = 0x0280;
+    cpu.pcr = ORIGIN as u16;
+    cpu.mode = Mode::RUN;
+    (cpu, memory)
+}
+
+fn same(a: &Cpu, b: &Cpu) -> bool {
+    a.acr == b.acr && a.ixr == b.ixr && a.status == b.status && a.pcr == b.pcr && a.mode == b.mode
+        && a.int_enb == b.int_enb && a.int_act == b.int_act && a.int_masked == b.int_masked
+        && a.cycles == b.cycles && a.halt_reason == b.halt_reason
+}
+
+// The nested match decoder the CPU used before the OPCODES table, kept here
+// as an independent reference for the table: the mnemonic of a word, None
+// for a word that is not an instruction.
+fn reference_decode(word: u16) -> Option<&'static str> {
+    const MEMORY_REFERENCE: [&str; 16] = ["", "JMP", "JSX", "STB", "CMB", "LDB", "STX", "STW",
+                                          "LDW", "LDX", "ADD", "SUB", "ORI", "ORE", "AND", "CMW"];
+    let digit2 = ((word & 0x00F0) >> 4) as usize;
+    let pick = |names: &[&'static str]| names.get(digit2).copied();
+    match word >> 8 {
+        0x10..=0xFF => Some(MEMORY_REFERENCE[(word >> 12) as usize]),
+        0x00 => pick(&["HLT", "INRET", "ENB", "DSB", "SLM", "SGM", "CEX", "CXE", "SML", "SMU", "MSK", "UNM"]),
+        0x01 => match digit2 {
+            0x1..=0x5 => pick(&["", "CLR", "CMP", "INV", "CAX", "CXA"]),
+            _ => None,
+        },
+        0x02 => Some("DIN"),
+        0x03 => Some("DOT"),
+        0x04 => Some("IXS"),
+        0x05 => Some("DXS"),
+        0x06 => Some("LLB"),
+        0x07 => Some("CLB"),
+        0x08 => pick(&["SAZ", "SAP", "SAM", "SAO", "SLS", "SXE", "SEQ", "SNE",
+                       "SGR", "SLE", "SNO", "SSE", "SS0", "SS1", "SS2", "SS3"]),
+        0x09 => pick(&["SRA", "SLA", "SRAD", "SLAD"]),
+        0x0A => pick(&["SRL", "SLL", "SRLD", "SLLD", "SRC", "SLC", "SRCD", "SLCD",
+                       "SRLL", "SLLL", "SRLR", "SLLR", "SRCL", "SLCL", "SRCR", "SLCR"]),
+        0x0F => Some("TRAP"),
+        _ => None,
+    }
+}
+
+#[test]
+fn table_agrees_with_the_nested_decoder() {
+    for word in 0..=0xFFFFu16 {
+        let table = Instruction::decode(word).map(|instruction| instruction.opcode.mnemonic());
+        assert_eq!(table, reference_decode(word), "{:04X}", word);
+    }
+}
+
+#[test]
+fn step_and_cache_agree_on_every_word() {
+    // every word with a zero top digit, and a spread of memory references
+    let words = (0x0000..0x1000u16).chain((0x1000..=0xFFFFu16).step_by(13));
+    for word in words {
+        let (mut table, mut table_memory) = machine(word);
+        table.step(&mut table_memory);
+        let (mut cached, mut cached_memory) = machine(word);
+        cached.dispatch = Some(DispatchCache::new());
+        cached.run(&mut cached_memory, 1);
+        assert!(same(&table, &cached), "{:04X}: the cache differs from step", word);
+        assert!(table_memory.core[..] == cached_memory.core[..], "{:04X}: core differs", word);
+    }
+}
+
+#[test]
+fn cache_decodes_a_word_stored_over() {
+    // LOOP LDW NEW / STW LOOP+3 / JMP LOOP+3 / CLR ... the CLR becomes HLT
+    let program: [u16; 6] = [0x8105, 0x7103, 0x1103, 0x0110, 0x1100, 0x0000];
+    let mut cpu = Cpu::new();
+    let mut memory = Memory::new();
+    for (i, word) in program.iter().enumerate() {
+        memory.core[ORIGIN + i] = *word as i16;
+    }
+    cpu.dispatch = Some(DispatchCache::new());
+    cpu.pcr = ORIGIN as u16 + 3;
+    cpu.mode = Mode::RUN;
+    cpu.run(&mut memory, 1);                            // CLR goes into the cache
+    cpu.pcr = ORIGIN as u16;
+    cpu.run(&mut memory, 10);
+    assert_eq!(cpu.mode, Mode::HALT);
+    assert_eq!(cpu.pcr, ORIGIN as u16 + 4);
+    assert_eq!(cpu.dispatch.as_ref().unwrap().decodes, 5);
+}
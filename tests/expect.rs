@@ -0,0 +1,127 @@
+// Dialogs on the console teletype: an echo program driven by send, expect
+// and expect_regex, a timeout with its transcript, and script parsing
+
+use rustheon::assembler::assemble;
+use rustheon::cpu::{Cpu, Memory, Mode};
+use rustheon::expect::{Command, Dialog, ExpectError, Script};
+
+// prints a prompt, then echoes each key until a Q, which halts
+const ECHO: &str = "
+        ORG     0x0100
+START   LLB     '>'
+        DOT     0x01            ; printer is ready at power on
+KEY     DIN     0x02            ; keyboard status
+        SAM
+        JMP     KEY
+        DIN     0x01            ; the key
+        CLB     'Q'
+        SNE
+        HLT
+        STW     CHAR
+PRINT   DIN     0x03            ; printer status
+        SAM
+        JMP     PRINT
+        LDW     CHAR
+        DOT     0x01
+        JMP     KEY
+CHAR    DATA    0
+        END     START
+";
+
+fn machine() -> (Cpu, Box<Memory>) {
+    let assembly = assemble(ECHO).unwrap();
+    let mut cpu = Cpu::new();
+    let mut memory = Box::new(Memory::new());
+    cpu.pcr = assembly.load(&mut memory).unwrap();
+    cpu.mode = Mode::RUN;
+    (cpu, memory)
+}
+
+#[test]
+fn talks_to_the_echo_program() {
+    let (mut cpu, mut memory) = machine();
+    let mut dialog = Dialog::new(&mut cpu, &mut memory).unwrap();
+    dialog.set_timeout(5.0);
+    assert_eq!(dialog.expect(">").unwrap(), ">");
+    dialog.send("LEVEL 42\n");
+    assert_eq!(dialog.expect_regex(r"LEVEL (\d+)\r").unwrap(), vec!["LEVEL 42\r", "42"]);
+    dialog.send("ABC");
+    assert_eq!(dialog.expect_regex(r"(X)?(B)").unwrap(), vec!["B", "", "B"]);
+    assert_eq!(dialog.expect("C").unwrap(), "C");     // only what came after the last match
+    dialog.send("Q");
+    dialog.halt().unwrap();
+    assert_eq!(dialog.output(), ">LEVEL 42\rABC");
+    assert_eq!(cpu.mode, Mode::HALT);
+}
+
+#[test]
+fn times_out_with_a_transcript() {
+    let (mut cpu, mut memory) = machine();
+    let mut dialog = Dialog::new(&mut cpu, &mut memory).unwrap();
+    dialog.set_timeout(0.5);
+    dialog.send("HI");
+    dialog.expect("H").unwrap();
+    match dialog.expect("NEVER") {
+        Err(ExpectError::Timeout { waiting_for, seconds, transcript }) => {
+            assert_eq!(waiting_for, "\"NEVER\"");
+            assert!((seconds - 0.5).abs() < 0.001, "{}", seconds);
+            assert!(transcript.starts_with("--- dialog ---\n"), "{}", transcript);
+            assert!(transcript.contains("sent \"HI\"\n"), "{}", transcript);
+            assert!(transcript.contains("found \"H\" as \"H\"\n"), "{}", transcript);
+            assert!(transcript.ends_with("--- teletype ---\n>H<<< not matched >>>\nI\n"), "{}", transcript);
+        },
+        other => panic!("expected a timeout, got {:?}", other),
+    }
+}
+
+#[test]
+fn fails_when_the_machine_halts_first() {
+    let (mut cpu, mut memory) = machine();
+    let mut dialog = Dialog::new(&mut cpu, &mut memory).unwrap();
+    dialog.send("Q");
+    assert!(matches!(dialog.expect("NEVER"), Err(ExpectError::Halted { pcr: 0x0109, .. })));
+}
+
+#[test]
+fn parses_a_script() {
+    let text = "; operator\n\
+                timeout 2.5\n\
+                expect  \">\"           ; prompt\n\
+                send    \"A \\\"B\\\"\\n\"\n\
+                expect  /A \"(\\w)\"\\/?/\n\
+                WAIT    0.25\n\
+                halt\n";
+    let script = Script::parse(text).unwrap();
+    assert_eq!(script.commands, vec![
+        (2, Command::Timeout(2.5)),
+        (3, Command::Expect(">".to_string())),
+        (4, Command::Send("A \"B\"\n".to_string())),
+        (5, Command::ExpectRegex("A \"(\\w)\"/?".to_string())),
+        (6, Command::Wait(0.25)),
+        (7, Command::Halt),
+    ]);
+}
+
+#[test]
+fn rejects_bad_scripts() {
+    for (text, line) in [("send HELLO", 1), ("expect \"open", 1), ("timeout -1", 1), ("wait soon", 1),
+                         ("\nsend \"A\" \"B\"", 2), ("type \"A\"", 1), ("expect /open", 1)] {
+        match Script::parse(text) {
+            Err(ExpectError::Syntax { line: found, .. }) => assert_eq!(found, line, "{}", text),
+            other => panic!("{}: expected a syntax error, got {:?}", text, other),
+        }
+    }
+    assert!(matches!(Script::parse("halt\nexpect /(/"),
+                     Err(ExpectError::Script { line: 2, err }) if matches!(*err, ExpectError::BadPattern(_))));
+}
+
+#[test]
+fn runs_a_script() {
+    let (mut cpu, mut memory) = machine();
+    let mut dialog = Dialog::new(&mut cpu, &mut memory).unwrap();
+    let script = Script::parse("expect \">\"\nsend \"7\\n\"\nexpect /(\\d)\\r/\nsend \"Q\"\nhalt\n").unwrap();
+    script.run(&mut dialog).unwrap();
+    let script = Script::parse("timeout 0.1\nexpect \"NEVER\"\n").unwrap();
+    assert!(matches!(script.run(&mut dialog), Err(ExpectError::Script { line: 2, err })
+                     if matches!(*err, ExpectError::Halted { .. })));
+}
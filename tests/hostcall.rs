@@ -0,0 +1,84 @@
+// Host calls: PUTS stops at the zero byte whichever half it is in, PASS and
+// FAIL are counted with where they came from, EXIT stops with ACR as the
+// status, and a function there is not halts as an illegal instruction
+
+use rustheon::cpu::{Cpu, HaltReason, Memory, Mode};
+use rustheon::hostcall::{Check, HostCalls};
+
+const ORIGIN: usize = 0x0100;
+const TEXT: usize = 0x0200;
+
+// run the words at ORIGIN until the machine stops
+fn run(program: &[u16], text: &[u16], acr: u16) -> (Cpu, Memory) {
+    let mut cpu = Cpu::new();
+    let mut memory = Memory::new();
+    for (i, word) in program.iter().enumerate() {
+        memory.core[ORIGIN + i] = *word as i16;
+    }
+    for (i, word) in text.iter().enumerate() {
+        memory.core[TEXT + i] = *word as i16;
+    }
+    let mut host = HostCalls::new();
+    host.echo = false;
+    cpu.host_calls = Some(host);
+    cpu.acr = acr as i16;
+    cpu.ixr = TEXT as i16;
+    cpu.set_pcr(ORIGIN as u16);
+    cpu.mode = Mode::RUN;
+    cpu.run(&mut memory, 100);
+    (cpu, memory)
+}
+
+fn output(cpu: &Cpu) -> String {
+    cpu.host_calls.as_ref().unwrap().output_text()
+}
+
+#[test]
+fn puts_an_odd_length_string() {
+    let (cpu, _) = run(&[0x0F01, 0x0000], &[0x4142, 0x4300, 0x4445], 0);    // "ABC", zero in the right byte
+    assert_eq!(output(&cpu), "ABC");
+    assert_eq!(cpu.halt_reason, Some(HaltReason::Instruction));
+}
+
+#[test]
+fn puts_stops_at_a_zero_left_byte() {
+    let (cpu, _) = run(&[0x0F01, 0x0000], &[0x4142, 0x0043, 0x4445], 0);    // "AB", the C is past the end
+    assert_eq!(output(&cpu), "AB");
+}
+
+#[test]
+fn puts_an_empty_string() {
+    let (cpu, _) = run(&[0x0F01, 0x0000], &[0x0041], 0);
+    assert_eq!(output(&cpu), "");
+}
+
+#[test]
+fn puts_leaves_the_registers() {
+    let (cpu, _) = run(&[0x0F01, 0x0000], &[0x4100], 0x1234);
+    assert_eq!((cpu.acr as u16, cpu.ixr as u16), (0x1234, TEXT as u16));
+}
+
+#[test]
+fn pass_and_fail_are_counted() {
+    // PASS, PASS, LLB 7, FAIL, HLT
+    let (cpu, _) = run(&[0x0F04, 0x0F04, 0x0607, 0x0F05, 0x0000], &[], 0x0011);
+    let host = cpu.host_calls.as_ref().unwrap();
+    assert_eq!(host.passed, [Check { pcr: 0x0100, code: 0x0011 }, Check { pcr: 0x0101, code: 0x0011 }]);
+    assert_eq!(host.failed, [Check { pcr: 0x0103, code: 0x0007 }]);
+    assert_eq!(host.output_text(), "FAIL 0007 at 0103\n");
+}
+
+#[test]
+fn exit_stops_with_acr_as_the_status() {
+    let (cpu, _) = run(&[0x0F00, 0x0000], &[], 0x0003);
+    assert_eq!(cpu.mode, Mode::HALT);
+    assert_eq!(cpu.halt_reason, Some(HaltReason::Exit(3)));
+    assert_eq!(cpu.pcr, ORIGIN as u16 + 1);
+}
+
+#[test]
+fn unknown_function_halts_as_illegal() {
+    let (cpu, _) = run(&[0x0F06, 0x0000], &[], 0);
+    assert_eq!(cpu.halt_reason, Some(HaltReason::Illegal));
+    assert_eq!(cpu.pcr, ORIGIN as u16 + 1);
+}
@@ -0,0 +1,31 @@
+// Instruction words: every word that decodes encodes back to itself, and a
+// memory reference names a word in its page, 2K words for a word reference
+// and 1K for a byte reference
+
+use rustheon::instruction::{Instruction, Opcode};
+
+#[test]
+fn decode_then_encode_gives_the_word_back() {
+    for word in 0..=0xFFFFu16 {
+        if let Some(instruction) = Instruction::decode(word) {
+            assert_eq!(instruction.encode(), word, "{:04X} decodes as {}", word, instruction);
+        }
+    }
+}
+
+#[test]
+fn word_reference_targets_the_2k_page() {
+    assert_eq!(Instruction::new(Opcode::LDW, 0x0234).target(0x0900), Some(0x0A34));
+    assert_eq!(Instruction::new(Opcode::JMP, 0x0034).target(0x1F00), Some(0x1834));
+}
+
+#[test]
+fn byte_reference_targets_the_1k_page() {
+    assert_eq!(Instruction::new(Opcode::LDB, 0x0201).target(0x0500), Some(0x0500));
+    assert_eq!(Instruction::new(Opcode::STB, 0x0010).target(0x0900), Some(0x0808));
+}
+
+#[test]
+fn other_operands_have_no_target() {
+    assert_eq!(Instruction::new(Opcode::DIN, 0x20).target(0x0100), None);
+}
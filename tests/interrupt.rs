@@ -0,0 +1,187 @@
+// Interrupts: recognition at the boundary after a memory reference, the one
+// instruction hold off after ENB, UNM and INRET, a lower level held while a
+// higher one is active, nesting and return, and the log of requests, entries
+// and exits with their cycle stamps
+
+use rustheon::assembler::assemble;
+use rustheon::cpu::{Cpu, Memory, Mode};
+use rustheon::interrupt::InterruptEventKind::{self, *};
+
+// vectors for levels 3, 5 and 7 at 4 * level, the linkage in the second word
+const SOURCE: &str = "
+        ORG     13
+        DATA    H3
+        ORG     21
+        DATA    H5
+        ORG     29
+        DATA    H7
+        ORG     0x0100
+MAIN    LDW     VALUE
+        CLR
+        CLR
+        CLR
+        HLT
+H3      CLR
+        INRET   3
+H5      CLR
+        CLR
+        INRET   5
+H7      CLR
+        INRET   7
+VALUE   DATA    0x1234
+        END     MAIN
+";
+
+const MAIN: u16 = 0x0100;
+const H3: u16 = 0x0105;
+const H5: u16 = 0x0107;
+const H7: u16 = 0x010A;
+
+fn machine() -> (Cpu, Memory) {
+    let mut cpu = Cpu::new();
+    let mut memory = Memory::new();
+    let transfer = assemble(SOURCE).unwrap().load(&mut memory).unwrap();
+    cpu.set_pcr(transfer);
+    cpu.mode = Mode::RUN;
+    (cpu, memory)
+}
+
+fn level(level: u16) -> u16 {
+    0x0001 << level
+}
+
+#[test]
+fn recognized_after_a_memory_reference() {
+    let (mut cpu, mut memory) = machine();
+    cpu.int_enb = level(3);
+    cpu.int_req = level(3);
+    cpu.step(&mut memory);                                  // LDW VALUE, then the entry
+    assert_eq!(cpu.acr, 0x1234);
+    assert_eq!(cpu.pcr, H3);
+    assert_eq!(cpu.int_act, level(3));
+    assert_eq!(cpu.int_req, 0);
+    assert_eq!(memory.core[12] as u16, MAIN + 1);           // return past the LDW
+}
+
+#[test]
+fn enb_holds_off_recognition_for_one_instruction() {
+    let (mut cpu, mut memory) = machine();
+    memory.core[MAIN as usize] = 0x0023;                    // ENB 3
+    cpu.int_req = level(3);
+    cpu.step(&mut memory);
+    assert_eq!(cpu.pcr, MAIN + 1);
+    assert_eq!(cpu.int_act, 0);
+    cpu.step(&mut memory);
+    assert_eq!(cpu.pcr, H3);
+    assert_eq!(memory.core[12] as u16, MAIN + 2);
+}
+
+#[test]
+fn unm_holds_off_recognition_for_one_instruction() {
+    let (mut cpu, mut memory) = machine();
+    memory.core[MAIN as usize] = 0x00B0;                    // UNM
+    cpu.int_enb = level(3);
+    cpu.int_req = level(3);
+    cpu.int_masked = true;
+    cpu.step(&mut memory);
+    assert!(!cpu.int_masked);
+    assert_eq!(cpu.pcr, MAIN + 1);
+    cpu.step(&mut memory);
+    assert_eq!(cpu.pcr, H3);
+    assert_eq!(memory.core[12] as u16, MAIN + 2);
+}
+
+#[test]
+fn lower_level_waits_for_higher_and_levels_nest() {
+    let (mut cpu, mut memory) = machine();
+    cpu.int_enb = level(3) | level(5) | level(7);
+    cpu.int_req = level(5);
+    cpu.step(&mut memory);                                  // LDW, enter 5
+    assert_eq!((cpu.pcr, cpu.int_act), (H5, level(5)));
+    cpu.int_req |= level(3);
+    cpu.step(&mut memory);                                  // 3 is held while 5 is active
+    assert_eq!((cpu.pcr, cpu.int_act, cpu.int_req), (H5 + 1, level(5), level(3)));
+    cpu.int_req |= level(7);
+    cpu.step(&mut memory);                                  // 7 interrupts 5
+    assert_eq!((cpu.pcr, cpu.int_act), (H7, level(5) | level(7)));
+    assert_eq!(memory.core[28] as u16, H5 + 2);
+    cpu.step(&mut memory);
+    cpu.step(&mut memory);                                  // INRET 7, back into 5
+    assert_eq!((cpu.pcr, cpu.int_act), (H5 + 2, level(5)));
+    cpu.step(&mut memory);                                  // INRET 5, 3 still held off
+    assert_eq!((cpu.pcr, cpu.int_act), (MAIN + 1, 0));
+    cpu.step(&mut memory);                                  // one instruction, then 3
+    assert_eq!((cpu.pcr, cpu.int_act, cpu.int_req), (H3, level(3), 0));
+    assert_eq!(memory.core[12] as u16, MAIN + 2);
+}
+
+#[test]
+fn log_records_requests_entries_and_exits() {
+    let (mut cpu, mut memory) = machine();
+    cpu.int_log.enabled = true;
+    cpu.int_enb = level(3) | level(5) | level(7);
+    cpu.int_req = level(5);
+    cpu.step(&mut memory);                                  // LDW, 2 cycles, enter 5
+    cpu.int_req |= level(3);
+    cpu.step(&mut memory);
+    cpu.int_req |= level(7);
+    cpu.step(&mut memory);                                  // enter 7
+    cpu.step(&mut memory);
+    cpu.step(&mut memory);                                  // INRET 7
+    let inret_7 = cpu.cycles;
+    cpu.step(&mut memory);                                  // INRET 5
+    let inret_5 = cpu.cycles;
+    cpu.step(&mut memory);                                  // enter 3
+    let entered_3 = cpu.cycles - 3;                         // before the entry's three cycles
+
+    let events: Vec<(u8, InterruptEventKind, u64)> = cpu.int_log.events.iter()
+        .map(|event| (event.level, event.kind, event.cycle))
+        .collect();
+    assert_eq!(events, [
+        (5, Requested, 2), (5, Entered, 2),
+        (3, Requested, 6),
+        (7, Requested, 7), (7, Entered, 7),
+        (7, Exited, inret_7),
+        (5, Exited, inret_5),
+        (3, Entered, entered_3),
+    ]);
+    assert_eq!(cpu.int_log.level(7).count(), 3);
+    let first = cpu.int_log.events[0];
+    assert_eq!((first.instruction, first.pcr), (1, MAIN + 1));
+}
+
+#[test]
+fn log_is_empty_unless_enabled() {
+    let (mut cpu, mut memory) = machine();
+    cpu.int_enb = level(3);
+    cpu.int_req = level(3);
+    cpu.step(&mut memory);
+    assert_eq!(cpu.pcr, H3);
+    assert!(cpu.int_log.events.is_empty());
+}
+
+#[test]
+fn handler_in_another_page_addresses_its_own_page() {
+    let source = "
+        ORG     13
+        DATA    H3
+        ORG     0x0100
+MAIN    CLR
+        HLT
+        ORG     0x0900
+H3      LDW     VALUE
+        HLT
+VALUE   DATA    0x0909
+        END     MAIN
+";
+    let mut cpu = Cpu::new();
+    let mut memory = Memory::new();
+    cpu.set_pcr(assemble(source).unwrap().load(&mut memory).unwrap());
+    cpu.mode = Mode::RUN;
+    memory.core[0x0102] = 0x0101;                           // what VALUE would name in page 0
+    cpu.int_enb = level(3);
+    cpu.int_req = level(3);
+    cpu.step(&mut memory);                                  // CLR, enter 3
+    cpu.step(&mut memory);                                  // LDW VALUE
+    assert_eq!(cpu.acr, 0x0909);
+}
@@ -0,0 +1,149 @@
+// Magnetic tape: .tap records, odd lengths and tape marks read back as they
+// were written, backspacing stops at markers that are not a record's, and
+// the unit reports a write of no words as an error instead of writing a mark
+
+use rustheon::cpu::Memory;
+use rustheon::io::Device;
+use rustheon::magtape::*;
+
+fn record(bytes: &[u8]) -> TapRecord {
+    TapRecord::Data { bytes: bytes.to_vec(), error: false }
+}
+
+fn marker(value: u32) -> Vec<u8> {
+    value.to_le_bytes().to_vec()
+}
+
+#[test]
+fn tap_records_and_marks_read_back() {
+    let mut image = TapImage::new();
+    assert_eq!(image.write(&record(&[1, 2, 3, 4])).unwrap(), 12);
+    assert_eq!(image.write(&record(&[5, 6, 7])).unwrap(), 12);      // padded to even
+    assert_eq!(image.write(&TapRecord::TapeMark).unwrap(), 4);
+    assert_eq!(&image.bytes()[..12], &[4, 0, 0, 0, 1, 2, 3, 4, 4, 0, 0, 0]);
+    assert_eq!(&image.bytes()[12..24], &[3, 0, 0, 0, 5, 6, 7, 0, 3, 0, 0, 0]);
+
+    image.rewind();
+    assert_eq!(image.read_forward(), (record(&[1, 2, 3, 4]), 12));
+    assert_eq!(image.read_forward(), (record(&[5, 6, 7]), 12));
+    assert_eq!(image.read_forward(), (TapRecord::TapeMark, 4));
+    assert_eq!(image.read_forward(), (TapRecord::EndOfMedium, 0));
+
+    assert_eq!(image.read_backward(), (Some(TapRecord::TapeMark), 4));
+    assert_eq!(image.read_backward(), (Some(record(&[5, 6, 7])), 12));
+    assert_eq!(image.read_backward(), (Some(record(&[1, 2, 3, 4])), 12));
+    assert_eq!(image.read_backward(), (None, 0));
+    assert!(image.at_bot());
+}
+
+#[test]
+fn tap_write_erases_what_followed() {
+    let mut image = TapImage::new();
+    image.write(&record(&[1, 2])).unwrap();
+    image.write(&record(&[3, 4])).unwrap();
+    image.rewind();
+    image.read_forward();
+    image.write(&TapRecord::TapeMark).unwrap();
+    assert_eq!(image.bytes().len(), 14);
+    image.rewind();
+    image.read_forward();
+    assert_eq!(image.read_forward().0, TapRecord::TapeMark);
+    assert_eq!(image.read_forward().0, TapRecord::EndOfMedium);
+}
+
+#[test]
+fn tap_rejects_records_without_a_length() {
+    let mut image = TapImage::new();
+    assert!(image.write(&record(&[])).is_err());
+    assert!(image.write(&record(&vec![0; 0x0100_0000])).is_err());  // past the 24 bit length
+    assert!(image.bytes().is_empty());
+}
+
+#[test]
+fn tap_gaps_and_end_of_medium_are_not_records() {
+    let mut bytes = [marker(2), vec![9, 8], marker(2), marker(0xFFFF_FFFE)].concat();
+    bytes.extend([marker(2), vec![7, 6], marker(2), marker(0xFFFF_FFFF)].concat());
+    let mut image = TapImage::from_bytes(bytes);
+    assert_eq!(image.read_forward().0, record(&[9, 8]));
+    assert_eq!(image.read_forward().0, record(&[7, 6]));                // over the gap
+    assert_eq!(image.read_forward(), (TapRecord::EndOfMedium, 0));
+    assert_eq!(image.read_backward().0, Some(record(&[7, 6])));
+    assert_eq!(image.read_backward().0, Some(record(&[9, 8])));         // back over the gap
+}
+
+#[test]
+fn tap_backspace_stops_at_a_bad_marker() {
+    // a record with the error flag, then a trailing length that does not
+    // match its leading one
+    let bytes = [marker(0x8000_0002), vec![1, 2], marker(0x8000_0002),
+                 marker(2), vec![3, 4], marker(4)].concat();
+    let mut image = TapImage::from_bytes(bytes);
+    assert_eq!(image.read_forward().0, TapRecord::Data { bytes: vec![1, 2], error: true });
+    image.read_forward();
+    let end = image.position();
+    assert_eq!(image.read_backward(), (None, 0));
+    assert_eq!(image.position(), end);
+}
+
+// run a command on the unit to completion
+fn command(tape: &mut MagTape, memory: &mut Memory, function: i16) -> i16 {
+    let mut now = 0;
+    tape.dot(0, function, now);
+    while tape.busy() {
+        now += 1_000;
+        tape.service(now, memory);
+    }
+    tape.din(0, now)
+}
+
+#[test]
+fn unit_writes_and_reads_records() {
+    let mut tape = MagTape::new();
+    let mut memory = Memory::new();
+    tape.mount(TapImage::new(), false);
+    memory.core[0x0200] = 0x4142;
+    memory.core[0x0201] = 0x4344;
+    tape.dot(1, 0x0200, 0);
+    tape.dot(2, 2, 0);
+    assert_eq!(command(&mut tape, &mut memory, FN_WRITE) & ERROR, 0);
+    assert_eq!(tape.din(2, 0), 4);
+    command(&mut tape, &mut memory, FN_WRITE_MARK);
+    assert_eq!(command(&mut tape, &mut memory, FN_REWIND) & BOT, BOT);
+
+    tape.dot(1, 0x0300, 0);
+    tape.dot(2, 1, 0);                                      // one word of a two word record
+    let status = command(&mut tape, &mut memory, FN_READ);
+    assert_eq!(status & (LENGTH_ERROR | ERROR), LENGTH_ERROR);
+    assert_eq!(memory.core[0x0300], 0x4142);
+    assert_eq!(memory.core[0x0301], 0);
+    assert_eq!(tape.din(1, 0), 0x0301);
+    assert_eq!(tape.din(2, 0), 4);
+    assert_eq!(command(&mut tape, &mut memory, FN_READ) & TAPE_MARK, TAPE_MARK);
+    assert_eq!(tape.din(2, 0), 0);
+}
+
+#[test]
+fn unit_refuses_a_write_of_no_words() {
+    let mut tape = MagTape::new();
+    let mut memory = Memory::new();
+    tape.mount(TapImage::new(), false);
+    tape.dot(2, 0, 0);
+    assert_eq!(command(&mut tape, &mut memory, FN_WRITE) & ERROR, ERROR);
+    assert!(tape.image().bytes().is_empty());
+}
+
+#[test]
+fn unit_reports_long_records_as_ffff() {
+    let mut tape = MagTape::new();
+    let mut memory = Memory::new();
+    let mut image = TapImage::new();
+    image.write(&record(&vec![0x55; 70_000])).unwrap();
+    image.rewind();
+    tape.mount(image, true);
+    tape.dot(1, 0x0100, 0);
+    tape.dot(2, 16, 0);
+    let status = command(&mut tape, &mut memory, FN_READ);
+    assert_eq!(status & LENGTH_ERROR, LENGTH_ERROR);
+    assert_eq!(tape.din(2, 0) as u16, 0xFFFF);
+    assert_eq!(memory.core[0x010F], 0x5555);
+}
@@ -0,0 +1,78 @@
+// Motor reference model: speed ramps toward the steady speed for the drive
+// voltage with the inertia's time constant, the tachometer reads the present
+// speed, and the contactor and at speed bits switch the drive and report it
+
+use rustheon::analog::{AnalogInput, AnalogOutput, ADC_ADDRESS, DAC_ADDRESS};
+use rustheon::cpu::Memory;
+use rustheon::digital::{DigitalIo, DIGITAL_ADDRESS};
+use rustheon::io::IoBus;
+use rustheon::plant::{Motor, Plant, PlantIo, Wiring, STEP_SECONDS};
+
+fn bus() -> IoBus {
+    let mut bus = IoBus::new();
+    bus.attach(ADC_ADDRESS, None, Box::new(AnalogInput::new())).expect("A/D address");
+    bus.attach(DAC_ADDRESS, None, Box::new(AnalogOutput::default())).expect("D/A address");
+    bus.attach(DIGITAL_ADDRESS, None, Box::new(DigitalIo::default())).expect("digital address");
+    bus
+}
+
+// step the motor for the given seconds
+fn run(motor: &mut Motor, bus: &mut IoBus, seconds: f64) {
+    let steps = (seconds / STEP_SECONDS).round() as u32;
+    for _ in 0..steps {
+        motor.step(0.0, STEP_SECONDS, &mut PlantIo::new(bus, Wiring::default()));
+    }
+}
+
+// convert the tachometer channel as a program would
+fn tachometer(bus: &mut IoBus, motor: &Motor) -> i16 {
+    let mut memory = Memory::new();
+    bus.dot(ADC_ADDRESS, motor.tach_channel as i16, 0);
+    bus.service(1_000, &mut memory);
+    bus.din(ADC_ADDRESS, 1_000)
+}
+
+#[test]
+fn speed_ramps_with_the_inertia_time_constant() {
+    let mut motor = Motor::new();
+    let mut bus = bus();
+    bus.dot(DAC_ADDRESS + motor.drive_channel, 0x4000, 0);      // 5 V
+    bus.dot(DIGITAL_ADDRESS, 1 << motor.contactor_bit.1, 0);
+    let steady = motor.steady_speed(5.0);
+    assert!((steady - 100.0).abs() < 1e-9);
+
+    let tau = motor.inertia / motor.friction;
+    run(&mut motor, &mut bus, tau);
+    let expected = steady * (1.0 - (-1.0f64).exp());
+    assert!((motor.speed - expected).abs() < 0.5, "speed {:.2} after one time constant", motor.speed);
+    assert!(motor.angle > 0.0 && motor.angle < steady * tau);
+
+    run(&mut motor, &mut bus, 9.0 * tau);
+    assert!((motor.speed - steady).abs() < 0.1, "speed {:.2} has not settled", motor.speed);
+    let tach = tachometer(&mut bus, &motor);                    // half of full speed reads 5 V
+    assert!((0x4000 - tach).abs() <= 0x0010, "tachometer {:04X}", tach);
+}
+
+#[test]
+fn contactor_switches_the_drive_and_at_speed_follows() {
+    let mut motor = Motor::new();
+    let mut bus = bus();
+    bus.dot(DAC_ADDRESS + motor.drive_channel, 0x7FFF, 0);      // full drive
+    run(&mut motor, &mut bus, 1.0);
+    assert_eq!(motor.speed, 0.0, "the drive is off with the contactor open");
+    assert_eq!(tachometer(&mut bus, &motor), 0);
+
+    bus.dot(DIGITAL_ADDRESS, 1 << motor.contactor_bit.1, 0);
+    run(&mut motor, &mut bus, 10.0);
+    let at_speed = 1 << motor.at_speed_bit.1;
+    assert_eq!(bus.din(DIGITAL_ADDRESS, 0) & at_speed, 0, "speed {:.1}", motor.speed);
+    run(&mut motor, &mut bus, 10.0);
+    assert_eq!(bus.din(DIGITAL_ADDRESS, 0) & at_speed, at_speed, "speed {:.1}", motor.speed);
+    assert!(tachometer(&mut bus, &motor) > 0x7800);
+
+    bus.dot(DIGITAL_ADDRESS, 0, 0);                             // coast down
+    let before = motor.speed;
+    run(&mut motor, &mut bus, 1.0);
+    assert!(motor.speed < before && motor.speed > 0.0);
+    assert_eq!(bus.din(DIGITAL_ADDRESS, 0) & at_speed, 0);
+}
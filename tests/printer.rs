@@ -0,0 +1,88 @@
+// Line printer: carriage control spacing and overprint, skips to carriage
+// tape channels with a form feed at top of form, and output to a Memory sink
+
+use rustheon::cpu::Memory;
+use rustheon::io::Device;
+use rustheon::printer::{LinePrinter, Sink, BUSY, COLUMNS, FORM_LINES, LINE_FULL, READY,
+                        SKIP_TO_CHANNEL, TOP_OF_FORM};
+
+// load text into the line buffer and print it with a carriage command,
+// returning the time the printer is ready again
+fn print(printer: &mut LinePrinter, now: u64, text: &str, command: i16) -> u64 {
+    for ch in text.bytes() {
+        printer.dot(0, ch as i16, now);
+    }
+    printer.dot(1, command, now);
+    let mut now = now;
+    while printer.din(0, now) & BUSY != 0 {
+        now += 1_000;
+    }
+    printer.service(now, &mut Memory::new());
+    now
+}
+
+fn output(printer: &LinePrinter) -> String {
+    String::from_utf8(printer.output().expect("memory sink").to_vec()).unwrap()
+}
+
+#[test]
+fn carriage_control_spaces_and_overprints() {
+    let mut printer = LinePrinter::new(Sink::Memory(Vec::new()));
+    assert_eq!(printer.din(0, 0), READY | TOP_OF_FORM);
+    let now = print(&mut printer, 0, "ONE  ", 1);
+    assert_eq!(printer.din(0, now) & TOP_OF_FORM, 0);
+    let now = print(&mut printer, now, "TWO", 0);               // overprinted by the next line
+    let now = print(&mut printer, now, "___", 3);
+    print(&mut printer, now, "\u{7}X", 1);                     // control characters print as spaces
+    assert_eq!(output(&printer), "ONE\nTWO\r___\n\n\n X\n");
+}
+
+#[test]
+fn printing_takes_a_line_time_plus_slew() {
+    let mut printer = LinePrinter::new(Sink::Memory(Vec::new()));
+    printer.dot(1, 1, 0);
+    assert_eq!(printer.din(0, printer.line_cycles - 1) & BUSY, BUSY);
+    printer.dot(1, 1, printer.line_cycles - 1);                 // lost while busy
+    assert_eq!(printer.din(0, printer.line_cycles) & READY, READY);
+    let start = printer.line_cycles;
+    printer.dot(1, 5, start);
+    let ready = start + printer.line_cycles + 4 * printer.slew_cycles;
+    assert_eq!(printer.din(0, ready - 1) & BUSY, BUSY);
+    assert_eq!(printer.din(0, ready) & READY, READY);
+    assert_eq!(output(&printer), "\n\n\n\n\n\n");
+}
+
+#[test]
+fn skips_to_channels_and_feeds_the_form() {
+    let mut printer = LinePrinter::new(Sink::Memory(Vec::new()));
+    let now = print(&mut printer, 0, "HEAD", 2);
+    let now = print(&mut printer, now, "FOOT", SKIP_TO_CHANNEL | 12);
+    let blank = FORM_LINES - 6 - 2;                             // channel 12 is six lines from the bottom
+    assert_eq!(output(&printer), format!("HEAD\n\nFOOT{}", "\n".repeat(blank)));
+    assert_eq!(printer.din(0, now) & TOP_OF_FORM, 0);
+
+    let now = print(&mut printer, now, "LAST", SKIP_TO_CHANNEL | 1);
+    assert!(output(&printer).ends_with("LAST\n\x0C"));
+    assert_eq!(printer.din(0, now) & TOP_OF_FORM, TOP_OF_FORM);
+
+    print(&mut printer, now, "", SKIP_TO_CHANNEL | 5);          // no punch in channel 5, one line
+    assert!(output(&printer).ends_with("\x0C\n"));
+}
+
+#[test]
+fn custom_form_and_a_full_line() {
+    let mut printer = LinePrinter::new(Sink::Memory(Vec::new()));
+    printer.set_form(4, &[(1, 0), (3, 2)]);
+    let now = print(&mut printer, 0, "A", SKIP_TO_CHANNEL | 3);
+    let now = print(&mut printer, now, "B", SKIP_TO_CHANNEL | 1);
+    assert_eq!(output(&printer), "A\n\nB\n\x0C");
+    assert_eq!(printer.din(0, now) & TOP_OF_FORM, TOP_OF_FORM);
+
+    let long = "X".repeat(COLUMNS + 8);
+    for ch in long.bytes() {
+        printer.dot(0, ch as i16, now);
+    }
+    assert_eq!(printer.din(0, now) & LINE_FULL, LINE_FULL);
+    print(&mut printer, now, "", 1);
+    assert!(output(&printer).ends_with(&format!("\x0C{}\n", "X".repeat(COLUMNS))));
+}
@@ -0,0 +1,121 @@
+// Profiler: instructions and cycles charged to each address, level and
+// symbol, the ordering of the hot spot and symbol tables, and the report
+
+use rustheon::assembler::assemble;
+use rustheon::cpu::{Cpu, Memory, Mode};
+use rustheon::profile::{HotSpot, Profiler, SymbolTime, BASE_LEVEL};
+use rustheon::symbols::SymbolTable;
+
+const LOOP:&str = "
+ ORG 0x0100
+MAIN CLR
+ STW CNT
+LOOP JSX SUB
+ LDW CNT
+ ADD ONE
+ STW CNT
+ SUB THREE
+ SAZ
+ JMP LOOP
+ HLT
+SUB STX SAVE
+ LDX SAVE
+ JMP 0,X
+CNT DATA 0
+ONE DATA 1
+THREE DATA 3
+SAVE DATA 0
+ END MAIN
+";
+
+#[test]
+fn counts_what_the_cpu_executes() {
+    let assembly = assemble(LOOP).unwrap();
+    let mut cpu = Cpu::new();
+    let mut memory = Memory::new();
+    for (address, word) in &assembly.words {
+        memory.core[*address as usize] = *word as i16;
+    }
+    cpu.profiler = Some(Profiler::new());
+    cpu.set_pcr(0x0100);
+    cpu.mode = Mode::RUN;
+    cpu.run(&mut memory, 1_000);
+    assert_eq!(memory.core[0x010D], 3, "loop ran three times");
+
+    let profiler = cpu.profiler.as_ref().unwrap();
+    assert_eq!(profiler.total_instructions(), cpu.instructions);
+    assert_eq!(profiler.total_cycles(), cpu.cycles);
+    assert_eq!(profiler.level(BASE_LEVEL), (cpu.instructions, cpu.cycles));
+    let counts: Vec<u64> = (0x0100..0x010D).map(|address| profiler.count(address)).collect();
+    assert_eq!(counts, [1, 1, 3, 3, 3, 3, 3, 3, 2, 1, 3, 3, 3]);
+    assert_eq!(profiler.count(0x010D), 0, "data is not executed");
+
+    let symbols = &assembly.symbols;
+    let by_symbol = profiler.by_symbol(symbols);
+    let count = |name: &str| by_symbol.iter().find(|time| time.name == name).map_or(0, |time| time.count);
+    assert_eq!((count("MAIN"), count("LOOP"), count("SUB")), (2, 21, 9));
+    let cycles: u64 = by_symbol.iter().map(|time| time.cycles).sum();
+    assert_eq!(cycles, cpu.cycles);
+}
+
+#[test]
+fn tables_rank_by_cycles_then_address_or_name() {
+    let mut profiler = Profiler::new();
+    for _ in 0..3 {
+        profiler.record(0x0200, 0, 2, None);
+    }
+    profiler.record(0x0101, 0, 3, None);
+    profiler.record(0x0100, 0, 3, None);
+    profiler.record(0x0300, 0x0008, 4, None);                   // at level 3
+    profiler.record(0x0050, 0, 1, None);
+    assert_eq!(profiler.hot_spots(), [
+        HotSpot { address: 0x0200, count: 3, cycles: 6 },
+        HotSpot { address: 0x0300, count: 1, cycles: 4 },
+        HotSpot { address: 0x0100, count: 1, cycles: 3 },       // ties in address order
+        HotSpot { address: 0x0101, count: 1, cycles: 3 },
+        HotSpot { address: 0x0050, count: 1, cycles: 1 },
+    ]);
+    assert_eq!(profiler.level(3), (1, 4));
+    assert_eq!(profiler.level(BASE_LEVEL), (6, 13));
+
+    let mut symbols = SymbolTable::new();
+    symbols.insert("BETA", 0x0100);
+    symbols.insert("ALPHA", 0x0200);
+    symbols.insert("GAMMA", 0x0300);
+    let time = |name: &str, count, cycles| SymbolTime { name: name.to_string(), count, cycles };
+    assert_eq!(profiler.by_symbol(&symbols), [
+        time("ALPHA", 3, 6),
+        time("BETA", 2, 6),                                     // ties in name order
+        time("GAMMA", 1, 4),
+        time("(no symbol)", 1, 1),
+    ]);
+}
+
+#[test]
+fn report_lists_base_then_levels_from_the_highest_and_the_top_entries() {
+    let mut profiler = Profiler::new();
+    profiler.record(0x0100, 0, 6, None);
+    profiler.record(0x0101, 0, 2, None);
+    profiler.record(0x0200, 0x0008, 2, None);
+    profiler.record(0x0201, 0x0001, 0, None);                   // level 0, counted with no cycles
+    let mut symbols = SymbolTable::new();
+    symbols.insert("MAIN", 0x0100);
+    symbols.insert("CLOCK", 0x0200);
+
+    let report = profiler.report(Some(&symbols), 2);
+    assert!(report.starts_with("Profile: 4 instructions, 10 cycles"));
+    let lines: Vec<&str> = report.lines().map(|line| line.trim()).collect();
+    let after = |heading: &str| lines.iter().position(|line| *line == heading).unwrap() + 2;
+    let levels = after("Time by level");
+    assert!(lines[levels].starts_with("base ") && lines[levels + 1].starts_with("int3 "));
+    assert_eq!(lines[levels + 2], "", "levels with no cycles are left out");
+    let spots = after("Hot spots");
+    assert!(lines[spots].starts_with("0100     MAIN ") && lines[spots].ends_with("60.00"));
+    assert!(lines[spots + 1].starts_with("0101     MAIN+1 "));
+    assert_eq!(lines[spots + 2], "", "only the top two");
+    let by_symbol = after("By symbol");
+    assert!(lines[by_symbol].starts_with("MAIN ") && lines[by_symbol].ends_with("80.00"));
+    assert!(lines[by_symbol + 1].starts_with("CLOCK ") && lines[by_symbol + 1].ends_with("20.00"));
+
+    assert!(!profiler.report(None, 10).contains("By symbol"));
+}
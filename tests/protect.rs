@@ -0,0 +1,119 @@
+// Protected core: parsing ranges, and what a halt, log or drop range does to
+// a program store and to the stores of an interrupt entry
+
+use rustheon::cpu::{Cpu, HaltReason, Memory, Mode};
+use rustheon::protect::{ProtectAction, ProtectedRange, Violation};
+
+fn machine(program: &[u16], range: &str) -> (Cpu, Box<Memory>) {
+    let mut cpu = Cpu::new();
+    let mut memory = Box::new(Memory::new());
+    for (i, word) in program.iter().enumerate() {
+        memory.write(0x0100 + i, *word as i16);
+    }
+    memory.write(0x0200, 0x1111);
+    memory.protect(ProtectedRange::parse(range).unwrap());
+    cpu.pcr = 0x0100;
+    cpu.mode = Mode::RUN;
+    (cpu, memory)
+}
+
+fn run(cpu: &mut Cpu, memory: &mut Memory) {
+    while cpu.mode == Mode::RUN && cpu.instructions < 100 {
+        cpu.step(memory);
+    }
+}
+
+// LDW FIVE / STW 0x0200 / HLT / FIVE DATA 5
+const STORE: [u16; 4] = [0x8103, 0x7200, 0x0000, 0x0005];
+
+#[test]
+fn parses_ranges() {
+    assert_eq!(ProtectedRange::parse("0200-02FF").unwrap(), ProtectedRange::new(0x0200, 0x02FF, ProtectAction::Halt));
+    assert_eq!(ProtectedRange::parse("0x0200-0x02ff:log").unwrap(),
+               ProtectedRange::new(0x0200, 0x02FF, ProtectAction::Log));
+    assert_eq!(ProtectedRange::parse("0300:drop").unwrap(), ProtectedRange::new(0x0300, 0x0300, ProtectAction::Drop));
+    assert_eq!(ProtectedRange::parse("7FFF:halt").unwrap(), ProtectedRange::new(0x7FFF, 0x7FFF, ProtectAction::Halt));
+    for bad in ["", "0300:stop", "0400-03FF", "8000", "0200-8000", "ZZ", "0200-", "0200:"] {
+        assert!(ProtectedRange::parse(bad).is_err(), "{}", bad);
+    }
+    let range = ProtectedRange::parse("0200-0201").unwrap();
+    assert!(!range.contains(0x01FF) && range.contains(0x0200) && range.contains(0x0201) && !range.contains(0x0202));
+}
+
+#[test]
+fn halt_stops_before_the_store() {
+    let (mut cpu, mut memory) = machine(&STORE, "0200");
+    run(&mut cpu, &mut memory);
+    assert_eq!(cpu.halt_reason, Some(HaltReason::Protected(0x0200)));
+    assert_eq!(cpu.pcr, 0x0102);
+    assert_eq!(memory.core[0x0200], 0x1111);
+    assert_eq!(memory.protection.as_ref().unwrap().violations,
+               vec![Violation { address: 0x0200, pcr: 0x0101, word: 5, action: ProtectAction::Halt }]);
+}
+
+#[test]
+fn log_reports_and_makes_the_store() {
+    let (mut cpu, mut memory) = machine(&STORE, "01FF-0200:log");
+    run(&mut cpu, &mut memory);
+    assert_eq!(cpu.halt_reason, Some(HaltReason::Instruction));
+    assert_eq!(memory.core[0x0200], 5);
+    assert_eq!(memory.protection.as_ref().unwrap().violations,
+               vec![Violation { address: 0x0200, pcr: 0x0101, word: 5, action: ProtectAction::Log }]);
+}
+
+#[test]
+fn drop_quietly_skips_the_store() {
+    let (mut cpu, mut memory) = machine(&STORE, "0200:drop");
+    run(&mut cpu, &mut memory);
+    assert_eq!(cpu.halt_reason, Some(HaltReason::Instruction));
+    assert_eq!(memory.core[0x0200], 0x1111);
+    assert_eq!(memory.protection.as_ref().unwrap().violations, vec![]);
+}
+
+#[test]
+fn unprotected_stores_are_made() {
+    let (mut cpu, mut memory) = machine(&STORE, "0201-02FF");
+    run(&mut cpu, &mut memory);
+    assert_eq!(cpu.halt_reason, Some(HaltReason::Instruction));
+    assert_eq!(memory.core[0x0200], 5);
+    assert_eq!(memory.protection.as_ref().unwrap().violations, vec![]);
+}
+
+// an interrupt at level 2, saving PCR at 0008 and status at 000A, with range protected
+fn interrupt_with(range: &str) -> (Cpu, Box<Memory>) {
+    let (mut cpu, mut memory) = machine(&[0x0110, 0x1101], range);     // CLR / JMP *
+    memory.write(0x0008, 0x2222);
+    memory.write(0x0009, 0x0300);                                       // linkage
+    memory.write(0x000A, 0x3333);
+    cpu.int_enb = 0x0004;
+    cpu.int_req = 0x0004;
+    cpu.status = 0x0000;
+    cpu.step(&mut memory);
+    (cpu, memory)
+}
+
+#[test]
+fn interrupt_entry_stops_at_a_protected_pcr_save() {
+    let (cpu, memory) = interrupt_with("0008");
+    assert_eq!(cpu.halt_reason, Some(HaltReason::Protected(0x0008)));
+    assert_eq!((cpu.int_act, cpu.int_req, cpu.pcr, cpu.status), (0x0000, 0x0004, 0x0101, 0x0000));
+    assert_eq!((memory.core[0x0008], memory.core[0x000A]), (0x2222, 0x3333));
+    assert_eq!(memory.protection.as_ref().unwrap().violations.len(), 1);
+}
+
+#[test]
+fn interrupt_entry_stops_at_a_protected_status_save() {
+    let (cpu, memory) = interrupt_with("000A");
+    assert_eq!(cpu.halt_reason, Some(HaltReason::Protected(0x000A)));
+    assert_eq!((cpu.int_act, cpu.int_req, cpu.pcr, cpu.status), (0x0000, 0x0004, 0x0101, 0x0000));
+    assert_eq!((memory.core[0x0008], memory.core[0x000A]), (0x0101, 0x3333));
+}
+
+#[test]
+fn interrupt_entry_is_made_past_a_logged_save() {
+    let (cpu, memory) = interrupt_with("0008-000A:log");
+    assert_eq!(cpu.mode, Mode::RUN);
+    assert_eq!((cpu.int_act, cpu.int_req, cpu.pcr), (0x0004, 0x0000, 0x0300));
+    assert_eq!((memory.core[0x0008], memory.core[0x000A]), (0x0101, 0x0000));
+    assert_eq!(memory.protection.as_ref().unwrap().violations.len(), 2);
+}
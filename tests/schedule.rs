@@ -0,0 +1,62 @@
+// Scheduled interrupts: requests due at the same boundary fire in the order
+// of their due time and then of scheduling, periodic requests repeat and fire
+// once for a late boundary, and an instruction count request is entered at
+// the boundary after that many instructions
+
+use rustheon::cpu::{Cpu, Memory, Mode};
+use rustheon::schedule::{Firing, InterruptScheduler};
+
+fn levels(scheduler: &InterruptScheduler) -> Vec<u8> {
+    scheduler.history.iter().map(|firing| firing.level).collect()
+}
+
+#[test]
+fn requests_due_together_fire_in_a_fixed_order() {
+    let mut scheduler = InterruptScheduler::new();
+    scheduler.at_cycle(3, 10);
+    scheduler.at_instruction(7, 2);
+    scheduler.at_cycle(5, 5);
+    scheduler.at_cycle(1, 5);
+    assert_eq!(scheduler.due(4, 1), 0);
+    assert_eq!(scheduler.due(20, 5), 0x0001 << 3 | 0x0001 << 7 | 0x0001 << 5 | 0x0001 << 1);
+    assert_eq!(levels(&scheduler), [5, 1, 3, 7]);       // cycle 5 in turn, cycle 10, then the count
+    assert!(scheduler.is_idle());
+}
+
+#[test]
+fn every_m_cycles_repeats() {
+    let mut scheduler = InterruptScheduler::new();
+    let id = scheduler.every_cycles(4, 100, 50);
+    let fired: Vec<bool> = [99, 100, 120, 150, 199, 260, 299, 300]
+        .iter().map(|cycle| scheduler.due(*cycle, 0) != 0).collect();
+    assert_eq!(fired, [false, true, false, true, false, true, false, true]);   // 260 is late for 200 and 250
+    assert_eq!(scheduler.history.iter().map(|firing| firing.cycle).collect::<Vec<_>>(), [100, 150, 260, 300]);
+    assert!(scheduler.cancel(id));
+    assert_eq!(scheduler.due(1_000, 0), 0);
+}
+
+#[test]
+fn every_n_instructions_repeats() {
+    let mut scheduler = InterruptScheduler::new();
+    scheduler.every_instructions(2, 3, 3);
+    let fired: Vec<u64> = (0..10).filter(|count| scheduler.due(0, *count) != 0).collect();
+    assert_eq!(fired, [3, 6, 9]);
+}
+
+#[test]
+fn instruction_count_request_is_entered_after_that_many() {
+    let mut cpu = Cpu::new();
+    let mut memory = Memory::new();
+    for word in &mut memory.core[0x0100..0x0110] {
+        *word = 0x0110;                                     // CLR
+    }
+    memory.core[13] = 0x0200;                               // level 3 handler, a HLT
+    cpu.int_enb = 0x0001 << 3;
+    cpu.scheduler.at_instruction(3, 3);
+    cpu.set_pcr(0x0100);
+    cpu.mode = Mode::RUN;
+    cpu.run(&mut memory, 100);
+    assert_eq!(cpu.pcr, 0x0201);
+    assert_eq!(memory.core[12], 0x0103);                    // entered after the third CLR
+    assert_eq!(cpu.scheduler.history, [Firing { id: 1, level: 3, cycle: 3, instruction: 3 }]);
+}
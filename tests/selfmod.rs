@@ -0,0 +1,68 @@
+// Self-modifying code: stores into words fetched as instructions are
+// recorded with the old and new words and summed up by location
+
+use rustheon::assembler::{assemble, Assembly};
+use rustheon::cpu::{Cpu, Memory, Mode};
+use rustheon::selfmod::CodeStore;
+
+const PATCHED: &str = "
+        ORG     0x0100
+START   JMP     PATCH
+BACK    LDW     NEWJ
+        STW     PATCH           ; into an instruction already run
+        STW     PATCH           ; the same word again
+        STW     WORD            ; into a word never fetched
+        JMP     PATCH
+PATCH   JMP     BACK            ; becomes JMP DONE
+DONE    HLT
+NEWJ    JMP     DONE
+WORD    DATA    0
+        END     START
+";
+
+fn run(source: &str) -> (Assembly, Box<Memory>) {
+    let assembly = assemble(source).unwrap();
+    let mut cpu = Cpu::new();
+    let mut memory = Box::new(Memory::new());
+    memory.watch_code();
+    memory.code_watch.as_mut().unwrap().echo = false;
+    cpu.pcr = assembly.load(&mut memory).unwrap();
+    cpu.mode = Mode::RUN;
+    while cpu.mode == Mode::RUN && cpu.instructions < 100 {
+        cpu.step(&mut memory);
+    }
+    assert_eq!(cpu.mode, Mode::HALT);
+    (assembly, memory)
+}
+
+#[test]
+fn records_stores_into_fetched_words() {
+    let (_, memory) = run(PATCHED);
+    let watch = memory.code_watch.as_ref().unwrap();
+    assert_eq!(watch.stores, vec![
+        CodeStore { address: 0x0106, pcr: 0x0102, old: 0x1101, new: 0x1107 },
+        CodeStore { address: 0x0106, pcr: 0x0103, old: 0x1107, new: 0x1107 },
+    ]);
+    assert_eq!(watch.stores[0].to_string(), "Store at PCR 0102 modifies 0106: JMP   0x0101 -> JMP   0x0107");
+    assert!(watch.is_code(0x0106) && watch.is_code(0x0107) && !watch.is_code(0x0108) && !watch.is_code(0x0109));
+}
+
+#[test]
+fn summarizes_by_location() {
+    let (assembly, memory) = run(PATCHED);
+    let watch = memory.code_watch.as_ref().unwrap();
+    assert_eq!(watch.summary(Some(&assembly.symbols)),
+               "2 stores into instructions at 1 location\n    \
+                0106 PATCH            2 stores by 0102 BACK+1, 0103 BACK+2\n");
+    assert_eq!(watch.summary(None),
+               "2 stores into instructions at 1 location\n    \
+                0106                  2 stores by 0102, 0103\n");
+}
+
+#[test]
+fn summary_of_a_run_without_stores() {
+    let (_, memory) = run(" ORG 0x0100\nSTART LDW 0x0100\n STW WORD\n HLT\nWORD DATA 0\n END START\n");
+    let watch = memory.code_watch.as_ref().unwrap();
+    assert!(watch.stores.is_empty());
+    assert_eq!(watch.summary(None), "0 stores into instructions at 0 locations\n");
+}
@@ -0,0 +1,71 @@
+// Uninitialized core: a word never written is reported once, on its first
+// read as an operand or an instruction, and never once it has been written
+
+use rustheon::cpu::{Cpu, Memory, Mode};
+use rustheon::shadow::UninitializedRead;
+
+fn machine(program: &[u16]) -> (Cpu, Box<Memory>) {
+    let mut cpu = Cpu::new();
+    let mut memory = Box::new(Memory::new());
+    memory.track_writes();
+    memory.shadow.as_mut().unwrap().echo = false;
+    for (i, word) in program.iter().enumerate() {
+        memory.write(0x0100 + i, *word as i16);
+    }
+    cpu.pcr = 0x0100;
+    cpu.mode = Mode::RUN;
+    (cpu, memory)
+}
+
+fn run(cpu: &mut Cpu, memory: &mut Memory) {
+    while cpu.mode == Mode::RUN && cpu.instructions < 100 {
+        cpu.step(memory);
+    }
+}
+
+#[test]
+fn reports_first_operand_read_and_fetch_once() {
+    let (mut cpu, mut memory) = machine(&[
+        0x8200,                                         // 0100  LDW 0x0200   never written
+        0x8200,                                         // 0101  LDW 0x0200   already reported
+        0x7201,                                         // 0102  STW 0x0201
+        0x8201,                                         // 0103  LDW 0x0201   written by the store
+        0x1300,                                         // 0104  JMP 0x0300   never written, a HLT
+    ]);
+    run(&mut cpu, &mut memory);
+    assert_eq!(cpu.mode, Mode::HALT);
+    assert_eq!(memory.shadow.as_ref().unwrap().reads, vec![
+        UninitializedRead { address: 0x0200, pcr: 0x0100, fetch: false },
+        UninitializedRead { address: 0x0300, pcr: 0x0300, fetch: true },
+    ]);
+    assert_eq!(memory.shadow.as_ref().unwrap().reads[0].to_string(), "Read of uninitialized core 0200 at PCR 0100");
+    assert_eq!(memory.shadow.as_ref().unwrap().reads[1].to_string(), "Fetch from uninitialized core 0300 at PCR 0300");
+}
+
+#[test]
+fn written_words_are_not_reported() {
+    let (mut cpu, mut memory) = machine(&[
+        0x8200,                                         // 0100  LDW 0x0200
+        0x8280,                                         // 0101  LDW 0x0280
+        0x8281,                                         // 0102  LDW 0x0281
+        0x1300,                                         // 0103  JMP 0x0300
+    ]);
+    memory.write(0x0200, 5);
+    memory.write_block(0x0280, &[1, 2]);
+    memory.write(0x0300, 0);                            // HLT
+    run(&mut cpu, &mut memory);
+    assert_eq!(cpu.mode, Mode::HALT);
+    assert_eq!(memory.shadow.as_ref().unwrap().reads, vec![]);
+}
+
+#[test]
+fn a_word_read_then_fetched_is_reported_once() {
+    let mut memory = Memory::new();
+    memory.track_writes();
+    memory.shadow.as_mut().unwrap().echo = false;
+    assert!(!memory.shadow.as_ref().unwrap().is_written(0x0200));
+    memory.read(0x0200, 0x0100, false);
+    memory.read(0x0200, 0x0101, true);
+    assert_eq!(memory.shadow.as_ref().unwrap().reads,
+               vec![UninitializedRead { address: 0x0200, pcr: 0x0100, fetch: false }]);
+}
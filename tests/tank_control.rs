@@ -0,0 +1,73 @@
+// Closed loop level control of the reference tank model
+//
+// A 703 proportional controller with bias reads the tank level on A/D
+// channel 0 and sets the inlet valve on D/A channel 0, holding the level at
+// half of full scale.  The test fails if the program stops or the level has
+// not settled at the setpoint after two minutes of emulated time.
+
+use rustheon::analog::{AnalogInput, AnalogOutput, ADC_ADDRESS, DAC_ADDRESS};
+use rustheon::cpu::{Cpu, Memory, Mode};
+use rustheon::plant::{Simulation, TankLevel};
+
+const ORIGIN:usize = 0x0100;
+const SETPOINT:f64 = 2.0;                       // metres, 0x4000 in the program
+const TOLERANCE:f64 = 0.04;
+
+const PROGRAM:[u16; WORDS] = [
+    0x0110,              // 0100  LOOP   CLR
+    0x0320,              // 0101         DOT ADC
+    0x0221,              // 0102  WAIT   DIN ADC+1
+    0x0820,              // 0103         SAM
+    0x1102,              // 0104         JMP WAIT
+    0x0220,              // 0105         DIN ADC
+    0x711F,              // 0106         STW PV
+    0x811C,              // 0107         LDW SP
+    0xB11F,              // 0108         SUB PV
+    0x7120,              // 0109         STW ERR
+    0x0913,              // 010A         SLA 3
+    0x08A0,              // 010B         SNO
+    0x1115,              // 010C         JMP CLAMP
+    0xA11D,              // 010D         ADD BIAS
+    0x08A0,              // 010E         SNO
+    0x1115,              // 010F         JMP CLAMP
+    0x0820,              // 0110         SAM
+    0x1113,              // 0111         JMP SEND
+    0x0110,              // 0112         CLR
+    0x0328,              // 0113  SEND   DOT DAC
+    0x1100,              // 0114         JMP LOOP
+    0x8120,              // 0115  CLAMP  LDW ERR
+    0x0820,              // 0116         SAM
+    0x111A,              // 0117         JMP FULL
+    0x0110,              // 0118         CLR
+    0x1113,              // 0119         JMP SEND
+    0x811E,              // 011A  FULL   LDW MAX
+    0x1113,              // 011B         JMP SEND
+    0x4000,              // 011C  SP     DATA 0x4000
+    0x5555,              // 011D  BIAS   DATA 0x5555
+    0x7FFF,              // 011E  MAX    DATA 0x7FFF
+    0x0000,              // 011F  PV     DATA 0
+    0x0000,              // 0120  ERR    DATA 0
+];
+const WORDS:usize = 33;
+
+#[test]
+fn level_settles_at_setpoint() {
+    let mut cpu = Cpu::new();
+    let mut memory = Memory::new();
+    for (i, word) in PROGRAM.iter().enumerate() {
+        memory.core[ORIGIN + i] = *word as i16;
+    }
+    cpu.io.attach(ADC_ADDRESS, None, Box::new(AnalogInput::new())).expect("A/D address");
+    cpu.io.attach(DAC_ADDRESS, None, Box::new(AnalogOutput::default())).expect("D/A address");
+    let mut simulation = Simulation::new(TankLevel::new());
+    cpu.pcr = ORIGIN as u16;
+    cpu.mode = Mode::RUN;
+
+    for _ in 0..12 {
+        simulation.run(&mut cpu, &mut memory, 10.0);
+        assert_eq!(cpu.mode, Mode::RUN, "program stopped at PCR {:04X}", cpu.pcr);
+    }
+    let level = simulation.plant.level;
+    assert!((level - SETPOINT).abs() <= TOLERANCE,
+            "level {:.3} m is not within {} m of the {} m setpoint", level, TOLERANCE, SETPOINT);
+}
@@ -0,0 +1,105 @@
+// Value Change Dump: the header's timescale and signals, initial values and
+// then only changes at nanosecond times, and busy lines that follow the bus
+// address of each device rather than the order they were attached in
+
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+use rustheon::io::DeviceInfo;
+use rustheon::vcd::{VcdSample, VcdWriter};
+
+#[derive(Clone, Default)]
+struct Shared(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Shared {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(bytes);
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn device(base: u8, name: &str) -> DeviceInfo {
+    DeviceInfo { base, count: 1, level: None, name: name.to_string(), busy: false }
+}
+
+fn sample(cycle: u64, int_req: u16, pcr: u16, busy: &[(u8, bool)]) -> VcdSample<'_> {
+    VcdSample { cycle, int_req, int_act: 0, int_enb: 0, int_masked: false, pcr, acr: -1, busy }
+}
+
+// dump three samples from a writer declaring devices in the given order
+fn dump(devices: &[DeviceInfo], busy: [&[(u8, bool)]; 3]) -> String {
+    let out = Shared::default();
+    let mut vcd = VcdWriter::new(Box::new(out.clone()), devices).unwrap();
+    vcd.sample(&sample(0, 0, 0x0100, busy[0]));
+    vcd.sample(&sample(2, 0x0004, 0x0102, busy[1]));
+    vcd.sample(&sample(3, 0x0004, 0x0102, busy[2]));           // nothing changed
+    assert!(vcd.error.is_none());
+    drop(vcd);
+    let bytes = out.0.borrow().clone();
+    String::from_utf8(bytes).unwrap()
+}
+
+// identifier code declared for a signal name
+fn code<'a>(text: &'a str, name: &str) -> &'a str {
+    text.lines()
+        .find(|line| line.ends_with(&format!(" {} $end", name)) || line.contains(&format!(" {} [15:0]", name)))
+        .and_then(|line| line.split(' ').nth(3))
+        .unwrap_or_else(|| panic!("no signal {}", name))
+}
+
+// the value changes after the header, one string for each time
+fn times(text: &str) -> Vec<String> {
+    let (_, body) = text.split_once("$enddefinitions $end\n").unwrap();
+    let mut times: Vec<String> = Vec::new();
+    for line in body.lines() {
+        if line.starts_with('#') {
+            times.push(String::new());
+        }
+        let time = times.last_mut().expect("a time first");
+        time.push_str(line);
+        time.push('\n');
+    }
+    times
+}
+
+#[test]
+fn dump_declares_signals_and_writes_changes_in_nanoseconds() {
+    let devices = [device(0x08, "line printer"), device(0x18, "disk")];
+    let text = dump(&devices, [&[(0x08, false), (0x18, true)], &[(0x08, true), (0x18, true)], &[(0x08, true), (0x18, true)]]);
+    assert!(text.contains("$timescale 1 ns $end"));
+    assert!(text.contains("$comment one memory cycle is 1750 ns $end"));
+    let (req_2, pcr, acr) = (code(&text, "req_2"), code(&text, "pcr"), code(&text, "acr"));
+    let (printer, disk) = (code(&text, "busy_08_line_printer"), code(&text, "busy_18_disk"));
+
+    let mut times = times(&text).into_iter();
+    let first = times.next().unwrap();
+    assert!(first.starts_with("#0\n$dumpvars\n") && first.ends_with("$end\n"));
+    assert!(first.contains(&format!("\n0{}\n", req_2)));
+    assert!(first.contains(&format!("\nb0000000100000000 {}\n", pcr)));
+    assert!(first.contains(&format!("\nb1111111111111111 {}\n", acr)));
+    assert!(first.contains(&format!("\n0{}\n", printer)));
+    assert!(first.contains(&format!("\n1{}\n", disk)));
+
+    let second = times.next().unwrap();                         // cycle 2, only what changed
+    assert_eq!(second, format!("#3500\n1{}\nb0000000100000010 {}\n1{}\n", req_2, pcr, printer));
+    assert_eq!(times.next(), None, "no time is written when nothing changed");
+}
+
+#[test]
+fn busy_lines_follow_the_bus_address() {
+    let printer = device(0x08, "line printer");
+    let disk = device(0x18, "disk");
+    let in_order = dump(&[printer.clone(), disk.clone()],
+                        [&[(0x08, true), (0x18, false)], &[(0x08, false), (0x18, true)], &[(0x08, false), (0x18, true)]]);
+    let reversed = dump(&[disk, printer],
+                        [&[(0x18, false), (0x08, true)], &[(0x18, true), (0x08, false)], &[(0x18, true), (0x08, false)]]);
+    assert_eq!(in_order, reversed);
+
+    let missing = dump(&[device(0x20, "analog input")], [&[], &[(0x21, true)], &[]]);
+    assert!(!missing.contains(&format!("1{}\n", code(&missing, "busy_20_analog_input"))));
+}